@@ -1,8 +1,17 @@
-// $ rustup run nightly cargo bench
+// $ rustup run nightly cargo bench --features nightly
+// (add `--features hazard` to compare with `HazardBackend`,
+//  `--features sharded` to compare with `ShardedBackend`,
+//  and `--features epoch` to compare with `EpochBackend`)
 #![feature(test)]
 extern crate atomic_immut;
 extern crate test;
 
+#[cfg(feature = "epoch")]
+use atomic_immut::backend::EpochBackend;
+#[cfg(feature = "hazard")]
+use atomic_immut::backend::HazardBackend;
+#[cfg(feature = "sharded")]
+use atomic_immut::backend::ShardedBackend;
 use atomic_immut::AtomicImmut;
 use std::sync::{Arc, Barrier};
 use std::thread;
@@ -130,3 +139,174 @@ fn multi_thread_store_and_load_std(b: &mut Bencher) {
     assert_eq!(Arc::strong_count(&v1.load()), 2);
     assert_eq!(*v1.load(), 1);
 }
+
+#[cfg(feature = "hazard")]
+#[bench]
+fn single_thread_load_hazard(b: &mut Bencher) {
+    let v = AtomicImmut::with_backend(vec![0, 1, 2], HazardBackend::new());
+    b.iter(|| {
+        test::black_box(v.load());
+    });
+}
+
+#[cfg(feature = "hazard")]
+#[bench]
+fn multi_thread_load_hazard(b: &mut Bencher) {
+    let v = Arc::new(AtomicImmut::with_backend(
+        vec![0, 1, 2],
+        HazardBackend::new(),
+    ));
+    let thread_count = 8;
+    let barrier = Arc::new(Barrier::new(thread_count));
+    for _ in 0..thread_count {
+        let v = Arc::clone(&v);
+        let barrier = Arc::clone(&barrier);
+        thread::spawn(move || {
+            while !v.load().is_empty() {}
+            barrier.wait();
+        });
+    }
+    thread::sleep(Duration::from_millis(10));
+    b.iter(|| {
+        test::black_box(v.load());
+    });
+    v.store(vec![]);
+    barrier.wait();
+    assert_eq!(Arc::strong_count(&v.load()), 2);
+}
+
+#[cfg(feature = "hazard")]
+#[bench]
+fn multi_thread_store_and_load_hazard(b: &mut Bencher) {
+    let v0 = Arc::new(AtomicImmut::with_backend(
+        vec![0, 1, 2],
+        HazardBackend::new(),
+    ));
+    let v1 = Arc::new(AtomicImmut::with_backend(0, HazardBackend::new()));
+    let thread_count = 4;
+    let barrier = Arc::new(Barrier::new(thread_count));
+    for _ in 0..thread_count {
+        let v0 = Arc::clone(&v0);
+        let v1 = Arc::clone(&v1);
+        let barrier = Arc::clone(&barrier);
+        thread::spawn(move || {
+            while !v0.load().is_empty() {
+                v1.store(1);
+            }
+            barrier.wait();
+        });
+    }
+    thread::sleep(Duration::from_millis(10));
+    b.iter(|| {
+        test::black_box(v0.load());
+        test::black_box(v1.load());
+    });
+    v0.store(vec![]);
+    barrier.wait();
+    assert_eq!(Arc::strong_count(&v0.load()), 2);
+    assert_eq!(Arc::strong_count(&v1.load()), 2);
+    assert_eq!(*v1.load(), 1);
+}
+
+#[cfg(feature = "sharded")]
+#[bench]
+fn single_thread_load_sharded(b: &mut Bencher) {
+    let v = AtomicImmut::with_backend(vec![0, 1, 2], ShardedBackend::new());
+    b.iter(|| {
+        test::black_box(v.load());
+    });
+}
+
+#[cfg(feature = "sharded")]
+#[bench]
+fn multi_thread_load_sharded(b: &mut Bencher) {
+    let v = Arc::new(AtomicImmut::with_backend(
+        vec![0, 1, 2],
+        ShardedBackend::new(),
+    ));
+    let thread_count = 8;
+    let barrier = Arc::new(Barrier::new(thread_count));
+    for _ in 0..thread_count {
+        let v = Arc::clone(&v);
+        let barrier = Arc::clone(&barrier);
+        thread::spawn(move || {
+            while !v.load().is_empty() {}
+            barrier.wait();
+        });
+    }
+    thread::sleep(Duration::from_millis(10));
+    b.iter(|| {
+        test::black_box(v.load());
+    });
+    v.store(vec![]);
+    barrier.wait();
+    assert_eq!(Arc::strong_count(&v.load()), 2);
+}
+
+#[cfg(feature = "sharded")]
+#[bench]
+fn multi_thread_store_and_load_sharded(b: &mut Bencher) {
+    let v0 = Arc::new(AtomicImmut::with_backend(
+        vec![0, 1, 2],
+        ShardedBackend::new(),
+    ));
+    let v1 = Arc::new(AtomicImmut::with_backend(0, ShardedBackend::new()));
+    let thread_count = 4;
+    let barrier = Arc::new(Barrier::new(thread_count));
+    for _ in 0..thread_count {
+        let v0 = Arc::clone(&v0);
+        let v1 = Arc::clone(&v1);
+        let barrier = Arc::clone(&barrier);
+        thread::spawn(move || {
+            while !v0.load().is_empty() {
+                v1.store(1);
+            }
+            barrier.wait();
+        });
+    }
+    thread::sleep(Duration::from_millis(10));
+    b.iter(|| {
+        test::black_box(v0.load());
+        test::black_box(v1.load());
+    });
+    v0.store(vec![]);
+    barrier.wait();
+    assert_eq!(Arc::strong_count(&v0.load()), 2);
+    assert_eq!(Arc::strong_count(&v1.load()), 2);
+    assert_eq!(*v1.load(), 1);
+}
+
+#[cfg(feature = "epoch")]
+#[bench]
+fn single_thread_load_epoch(b: &mut Bencher) {
+    let v = AtomicImmut::with_backend(vec![0, 1, 2], EpochBackend::new());
+    b.iter(|| {
+        test::black_box(v.load());
+    });
+}
+
+#[cfg(feature = "epoch")]
+#[bench]
+fn multi_thread_load_epoch(b: &mut Bencher) {
+    let v = Arc::new(AtomicImmut::with_backend(
+        vec![0, 1, 2],
+        EpochBackend::new(),
+    ));
+    let thread_count = 8;
+    let barrier = Arc::new(Barrier::new(thread_count));
+    for _ in 0..thread_count {
+        let v = Arc::clone(&v);
+        let barrier = Arc::clone(&barrier);
+        thread::spawn(move || {
+            while !v.load().is_empty() {}
+            barrier.wait();
+        });
+    }
+    thread::sleep(Duration::from_millis(10));
+    b.iter(|| {
+        test::black_box(v.load());
+    });
+    v.store(vec![]);
+    barrier.wait();
+    assert_eq!(Arc::strong_count(&v.load()), 2);
+}
@@ -26,6 +26,7 @@ impl<T> StdAtomicImmut<T> {
     pub fn swap(&self, value: T) -> Arc<T> {
         let new = to_arc_ptr(value);
         let old = {
+            #[allow(clippy::readonly_write_lock)]
             let ptr = self.rwlock.write().unwrap();
             ptr.swap(new, Ordering::SeqCst)
         };
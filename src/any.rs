@@ -0,0 +1,172 @@
+use core::any::Any;
+use core::fmt;
+use primitive::Arc;
+
+use backend::{Backend, DefaultBackend};
+use error::TypeMismatch;
+use AtomicImmut;
+
+/// The type of the values held by `AtomicImmutAny`.
+pub type AnyValue = dyn Any + Send + Sync;
+
+/// A type-erased `AtomicImmut` which can hold a value of any type.
+///
+/// This is handy for heterogeneous collections of shared values:
+/// the stored type may even change over time, and readers downcast the value to the type they expect.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutAny;
+///
+/// let value = AtomicImmutAny::new(5u32);
+/// assert_eq!(value.load_as::<u32>().map(|v| *v), Some(5));
+/// assert!(value.load_as::<String>().is_none());
+///
+/// value.store("foo".to_owned());
+/// assert_eq!(value.load_as::<String>().map(|v| v.len()), Some(3));
+/// assert!(!value.is::<u32>());
+/// ```
+pub struct AtomicImmutAny<B = DefaultBackend> {
+    inner: AtomicImmut<AnyValue, B>,
+}
+impl AtomicImmutAny {
+    /// Makes a new `AtomicImmutAny` instance.
+    pub fn new<T: Any + Send + Sync>(value: T) -> Self {
+        Self::with_backend(value, DefaultBackend::new())
+    }
+}
+impl<B: Backend> AtomicImmutAny<B> {
+    /// Makes a new `AtomicImmutAny` instance which uses the given synchronization backend.
+    pub fn with_backend<T: Any + Send + Sync>(value: T, backend: B) -> Self {
+        AtomicImmutAny {
+            inner: AtomicImmut::from_arc_with_backend(dyn_arc!(value), backend),
+        }
+    }
+
+    /// Loads the type-erased value of this pointer.
+    pub fn load(&self) -> Arc<AnyValue> {
+        self.inner.load()
+    }
+
+    /// Loads the value of this pointer as `T`.
+    ///
+    /// Returns `None` if the value is not of the type `T`.
+    pub fn load_as<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.try_load_as().ok()
+    }
+
+    /// Loads the value of this pointer as `T`,
+    /// returning a `TypeMismatch` error if the value is not of the type `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutAny;
+    ///
+    /// let value = AtomicImmutAny::new(5u32);
+    /// let e = value.try_load_as::<i32>().unwrap_err();
+    /// assert_eq!(e.expected(), "i32");
+    /// ```
+    pub fn try_load_as<T: Any + Send + Sync>(&self) -> Result<Arc<T>, TypeMismatch> {
+        self.load()
+            .downcast::<T>()
+            .map_err(|_| TypeMismatch::new::<T>())
+    }
+
+    /// Returns `true` if the value of this pointer is of the type `T`.
+    pub fn is<T: Any>(&self) -> bool {
+        self.inner.peek().is::<T>()
+    }
+
+    /// Stores a value of any type into this pointer.
+    pub fn store<T: Any + Send + Sync>(&self, value: T) {
+        self.inner.store_arc(dyn_arc!(value));
+    }
+
+    /// Stores an already shared value of any type into this pointer.
+    ///
+    /// This is unavailable with the `portable-atomic` feature,
+    /// whose `Arc` cannot be converted into `Arc<AnyValue>`.
+    #[cfg(not(feature = "portable-atomic"))]
+    pub fn store_arc<T: Any + Send + Sync>(&self, value: Arc<T>) {
+        self.inner.store_arc(value);
+    }
+
+    /// Updates the value of this pointer, which must be of the type `T`.
+    ///
+    /// Like `AtomicImmut::update`, `f` may be called multiple times on conflicts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutAny;
+    ///
+    /// let value = AtomicImmutAny::new(5u32);
+    /// assert!(value.update_as(|v: &u32| v + 1).is_ok());
+    /// assert_eq!(value.load_as::<u32>().map(|v| *v), Some(6));
+    ///
+    /// assert!(value.update_as(|v: &String| v.clone()).is_err());
+    /// assert_eq!(value.load_as::<u32>().map(|v| *v), Some(6));
+    /// ```
+    pub fn update_as<T, F>(&self, mut f: F) -> Result<(), TypeMismatch>
+    where
+        T: Any + Send + Sync,
+        F: FnMut(&T) -> T,
+    {
+        self.inner
+            .update_core(|v| match v.downcast_ref::<T>() {
+                Some(v) => Ok(dyn_arc!(f(v))),
+                None => Err(TypeMismatch::new::<T>()),
+            })
+            .map(|_| ())
+    }
+
+    /// Returns a reference to the underlying `AtomicImmut`.
+    pub fn as_immut(&self) -> &AtomicImmut<AnyValue, B> {
+        &self.inner
+    }
+}
+impl<B: Backend> fmt::Debug for AtomicImmutAny<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AtomicImmutAny").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn atomic_immut_any_works() {
+        let value = Arc::new(AtomicImmutAny::new(0usize));
+        let handles = (0..4)
+            .map(|_| {
+                let value = Arc::clone(&value);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        value.update_as(|v: &usize| v + 1).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(value.load_as::<usize>().map(|v| *v), Some(400));
+
+        let shared = Arc::new(vec![1, 2]);
+        value.store_arc(Arc::clone(&shared));
+        assert!(Arc::ptr_eq(&value.load_as::<Vec<i32>>().unwrap(), &shared));
+        assert_eq!(
+            value.update_as(|v: &usize| *v),
+            Err(TypeMismatch::new::<usize>())
+        );
+        assert_eq!(
+            TypeMismatch::new::<usize>().to_string(),
+            "The value is not of the type `usize`"
+        );
+    }
+}
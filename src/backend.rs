@@ -0,0 +1,441 @@
+//! Synchronization backends of `AtomicImmut`.
+//!
+//! A backend decides how readers of an `AtomicImmut` are protected against writers
+//! which release the replaced values.
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::AtomicImmut;
+//! use atomic_immut::backend::StdBackend;
+//!
+//! let value = AtomicImmut::with_backend(5, StdBackend::new());
+//! value.store(10);
+//! assert_eq!(*value.load(), 10);
+//! ```
+use core::sync::atomic::Ordering;
+#[cfg(feature = "std")]
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, TryLockError};
+
+use lock::WriterLock;
+use padded::CachePadded;
+use primitive::{self as atomic, AtomicPtr, AtomicUsize};
+
+pub use self::backoff::Backoff;
+#[cfg(feature = "epoch")]
+pub use self::epoch::EpochBackend;
+#[cfg(feature = "hazard")]
+pub use self::hazard::{HazardBackend, HazardGuard};
+#[cfg(feature = "sharded")]
+pub use self::sharded::{ShardedBackend, ShardedGuard};
+pub use self::single::{SingleThreadBackend, SingleThreadGuard};
+
+mod backoff;
+#[cfg(feature = "epoch")]
+mod epoch;
+#[cfg(feature = "hazard")]
+mod hazard;
+#[cfg(feature = "sharded")]
+mod sharded;
+mod single;
+
+/// The backend of `AtomicImmut` (and the related types) when none is specified.
+///
+/// This is `SingleThreadBackend` on `wasm32` targets without the atomics proposal,
+/// which have no threads, and `SpinBackend` otherwise.
+#[cfg(not(all(target_arch = "wasm32", not(target_feature = "atomics"))))]
+pub type DefaultBackend = SpinBackend;
+/// The backend of `AtomicImmut` (and the related types) when none is specified.
+///
+/// This is `SingleThreadBackend` on `wasm32` targets without the atomics proposal,
+/// which have no threads, and `SpinBackend` otherwise.
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+pub type DefaultBackend = SingleThreadBackend;
+
+/// Synchronization backend of `AtomicImmut`.
+///
+/// Writers of an `AtomicImmut` first replace the pointer
+/// (so that new readers never see the old value),
+/// and then call `synchronize` to wait until the old pointer is no longer protected by any reader.
+/// Only after that the reference owned by the `AtomicImmut` is released.
+///
+/// # Memory ordering
+///
+/// Writers replace the pointer with (at least) `Release` ordering,
+/// and readers see the pointed value only through pointers loaded by `protect`.
+/// Neither side issues any other fence,
+/// so the following is up to the backend:
+///
+/// - `protect` loads the pointer with (at least) `Acquire` ordering.
+/// - Announcing a reader and loading the pointer on one side,
+///   and replacing the pointer and checking the announced readers on the other side,
+///   is a store-load pattern which `Acquire`/`Release` alone does not order.
+///   Typically, both `protect` and `synchronize` issue a `SeqCst` fence in between.
+/// - Releasing a guard must happen before `synchronize` returns (e.g., `Release` and `Acquire`),
+///   so that the accesses of the reader to the retired value are finished before it is released.
+///
+/// # Safety
+///
+/// Implementations must guarantee that `synchronize(retired)` does not return
+/// while a guard returned by `protect` along with `retired` is alive.
+/// This includes the memory ordering requirements described above.
+pub unsafe trait Backend {
+    /// A guard which keeps a loaded pointer protected.
+    type Guard<'a>
+    where
+        Self: 'a;
+
+    /// Loads a pointer from `ptr` and protects it until the returned guard is dropped.
+    fn protect<P>(&self, ptr: &AtomicPtr<P>) -> (Self::Guard<'_>, *mut P);
+
+    /// Same as `protect` except that it returns `None` instead of waiting for writers.
+    ///
+    /// The default implementation calls `protect`,
+    /// which is enough for backends whose readers never wait.
+    fn try_protect<P>(&self, ptr: &AtomicPtr<P>) -> Option<(Self::Guard<'_>, *mut P)> {
+        Some(self.protect(ptr))
+    }
+
+    /// Waits until `retired`, which has been removed from the `AtomicPtr`,
+    /// is no longer protected by any guard.
+    fn synchronize<P>(&self, retired: *mut P);
+
+    /// Waits until every guard returned by `protect` before this call is dropped,
+    /// whichever pointer it protects.
+    ///
+    /// The default implementation calls `synchronize` with a null pointer,
+    /// which is enough for backends that wait for all the readers regardless of `retired`.
+    /// Backends that wait only for the readers of `retired` must override this.
+    fn synchronize_all(&self) {
+        self.synchronize(::core::ptr::null_mut::<()>());
+    }
+}
+
+/// The default backend which counts the active readers.
+///
+/// Readers never wait for writers: `protect` only increments a counter.
+/// Writers spin (following the `Backoff` policy) until the counter becomes zero.
+///
+/// To prevent writers from starving under a constant stream of readers,
+/// readers are counted in two phases:
+/// a writer flips the current phase and waits only for the readers of the previous phase,
+/// which no new reader joins, and then does the same for the other phase.
+/// So a writer completes in bounded time as soon as the readers existing at that time finish.
+///
+/// Concurrent grace periods are serialized,
+/// since the phase flips of one writer could otherwise be interleaved with those of another
+/// and leave a reader counted in a phase which neither of them drains.
+#[derive(Debug, Default)]
+pub struct SpinBackend {
+    readers: [CachePadded<AtomicUsize>; 2],
+    phase: CachePadded<AtomicUsize>,
+    sync: WriterLock,
+    backoff: Backoff,
+}
+impl SpinBackend {
+    /// Makes a new `SpinBackend` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a new `SpinBackend` instance which waits for readers following `backoff`.
+    pub fn with_backoff(backoff: Backoff) -> Self {
+        SpinBackend {
+            readers: [
+                CachePadded::new(AtomicUsize::new(0)),
+                CachePadded::new(AtomicUsize::new(0)),
+            ],
+            phase: CachePadded::new(AtomicUsize::new(0)),
+            sync: WriterLock::new(),
+            backoff,
+        }
+    }
+}
+unsafe impl Backend for SpinBackend {
+    type Guard<'a> = SpinGuard<'a>;
+
+    fn protect<P>(&self, ptr: &AtomicPtr<P>) -> (Self::Guard<'_>, *mut P) {
+        // The phase only decides which counter is used, and writers drain both of them,
+        // so it needs no synchronization.
+        let phase = self.phase.load(Ordering::Relaxed) & 1;
+        let readers = self.readers[phase].fetch_add(1, Ordering::Relaxed);
+        if readers > MAX_READERS {
+            // Like `Arc`, far fewer guards can be alive in any sane program,
+            // so this is a leak of guards (e.g., `mem::forget`) rather than a real load.
+            self.readers[phase].fetch_sub(1, Ordering::Relaxed);
+            panic!("Too many readers of a `SpinBackend`");
+        }
+        let guard = SpinGuard {
+            backend: self,
+            phase,
+        };
+
+        // Pairs with the fence in `synchronize`:
+        // either this load sees the new pointer, or the writer sees the increment above.
+        atomic::fence(Ordering::SeqCst);
+        (guard, ptr.load(Ordering::Acquire))
+    }
+
+    fn synchronize<P>(&self, _retired: *mut P) {
+        // Retiring pointers of a cell are not always serialized by its writer lock
+        // (e.g., `retire` runs after the lock is released),
+        // so the two flips below must not be interleaved with those of another writer.
+        let _sync = self.sync.lock();
+        atomic::fence(Ordering::SeqCst);
+
+        // Any reader which may see the retired pointer has been counted before the fence.
+        // Whichever phase it has been counted in,
+        // the phase is drained by one of the following two iterations.
+        for _ in 0..2 {
+            let previous = self.phase.fetch_add(1, Ordering::Relaxed) & 1;
+            let readers = &self.readers[previous];
+            // `Acquire` pairs with the `Release` decrement of `SpinGuard::drop`.
+            self.backoff
+                .wait_until(|| readers.load(Ordering::Acquire) == 0);
+        }
+    }
+}
+
+/// The maximum number of readers counted in a phase of `SpinBackend`.
+///
+/// The counters are full-width `usize`s, so the limit is the same (`isize::MAX`)
+/// as the reference count of `Arc` on every platform.
+const MAX_READERS: usize = isize::MAX as usize;
+
+/// The guard of `SpinBackend`.
+#[derive(Debug)]
+pub struct SpinGuard<'a> {
+    backend: &'a SpinBackend,
+    phase: usize,
+}
+impl<'a> Drop for SpinGuard<'a> {
+    fn drop(&mut self) {
+        self.backend.readers[self.phase].fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// A backend based on `std::sync::RwLock`.
+///
+/// Unlike `SpinBackend`, waiting threads are blocked by the OS instead of spinning,
+/// but readers may wait for writers.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct StdBackend {
+    lock: RwLock<()>,
+}
+#[cfg(feature = "std")]
+impl StdBackend {
+    /// Makes a new `StdBackend` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+#[cfg(feature = "std")]
+unsafe impl Backend for StdBackend {
+    type Guard<'a> = RwLockReadGuard<'a, ()>;
+
+    fn protect<P>(&self, ptr: &AtomicPtr<P>) -> (Self::Guard<'_>, *mut P) {
+        let guard = self.lock.read().unwrap_or_else(PoisonError::into_inner);
+        (guard, ptr.load(Ordering::Acquire))
+    }
+
+    fn try_protect<P>(&self, ptr: &AtomicPtr<P>) -> Option<(Self::Guard<'_>, *mut P)> {
+        let guard = match self.lock.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        Some((guard, ptr.load(Ordering::Acquire)))
+    }
+
+    fn synchronize<P>(&self, _retired: *mut P) {
+        // The lock orders the accesses of readers and writers by itself.
+        let _guard = self.lock.write().unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use super::*;
+    use AtomicImmut;
+
+    fn concurrent_update<B>(v: AtomicImmut<usize, B>)
+    where
+        B: Backend + Send + Sync + 'static,
+    {
+        let v = Arc::new(v);
+        let thread_count = 8;
+        let barrier = Arc::new(Barrier::new(thread_count));
+        let handles = (0..thread_count)
+            .map(|_| {
+                let v = v.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..100 {
+                        v.update(|x| x + 1);
+                        v.load();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*v.load(), thread_count * 100);
+        assert_eq!(Arc::strong_count(&v.load()), 2);
+    }
+
+    #[test]
+    fn spin_backend_works() {
+        concurrent_update(AtomicImmut::with_backend(0, SpinBackend::new()));
+    }
+
+    #[test]
+    fn spin_backend_with_backoff_works() {
+        for &backoff in &[Backoff::new().spin_limit(0), Backoff::new().yielding(false)] {
+            let backend = SpinBackend::with_backoff(backoff);
+            concurrent_update(AtomicImmut::with_backend(0, backend));
+        }
+    }
+
+    #[test]
+    fn spin_backend_does_not_starve_writers() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers = (0..2)
+            .map(|_| {
+                let v = Arc::clone(&v);
+                let stop = Arc::clone(&stop);
+                thread::spawn(move || {
+                    while !stop.load(Ordering::SeqCst) {
+                        // Overlapping guards keep the reader count non-zero.
+                        let _g0 = v.peek();
+                        let _g1 = v.peek();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 0..100 {
+            v.store(i);
+        }
+        stop.store(true, Ordering::SeqCst);
+        for h in readers {
+            h.join().unwrap();
+        }
+        assert_eq!(*v.load(), 99);
+    }
+
+    #[test]
+    fn spin_backend_detects_reader_overflow() {
+        let backend = SpinBackend::new();
+        backend.readers[0].store(MAX_READERS + 1, Ordering::SeqCst);
+        let mut value = 0;
+        let ptr = AtomicPtr::new(&mut value);
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            let _ = backend.protect(&ptr);
+        }));
+        assert!(result.is_err());
+        assert_eq!(backend.readers[0].load(Ordering::SeqCst), MAX_READERS + 1);
+    }
+
+    #[test]
+    fn single_thread_backend_works() {
+        let v = AtomicImmut::with_backend(0, SingleThreadBackend::new());
+        for _ in 0..100 {
+            v.update(|x| x + 1);
+        }
+        assert_eq!(*v.load(), 100);
+        assert_eq!(Arc::strong_count(&v.load()), 2);
+
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            let _peek = v.peek();
+            v.store(0);
+        }));
+        assert!(result.is_err());
+
+        // The old value is leaked on purpose (it might still be peeked),
+        // which `debug-checks` would report when dropping the pointer.
+        mem::forget(v);
+    }
+
+    #[test]
+    fn std_backend_works() {
+        concurrent_update(AtomicImmut::with_backend(0, StdBackend::new()));
+    }
+
+    #[test]
+    fn std_backend_try_protect_works() {
+        let backend = StdBackend::new();
+        let mut value = 0u8;
+        let ptr = AtomicPtr::new(&mut value as *mut u8);
+        assert!(backend.try_protect(&ptr).is_some());
+
+        let _writer = backend.lock.write().unwrap();
+        assert!(backend.try_protect(&ptr).is_none());
+    }
+
+    #[cfg(feature = "epoch")]
+    #[test]
+    fn epoch_backend_works() {
+        concurrent_update(AtomicImmut::with_backend(0, EpochBackend::new()));
+    }
+
+    #[cfg(feature = "epoch")]
+    #[test]
+    fn epoch_backend_waits_for_guards_of_other_pointers() {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let a = AtomicImmut::with_backend(0, EpochBackend::new());
+        let b = Arc::new(AtomicImmut::with_backend(0, EpochBackend::new()));
+        let (peeked_tx, peeked_rx) = mpsc::channel();
+        let reader = {
+            let b = Arc::clone(&b);
+            thread::spawn(move || {
+                let _peek = b.peek();
+                peeked_tx.send(()).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            })
+        };
+        peeked_rx.recv().unwrap();
+        a.store(1);
+        reader.join().unwrap();
+        assert_eq!(*a.load(), 1);
+    }
+
+    #[cfg(feature = "epoch")]
+    #[test]
+    fn epoch_backend_panics_instead_of_deadlock() {
+        let a = AtomicImmut::with_backend(0, EpochBackend::new());
+        let b = AtomicImmut::with_backend(0, EpochBackend::new());
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            let _peek = a.peek();
+            b.store(1);
+        }));
+        assert!(result.is_err());
+
+        // The old value is leaked like in `single_thread_backend_works`.
+        mem::forget(b);
+    }
+
+    #[cfg(feature = "hazard")]
+    #[test]
+    fn hazard_backend_works() {
+        concurrent_update(AtomicImmut::with_backend(0, HazardBackend::new()));
+    }
+
+    #[cfg(feature = "sharded")]
+    #[test]
+    fn sharded_backend_works() {
+        for &shards in &[1, 3, 8] {
+            let backend = ShardedBackend::with_shards(shards);
+            concurrent_update(AtomicImmut::with_backend(0, backend));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use primitive;
+
+/// The backoff policy of spinning waits.
+///
+/// A waiting thread first spins with exponentially increasing intervals
+/// (issuing `spin_loop` hints), and after `spin_limit` steps
+/// yields the processor to other threads (when the `std` feature is enabled).
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::backend::{Backoff, SpinBackend};
+///
+/// let backend = SpinBackend::with_backoff(Backoff::new().spin_limit(3));
+/// let value = AtomicImmut::with_backend(5, backend);
+/// value.store(10);
+/// assert_eq!(*value.load(), 10);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Backoff {
+    spin_limit: u32,
+    yielding: bool,
+}
+impl Backoff {
+    /// The default value of `spin_limit`.
+    pub const DEFAULT_SPIN_LIMIT: u32 = 6;
+
+    /// Makes a new `Backoff` instance with the default settings.
+    pub const fn new() -> Self {
+        Backoff {
+            spin_limit: Self::DEFAULT_SPIN_LIMIT,
+            yielding: true,
+        }
+    }
+
+    /// Sets the number of the spinning steps before yielding.
+    ///
+    /// The `n`-th step spins `2^n` times.
+    pub const fn spin_limit(mut self, steps: u32) -> Self {
+        self.spin_limit = steps;
+        self
+    }
+
+    /// Sets whether the waiting thread yields the processor after `spin_limit` steps.
+    ///
+    /// If `false` (or the `std` feature is disabled), the thread keeps spinning.
+    pub const fn yielding(mut self, yielding: bool) -> Self {
+        self.yielding = yielding;
+        self
+    }
+
+    pub(crate) fn start(self) -> Snooze {
+        Snooze {
+            policy: self,
+            step: 0,
+        }
+    }
+
+    pub(crate) fn wait_until<F: FnMut() -> bool>(self, mut cond: F) {
+        let mut snooze = self.start();
+        while !cond() {
+            snooze.snooze();
+        }
+    }
+}
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of a spinning wait.
+#[derive(Debug)]
+pub(crate) struct Snooze {
+    policy: Backoff,
+    step: u32,
+}
+impl Snooze {
+    pub fn snooze(&mut self) {
+        if self.step < self.policy.spin_limit {
+            for _ in 0..1u32 << self.step.min(16) {
+                primitive::spin_loop();
+            }
+            self.step += 1;
+        } else {
+            self.idle();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn idle(&self) {
+        if self.policy.yielding {
+            primitive::yield_now();
+        } else {
+            self.spin_max();
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn idle(&self) {
+        self.spin_max();
+    }
+
+    fn spin_max(&self) {
+        for _ in 0..1u32 << self.policy.spin_limit.min(16) {
+            primitive::spin_loop();
+        }
+    }
+}
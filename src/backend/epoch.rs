@@ -0,0 +1,72 @@
+use crossbeam_epoch::{self as epoch, Guard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use super::Backend;
+use primitive::AtomicPtr;
+
+/// A backend based on epoch-based reclamation (`crossbeam-epoch`).
+///
+/// Readers only pin the current thread to the global epoch,
+/// so they never touch a counter shared with other threads.
+/// Instead, writers wait until the global epoch advances,
+/// which requires that all the threads pinned before have been unpinned.
+///
+/// Note that the epoch is shared by all the `AtomicImmut` instances using this backend
+/// (and other users of `crossbeam-epoch`'s default collector),
+/// so a long-lived guard delays writers of any of them.
+/// In particular, a thread holding any guard of the collector
+/// (e.g., a `peek` of another pointer using this backend) cannot wait for the epoch to advance.
+/// Writing in such a thread would deadlock, so it panics instead.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::backend::EpochBackend;
+///
+/// let value = AtomicImmut::with_backend(5, EpochBackend::new());
+/// value.store(10);
+/// assert_eq!(*value.peek(), 10);
+/// ```
+#[derive(Debug, Default)]
+pub struct EpochBackend {
+    _private: (),
+}
+impl EpochBackend {
+    /// Makes a new `EpochBackend` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+unsafe impl Backend for EpochBackend {
+    type Guard<'a> = Guard;
+
+    fn protect<P>(&self, ptr: &AtomicPtr<P>) -> (Self::Guard<'_>, *mut P) {
+        let guard = epoch::pin();
+        // `epoch::pin` issues a `SeqCst` fence, which pairs with the one issued by `flush`.
+        (guard, ptr.load(Ordering::Acquire))
+    }
+
+    fn synchronize<P>(&self, _retired: *mut P) {
+        // This thread's own guard would keep the epoch from advancing forever.
+        assert!(
+            !epoch::is_pinned(),
+            "Cannot write to an `AtomicImmut` using `EpochBackend` while holding an epoch guard on the same thread"
+        );
+        // The deferred function is executed only after all the threads
+        // which had been pinned at the time of the deferral are unpinned.
+        let done = Arc::new(AtomicBool::new(false));
+        {
+            let done = Arc::clone(&done);
+            let guard = epoch::pin();
+            guard.defer(move || done.store(true, Ordering::Release));
+            guard.flush();
+        }
+        while !done.load(Ordering::Acquire) {
+            epoch::pin().flush();
+            thread::yield_now();
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use alloc::boxed::Box;
+use core::ptr;
+use core::sync::atomic::Ordering;
+
+use primitive::{self as atomic, AtomicBool, AtomicPtr};
+
+use super::{Backend, Backoff};
+
+/// A backend based on hazard pointers.
+///
+/// Each reader publishes the pointer it is reading in a hazard slot,
+/// and writers wait only for the readers which are reading the retired pointer.
+/// Unlike epoch-based schemes, a stalled reader delays writers
+/// only while it holds the very value being replaced,
+/// and the number of unreclaimed values is bounded by the number of active readers.
+///
+/// Hazard slots are allocated on demand and reused after the guards are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::backend::HazardBackend;
+///
+/// let value = AtomicImmut::with_backend(5, HazardBackend::new());
+/// value.store(10);
+/// assert_eq!(*value.peek(), 10);
+/// ```
+#[derive(Debug)]
+pub struct HazardBackend {
+    head: AtomicPtr<HazardSlot>,
+}
+impl HazardBackend {
+    /// Makes a new `HazardBackend` instance.
+    pub fn new() -> Self {
+        HazardBackend {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    fn acquire_slot(&self) -> &HazardSlot {
+        // `Acquire` pairs with the `Release` push of slots.
+        let mut current = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { current.as_ref() } {
+            // Not `compare_exchange_weak`: a spurious failure would skip a free slot
+            // and allocate a new one.
+            if slot
+                .active
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return slot;
+            }
+            current = slot.next;
+        }
+
+        let slot = Box::into_raw(Box::new(HazardSlot {
+            hazard: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicBool::new(true),
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe {
+                (*slot).next = head;
+            }
+            match self
+                .head
+                .compare_exchange_weak(head, slot, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return unsafe { &*slot },
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn is_protected(&self, retired: *mut ()) -> bool {
+        let mut current = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { current.as_ref() } {
+            // `Acquire` pairs with the `Release` store of `HazardGuard::drop`.
+            if slot.hazard.load(Ordering::Acquire) == retired {
+                return true;
+            }
+            current = slot.next;
+        }
+        false
+    }
+}
+impl Default for HazardBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Drop for HazardBackend {
+    fn drop(&mut self) {
+        let mut current = atomic::with_mut(&mut self.head, |p| *p);
+        while !current.is_null() {
+            let slot = unsafe { Box::from_raw(current) };
+            current = slot.next;
+        }
+    }
+}
+unsafe impl Send for HazardBackend {}
+unsafe impl Sync for HazardBackend {}
+unsafe impl Backend for HazardBackend {
+    type Guard<'a> = HazardGuard<'a>;
+
+    fn protect<P>(&self, ptr: &AtomicPtr<P>) -> (Self::Guard<'_>, *mut P) {
+        let slot = self.acquire_slot();
+        let mut current = ptr.load(Ordering::Relaxed);
+        loop {
+            slot.hazard.store(current.cast(), Ordering::Relaxed);
+
+            // Pairs with the fence in `synchronize`:
+            // either the validation below sees the new pointer, or the writer sees the hazard.
+            atomic::fence(Ordering::SeqCst);
+            let actual = ptr.load(Ordering::Acquire);
+            if actual == current {
+                return (HazardGuard(slot), current);
+            }
+            current = actual;
+        }
+    }
+
+    fn try_protect<P>(&self, ptr: &AtomicPtr<P>) -> Option<(Self::Guard<'_>, *mut P)> {
+        // A single attempt of `protect`, which retries as long as writers keep replacing the pointer.
+        let slot = self.acquire_slot();
+        let current = ptr.load(Ordering::Relaxed);
+        slot.hazard.store(current.cast(), Ordering::Relaxed);
+        let guard = HazardGuard(slot);
+
+        // See `protect`.
+        atomic::fence(Ordering::SeqCst);
+        if ptr.load(Ordering::Acquire) == current {
+            Some((guard, current))
+        } else {
+            None
+        }
+    }
+
+    fn synchronize<P>(&self, retired: *mut P) {
+        atomic::fence(Ordering::SeqCst);
+        Backoff::new().wait_until(|| !self.is_protected(retired.cast()));
+    }
+
+    fn synchronize_all(&self) {
+        atomic::fence(Ordering::SeqCst);
+        let mut current = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { current.as_ref() } {
+            // A reader that keeps re-protecting the same pointer with the same slot
+            // is indistinguishable from the one which was there, so it is waited for too.
+            let hazard = slot.hazard.load(Ordering::Acquire);
+            if !hazard.is_null() {
+                Backoff::new().wait_until(|| slot.hazard.load(Ordering::Acquire) != hazard);
+            }
+            current = slot.next;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HazardSlot {
+    hazard: AtomicPtr<()>,
+    active: AtomicBool,
+    next: *mut HazardSlot,
+}
+
+/// The guard of `HazardBackend`.
+#[derive(Debug)]
+pub struct HazardGuard<'a>(&'a HazardSlot);
+impl<'a> Drop for HazardGuard<'a> {
+    fn drop(&mut self) {
+        self.0.hazard.store(ptr::null_mut(), Ordering::Release);
+        self.0.active.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hazard_backend_reuses_slots() {
+        let backend = HazardBackend::new();
+        let mut value = 0u8;
+        let ptr = AtomicPtr::new(&mut value as *mut u8);
+        {
+            let (_g0, p0) = backend.protect(&ptr);
+            let (_g1, p1) = backend.protect(&ptr);
+            assert_eq!(p0, p1);
+            assert!(backend.is_protected(p0 as *mut ()));
+        }
+        assert!(!backend.is_protected(ptr.load(Ordering::SeqCst) as *mut ()));
+
+        let (_g, _) = backend.protect(&ptr);
+        let mut slots = 0;
+        let mut current = backend.head.load(Ordering::SeqCst);
+        while let Some(slot) = unsafe { current.as_ref() } {
+            slots += 1;
+            current = slot.next;
+        }
+        assert_eq!(slots, 2);
+    }
+}
@@ -0,0 +1,192 @@
+use std::cell::Cell;
+use std::sync::atomic::Ordering;
+
+use lock::WriterLock;
+use primitive::{self as atomic, AtomicPtr, AtomicUsize};
+use std::thread;
+
+use super::{Backend, Backoff, MAX_READERS};
+
+/// A backend which distributes the reader counters over multiple shards.
+///
+/// `SpinBackend` counts all the readers in a single counter,
+/// so concurrent `load`s on different cores contend on the same cache line.
+/// This backend gives each thread its own shard (as long as the number of shards suffices),
+/// and each shard is aligned to a cache line,
+/// so readers on different threads rarely touch the same cache line.
+/// In exchange, writers have to scan all the shards.
+///
+/// Like `SpinBackend`, readers of each shard are counted in two phases
+/// so that writers are not starved by a constant stream of readers,
+/// and concurrent grace periods are serialized.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::backend::ShardedBackend;
+///
+/// let value = AtomicImmut::with_backend(5, ShardedBackend::new());
+/// value.store(10);
+/// assert_eq!(*value.peek(), 10);
+/// ```
+#[derive(Debug)]
+pub struct ShardedBackend {
+    shards: Box<[Shard]>,
+    phase: AtomicUsize,
+    sync: WriterLock,
+    backoff: Backoff,
+}
+impl ShardedBackend {
+    /// Makes a new `ShardedBackend` instance.
+    ///
+    /// The number of shards is the available parallelism of the platform.
+    pub fn new() -> Self {
+        let shards = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(shards)
+    }
+
+    /// Makes a new `ShardedBackend` instance which has `shards` reader counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    /// use atomic_immut::backend::ShardedBackend;
+    ///
+    /// let backend = ShardedBackend::with_shards(4);
+    /// assert_eq!(backend.shards(), 4);
+    ///
+    /// let value = AtomicImmut::with_backend(5, backend);
+    /// value.store(10);
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn with_shards(shards: usize) -> Self {
+        assert_ne!(shards, 0, "`shards` must be greater than zero");
+        ShardedBackend {
+            shards: (0..shards).map(|_| Shard::default()).collect(),
+            phase: AtomicUsize::new(0),
+            sync: WriterLock::new(),
+            backoff: Backoff::new(),
+        }
+    }
+
+    /// Sets the `Backoff` policy used by writers waiting for readers.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Returns the number of the shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard(&self) -> &Shard {
+        &self.shards[thread_index() % self.shards.len()]
+    }
+}
+impl Default for ShardedBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+unsafe impl Backend for ShardedBackend {
+    type Guard<'a> = ShardedGuard<'a>;
+
+    fn protect<P>(&self, ptr: &AtomicPtr<P>) -> (Self::Guard<'_>, *mut P) {
+        let shard = self.shard();
+        let phase = self.phase.load(Ordering::Relaxed) & 1;
+        let readers = shard.readers[phase].fetch_add(1, Ordering::Relaxed);
+        if readers > MAX_READERS {
+            shard.readers[phase].fetch_sub(1, Ordering::Relaxed);
+            panic!("Too many readers of a `ShardedBackend` shard");
+        }
+        let guard = ShardedGuard { shard, phase };
+
+        // See `SpinBackend::protect`.
+        atomic::fence(Ordering::SeqCst);
+        (guard, ptr.load(Ordering::Acquire))
+    }
+
+    fn synchronize<P>(&self, _retired: *mut P) {
+        // See `SpinBackend::synchronize`.
+        let _sync = self.sync.lock();
+        atomic::fence(Ordering::SeqCst);
+        for _ in 0..2 {
+            let previous = self.phase.fetch_add(1, Ordering::Relaxed) & 1;
+            for shard in self.shards.iter() {
+                let readers = &shard.readers[previous];
+                self.backoff
+                    .wait_until(|| readers.load(Ordering::Acquire) == 0);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct Shard {
+    readers: [AtomicUsize; 2],
+}
+
+/// The guard of `ShardedBackend`.
+#[derive(Debug)]
+pub struct ShardedGuard<'a> {
+    shard: &'a Shard,
+    phase: usize,
+}
+impl<'a> Drop for ShardedGuard<'a> {
+    fn drop(&mut self) {
+        self.shard.readers[self.phase].fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Returns a small integer which identifies the current thread.
+fn thread_index() -> usize {
+    static NEXT_INDEX: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+    thread_local! {
+        static INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+    INDEX.with(|index| {
+        index.get().unwrap_or_else(|| {
+            let i = NEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+            index.set(Some(i));
+            i
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn sharded_backend_spreads_readers() {
+        let backend = Arc::new(ShardedBackend::with_shards(2));
+        let mut value = 0u8;
+        let ptr = AtomicPtr::new(&mut value as *mut u8);
+        let (_g, _) = backend.protect(&ptr);
+
+        let other = Arc::clone(&backend);
+        thread::spawn(move || {
+            let mut value = 0u8;
+            let ptr = AtomicPtr::new(&mut value as *mut u8);
+            let (_g, _) = other.protect(&ptr);
+            let counts = other
+                .shards
+                .iter()
+                .map(|s| s.readers[0].load(Ordering::SeqCst))
+                .collect::<Vec<_>>();
+            assert_eq!(counts, [1, 1]);
+        })
+        .join()
+        .unwrap();
+    }
+}
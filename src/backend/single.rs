@@ -0,0 +1,69 @@
+use core::cell::Cell;
+use core::sync::atomic::Ordering;
+
+use primitive::AtomicPtr;
+
+use super::Backend;
+
+/// A backend for single-threaded programs.
+///
+/// Readers and writers never synchronize with each other:
+/// `protect` is a plain load, and `synchronize` only checks that
+/// no guard is alive (which would otherwise be a use-after-free),
+/// panicking instead of waiting forever like the other backends.
+///
+/// This backend is neither `Send` nor `Sync`, so neither are the `AtomicImmut`s using it,
+/// except on `wasm32` targets without the atomics proposal, which have no threads.
+/// There, it is the default backend (see `DefaultBackend`),
+/// which keeps the public API of `AtomicImmut` without the cost of the reader counting.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::backend::SingleThreadBackend;
+///
+/// let value = AtomicImmut::with_backend(5, SingleThreadBackend::new());
+/// value.store(10);
+/// assert_eq!(*value.peek(), 10);
+/// ```
+#[derive(Debug, Default)]
+pub struct SingleThreadBackend {
+    readers: Cell<usize>,
+}
+impl SingleThreadBackend {
+    /// Makes a new `SingleThreadBackend` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+unsafe impl Backend for SingleThreadBackend {
+    type Guard<'a> = SingleThreadGuard<'a>;
+
+    fn protect<P>(&self, ptr: &AtomicPtr<P>) -> (Self::Guard<'_>, *mut P) {
+        self.readers.set(self.readers.get() + 1);
+        (SingleThreadGuard(self), ptr.load(Ordering::Relaxed))
+    }
+
+    fn synchronize<P>(&self, _retired: *mut P) {
+        // The only thread holds a guard, so waiting would never finish.
+        assert_eq!(
+            self.readers.get(),
+            0,
+            "Cannot write to an `AtomicImmut` while peeking it on the same thread"
+        );
+    }
+}
+
+// Without threads, no reference is ever shared between threads.
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+unsafe impl Sync for SingleThreadBackend {}
+
+/// The guard of `SingleThreadBackend`.
+#[derive(Debug)]
+pub struct SingleThreadGuard<'a>(&'a SingleThreadBackend);
+impl<'a> Drop for SingleThreadGuard<'a> {
+    fn drop(&mut self) {
+        self.0.readers.set(self.0.readers.get() - 1);
+    }
+}
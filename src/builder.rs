@@ -0,0 +1,198 @@
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use primitive::Arc;
+
+use backend::{Backend, DefaultBackend};
+#[cfg(feature = "std")]
+use hook::HookFn;
+#[cfg(feature = "tracing")]
+use trace::Tracer;
+use AtomicImmut;
+
+/// A builder of `AtomicImmut` which sets up the per-pointer configurations in one expression.
+///
+/// This is created by the `AtomicImmut::builder` method.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::backend::StdBackend;
+///
+/// let stores = Arc::new(AtomicUsize::new(0));
+/// let routes = {
+///     let stores = Arc::clone(&stores);
+///     AtomicImmut::builder()
+///         .name("routing_table")
+///         .keep_history(4)
+///         .backend(StdBackend::new())
+///         .on_store(move |_, _| {
+///             stores.fetch_add(1, Ordering::SeqCst);
+///         })
+///         .build(vec!["/"])
+/// };
+/// routes.update(|r| [&r[..], &["/foo"]].concat());
+///
+/// assert_eq!(routes.name(), Some("routing_table"));
+/// assert_eq!(routes.history().len(), 1);
+/// assert_eq!(stores.load(Ordering::SeqCst), 1);
+/// ```
+pub struct AtomicImmutBuilder<T: ?Sized, B = DefaultBackend> {
+    backend: B,
+    name: Option<String>,
+    #[cfg(feature = "std")]
+    history: usize,
+    #[cfg(feature = "std")]
+    hooks: Vec<HookFn<T>>,
+    #[cfg(feature = "std")]
+    track_last_updated: bool,
+    #[cfg(feature = "tracing")]
+    render_values: Option<fn(&mut Tracer<T>)>,
+    _value: PhantomData<fn(&T)>,
+}
+impl<T: ?Sized> AtomicImmutBuilder<T> {
+    pub(crate) fn new() -> Self {
+        AtomicImmutBuilder {
+            backend: DefaultBackend::new(),
+            name: None,
+            #[cfg(feature = "std")]
+            history: 0,
+            #[cfg(feature = "std")]
+            hooks: Vec::new(),
+            #[cfg(feature = "std")]
+            track_last_updated: false,
+            #[cfg(feature = "tracing")]
+            render_values: None,
+            _value: PhantomData,
+        }
+    }
+}
+impl<T: ?Sized, B: Backend> AtomicImmutBuilder<T, B> {
+    /// Sets the synchronization backend (`DefaultBackend` by default).
+    pub fn backend<C: Backend>(self, backend: C) -> AtomicImmutBuilder<T, C> {
+        AtomicImmutBuilder {
+            backend,
+            name: self.name,
+            #[cfg(feature = "std")]
+            history: self.history,
+            #[cfg(feature = "std")]
+            hooks: self.hooks,
+            #[cfg(feature = "std")]
+            track_last_updated: self.track_last_updated,
+            #[cfg(feature = "tracing")]
+            render_values: self.render_values,
+            _value: PhantomData,
+        }
+    }
+
+    /// Sets the name of the pointer (see `AtomicImmut::named`).
+    pub fn name<N: Into<String>>(mut self, name: N) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the number of the replaced values to be kept (see `AtomicImmut::keep_history`).
+    #[cfg(feature = "std")]
+    pub fn keep_history(mut self, n: usize) -> Self {
+        self.history = n;
+        self
+    }
+
+    /// Adds a hook called after every successful store (see `AtomicImmut::register_on_store`).
+    ///
+    /// Unlike `register_on_store`, the hooks added by the builder cannot be unregistered.
+    #[cfg(feature = "std")]
+    pub fn on_store<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Arc<T>, &Arc<T>) + Send + Sync + 'static,
+    {
+        self.hooks.push(Arc::new(f));
+        self
+    }
+
+    /// Makes the pointer track the time of the last store (see `AtomicImmut::track_last_updated`).
+    #[cfg(feature = "std")]
+    pub fn track_last_updated(mut self) -> Self {
+        self.track_last_updated = true;
+        self
+    }
+
+    /// Makes the `tracing` events of the stores include the new values
+    /// (see `AtomicImmut::trace_values`).
+    #[cfg(feature = "tracing")]
+    pub fn trace_values(mut self) -> Self
+    where
+        T: fmt::Debug,
+    {
+        self.render_values = Some(Tracer::render_values);
+        self
+    }
+
+    /// Builds an `AtomicImmut` which has the value `initial`.
+    pub fn build(self, initial: T) -> AtomicImmut<T, B>
+    where
+        T: Sized,
+    {
+        self.build_arc(Arc::new(initial))
+    }
+
+    /// Builds an `AtomicImmut` which has the shared value `initial`.
+    ///
+    /// Unlike `build`, this method can be used for unsized values.
+    pub fn build_arc(self, initial: Arc<T>) -> AtomicImmut<T, B> {
+        #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+        let mut cell = AtomicImmut::from_arc_with_backend(initial, self.backend);
+        if let Some(name) = self.name {
+            cell = cell.named(name);
+        }
+        #[cfg(feature = "std")]
+        {
+            // Each of them allocates the extras of the cell, so they are set only if needed.
+            if self.history != 0 {
+                cell = cell.keep_history(self.history);
+            }
+            for hook in self.hooks {
+                cell.extras.get_mut().hooks.register(hook);
+            }
+            if self.track_last_updated {
+                cell = cell.track_last_updated();
+            }
+        }
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(render_values) = self.render_values {
+                render_values(&mut cell.tracer);
+            }
+        }
+        cell
+    }
+}
+impl<T: ?Sized, B: fmt::Debug> fmt::Debug for AtomicImmutBuilder<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("AtomicImmutBuilder");
+        s.field("backend", &self.backend).field("name", &self.name);
+        #[cfg(feature = "std")]
+        s.field("history", &self.history)
+            .field("hooks", &self.hooks.len());
+        s.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_arc_works() {
+        let value: AtomicImmut<str> = AtomicImmut::builder()
+            .name("greeting")
+            .build_arc(Arc::from("hello"));
+        assert_eq!(&*value.load(), "hello");
+        assert_eq!(value.name(), Some("greeting"));
+    }
+}
@@ -0,0 +1,91 @@
+use core::fmt;
+use primitive::Arc;
+
+use backend::{Backend, DefaultBackend};
+use AtomicImmut;
+
+/// A handle which caches the value of an `AtomicImmut` locally.
+///
+/// This is created by the `AtomicImmut::cache` method.
+///
+/// `get` only checks the version counter of the `AtomicImmut`,
+/// and touches the value (and its reference count) only if a new value has been stored.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+///
+/// let value = AtomicImmut::new(5);
+/// let mut cache = value.cache();
+/// assert_eq!(**cache.get(), 5);
+///
+/// value.store(10);
+/// assert_eq!(**cache.get(), 10);
+/// ```
+pub struct Cache<'a, T: ?Sized + 'a, B: 'a = DefaultBackend> {
+    cell: &'a AtomicImmut<T, B>,
+    value: Arc<T>,
+    version: u64,
+}
+impl<'a, T: ?Sized, B: Backend> Cache<'a, T, B> {
+    pub(crate) fn new(cell: &'a AtomicImmut<T, B>) -> Self {
+        let (value, version) = cell.load_versioned();
+        Cache {
+            cell,
+            value,
+            version,
+        }
+    }
+
+    /// Returns the latest value of the `AtomicImmut`.
+    ///
+    /// The cached value is reloaded only if a new value has been stored since the last load.
+    pub fn get(&mut self) -> &Arc<T> {
+        if let Some((value, version)) = self.cell.load_if_newer(self.version) {
+            self.value = value;
+            self.version = version;
+        }
+        &self.value
+    }
+
+    /// Returns the cached value without checking whether it is the latest.
+    pub fn cached(&self) -> &Arc<T> {
+        &self.value
+    }
+
+    /// Returns the version of the cached value.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+impl<'a, T: ?Sized + fmt::Debug, B> fmt::Debug for Cache<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("value", &&*self.value)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use AtomicImmut;
+
+    #[test]
+    fn cache_works() {
+        let v = AtomicImmut::new(vec![0]);
+        let mut cache = v.cache();
+        let first = Arc::clone(cache.get());
+        assert!(Arc::ptr_eq(cache.get(), &first));
+        assert_eq!(cache.version(), 0);
+
+        v.store(vec![1]);
+        assert_eq!(**cache.cached(), [0]);
+        assert_eq!(**cache.get(), [1]);
+        assert_eq!(cache.version(), 1);
+        assert_eq!(Arc::strong_count(&first), 1);
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(not(feature = "debug-checks"))]
+use core::marker::PhantomData;
+#[cfg(feature = "debug-checks")]
+use primitive::plain::{AtomicUsize, Ordering};
+
+/// Shadow counters of a pointer (maintained only if the `debug-checks` feature is enabled).
+///
+/// They count the values owned through the raw pointer (i.e., forgotten but not yet recovered)
+/// and the outstanding read guards, and panic as soon as the counts go wrong.
+#[derive(Debug, Default)]
+pub(crate) struct Checks {
+    #[cfg(feature = "debug-checks")]
+    owned: AtomicUsize,
+    #[cfg(feature = "debug-checks")]
+    readers: AtomicUsize,
+}
+impl Checks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that a value has been forgotten into the raw pointer.
+    #[cfg(feature = "debug-checks")]
+    pub fn forgotten(&self) {
+        self.owned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(not(feature = "debug-checks"))]
+    pub fn forgotten(&self) {}
+
+    /// Records that a value has been recovered from the raw pointer.
+    #[cfg(feature = "debug-checks")]
+    pub fn recovered(&self) {
+        let prev = self.owned.fetch_sub(1, Ordering::Relaxed);
+        assert_ne!(
+            prev, 0,
+            "debug-checks: recovered a value which is not owned"
+        );
+    }
+
+    #[cfg(not(feature = "debug-checks"))]
+    pub fn recovered(&self) {}
+
+    /// Records a read of the value until the returned token is dropped.
+    #[cfg(feature = "debug-checks")]
+    pub fn enter(&self) -> ReadToken<'_> {
+        self.readers.fetch_add(1, Ordering::Relaxed);
+        ReadToken(self)
+    }
+
+    #[cfg(not(feature = "debug-checks"))]
+    pub fn enter(&self) -> ReadToken<'_> {
+        ReadToken(PhantomData)
+    }
+
+    /// Asserts that `owned` values are left and no read is in progress.
+    #[cfg(feature = "debug-checks")]
+    pub fn assert_balanced(&self, owned: usize) {
+        let readers = self.readers.load(Ordering::Relaxed);
+        assert_eq!(
+            readers, 0,
+            "debug-checks: {} read guards are leaked",
+            readers
+        );
+        let actual = self.owned.load(Ordering::Relaxed);
+        assert_eq!(actual, owned, "debug-checks: unbalanced owned values");
+    }
+
+    #[cfg(not(feature = "debug-checks"))]
+    pub fn assert_balanced(&self, _owned: usize) {}
+}
+
+#[derive(Debug)]
+pub(crate) struct ReadToken<'a>(
+    #[cfg(feature = "debug-checks")] &'a Checks,
+    #[cfg(not(feature = "debug-checks"))] PhantomData<&'a Checks>,
+);
+#[cfg(feature = "debug-checks")]
+impl<'a> Drop for ReadToken<'a> {
+    fn drop(&mut self) {
+        let prev = self.0.readers.fetch_sub(1, Ordering::Relaxed);
+        assert_ne!(
+            prev, 0,
+            "debug-checks: more read guards are dropped than taken"
+        );
+    }
+}
+
+#[cfg(all(test, feature = "debug-checks"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn balanced_counts_pass() {
+        let checks = Checks::new();
+        checks.forgotten();
+        {
+            let _a = checks.enter();
+            let _b = checks.enter();
+        }
+        checks.assert_balanced(1);
+        checks.recovered();
+        checks.assert_balanced(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "recovered a value which is not owned")]
+    fn underflow_panics() {
+        Checks::new().recovered();
+    }
+
+    #[test]
+    #[should_panic(expected = "read guards are leaked")]
+    fn leaked_guard_panics() {
+        let checks = Checks::new();
+        ::std::mem::forget(checks.enter());
+        checks.assert_balanced(0);
+    }
+}
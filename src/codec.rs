@@ -0,0 +1,109 @@
+//! Binary encodings of values, used to persist values (see the `persist` module).
+//!
+//! Besides user-defined codecs, `JsonCodec` and `BincodeCodec` are provided
+//! when the `json` and `bincode` features are enabled respectively.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::convert::TryInto;
+//! use atomic_immut::codec::{Codec, CodecError};
+//!
+//! let encode = |v: &u32| -> Result<Vec<u8>, CodecError> { Ok(v.to_le_bytes().to_vec()) };
+//! let decode = |b: &[u8]| -> Result<u32, CodecError> { Ok(u32::from_le_bytes(b.try_into()?)) };
+//! let codec = (encode, decode);
+//!
+//! let bytes = codec.encode(&10).unwrap();
+//! assert_eq!(codec.decode(&bytes).unwrap(), 10);
+//! ```
+#[cfg(feature = "bincode")]
+use bincode;
+#[cfg(any(feature = "json", feature = "bincode"))]
+use serde::de::DeserializeOwned;
+#[cfg(any(feature = "json", feature = "bincode"))]
+use serde::Serialize;
+#[cfg(feature = "json")]
+use serde_json;
+use std::error;
+
+/// Boxed error returned by codecs.
+pub type CodecError = Box<dyn error::Error + Send + Sync>;
+
+/// An encoding of values of `T` into bytes and back.
+///
+/// This is implemented for pairs of encode and decode functions.
+pub trait Codec<T> {
+    /// Encodes `value` into bytes.
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    /// Decodes bytes made by `encode`.
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError>;
+}
+impl<T, E, D> Codec<T> for (E, D)
+where
+    E: Fn(&T) -> Result<Vec<u8>, CodecError>,
+    D: Fn(&[u8]) -> Result<T, CodecError>,
+{
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        (self.0)(value)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        (self.1)(bytes)
+    }
+}
+
+/// A codec which encodes values as JSON.
+///
+/// This is available when the `json` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::codec::{Codec, JsonCodec};
+///
+/// let bytes = JsonCodec.encode(&vec![1, 2]).unwrap();
+/// assert_eq!(bytes, b"[1,2]");
+/// assert_eq!(JsonCodec.decode(&bytes).ok(), Some(vec![1, 2]));
+/// ```
+#[cfg(feature = "json")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JsonCodec;
+#[cfg(feature = "json")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// A codec which encodes values in the compact binary format of `bincode`.
+///
+/// This is available when the `bincode` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::codec::{BincodeCodec, Codec};
+///
+/// let value = (1u32, String::from("a"));
+/// let bytes = BincodeCodec.encode(&value).unwrap();
+/// assert_eq!(bytes.len(), 4 + 8 + 1);
+/// assert_eq!(BincodeCodec.decode(&bytes).ok(), Some(value));
+/// ```
+#[cfg(feature = "bincode")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BincodeCodec;
+#[cfg(feature = "bincode")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for BincodeCodec {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
@@ -0,0 +1,205 @@
+//! Update methods of `AtomicImmut`s holding collections.
+//!
+//! Each method clones the current collection, modifies the clone and stores it
+//! (retrying on conflicts like `AtomicImmut::update`),
+//! and returns whether the collection has been changed.
+//! If nothing changes, nothing is stored.
+//!
+//! Cloning takes O(n) time, so for large collections consider
+//! `AtomicImmutHashMap` and `AtomicImmutVector` (available when the `im` feature is enabled),
+//! whose updates take O(log n) time.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use atomic_immut::AtomicImmut;
+//! use atomic_immut::collections::{AtomicImmutMapExt, AtomicImmutVecExt};
+//!
+//! let routes = AtomicImmut::new(HashMap::new());
+//! assert!(routes.insert("/", 0));
+//! assert!(!routes.insert("/", 0));
+//! assert!(routes.remove(&"/"));
+//!
+//! let queue = AtomicImmut::new(vec![1, 2, 3]);
+//! queue.push(4);
+//! assert!(queue.retain(|x| x % 2 == 0));
+//! assert_eq!(*queue.load(), [2, 4]);
+//! ```
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hash};
+
+use backend::Backend;
+use AtomicImmut;
+
+/// Update methods of `AtomicImmut<Vec<T>>`.
+pub trait AtomicImmutVecExt<T> {
+    /// Appends `value` to the vector.
+    fn push(&self, value: T);
+
+    /// Retains only the elements for which `f` returns `true`.
+    ///
+    /// Returns `true` if any element has been removed.
+    fn retain<F>(&self, f: F) -> bool
+    where
+        F: FnMut(&T) -> bool;
+}
+impl<T: Clone, B: Backend> AtomicImmutVecExt<T> for AtomicImmut<Vec<T>, B> {
+    fn push(&self, value: T) {
+        self.update(|v| {
+            let mut v = v.clone();
+            v.push(value.clone());
+            v
+        });
+    }
+
+    fn retain<F>(&self, mut f: F) -> bool
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.try_update(|v| {
+            let retained = v.iter().filter(|x| f(x)).cloned().collect::<Vec<_>>();
+            if retained.len() == v.len() {
+                None
+            } else {
+                Some(retained)
+            }
+        })
+    }
+}
+
+/// Update methods of `AtomicImmut`s holding maps (`HashMap` and `BTreeMap`).
+pub trait AtomicImmutMapExt<K, V> {
+    /// Inserts an entry into the map.
+    ///
+    /// Returns `false` if the map already has the same entry.
+    fn insert(&self, key: K, value: V) -> bool;
+
+    /// Removes the entry of `key` from the map.
+    ///
+    /// Returns `false` if the map has no such entry.
+    fn remove(&self, key: &K) -> bool;
+
+    /// Retains only the entries for which `f` returns `true`.
+    ///
+    /// Returns `true` if any entry has been removed.
+    fn retain<F>(&self, f: F) -> bool
+    where
+        F: FnMut(&K, &V) -> bool;
+}
+
+macro_rules! impl_map_ext {
+    ($map:ty, [$($bounds:tt)*], [$($params:tt)*]) => {
+        impl<$($params)*, B: Backend> AtomicImmutMapExt<K, V> for AtomicImmut<$map, B>
+        where
+            $($bounds)*
+        {
+            fn insert(&self, key: K, value: V) -> bool {
+                self.try_update(|m| {
+                    if m.get(&key) == Some(&value) {
+                        return None;
+                    }
+                    let mut m = m.clone();
+                    m.insert(key.clone(), value.clone());
+                    Some(m)
+                })
+            }
+
+            fn remove(&self, key: &K) -> bool {
+                self.try_update(|m| {
+                    if !m.contains_key(key) {
+                        return None;
+                    }
+                    let mut m = m.clone();
+                    m.remove(key);
+                    Some(m)
+                })
+            }
+
+            fn retain<F>(&self, mut f: F) -> bool
+            where
+                F: FnMut(&K, &V) -> bool,
+            {
+                self.try_update(|m| {
+                    let retained = m
+                        .iter()
+                        .filter(|&(k, v)| f(k, v))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect::<$map>();
+                    if retained.len() == m.len() {
+                        None
+                    } else {
+                        Some(retained)
+                    }
+                })
+            }
+        }
+    };
+}
+#[cfg(feature = "std")]
+impl_map_ext!(
+    HashMap<K, V, S>,
+    [K: Clone + Hash + Eq, V: Clone + PartialEq, S: Clone + BuildHasher + Default],
+    [K, V, S]
+);
+impl_map_ext!(
+    BTreeMap<K, V>,
+    [K: Clone + Ord, V: Clone + PartialEq],
+    [K, V]
+);
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn vec_ext_works() {
+        let v = Arc::new(AtomicImmut::new(Vec::new()));
+        let handles = (0..4)
+            .map(|i| {
+                let v = Arc::clone(&v);
+                thread::spawn(move || {
+                    for j in 0..50 {
+                        v.push(i * 50 + j);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(v.load().len(), 200);
+        assert!(v.retain(|&x| x < 100));
+        assert!(!v.retain(|&x| x < 100));
+        let mut values = (*v.load()).clone();
+        values.sort();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn map_ext_works() {
+        let m = AtomicImmut::new(BTreeMap::new());
+        assert!(m.insert(1, "a"));
+        assert!(m.insert(2, "b"));
+        assert!(!m.insert(2, "b"));
+        assert!(m.insert(2, "c"));
+        assert!(!m.remove(&3));
+        assert!(m.retain(|&k, _| k > 1));
+        assert_eq!(m.load().iter().collect::<Vec<_>>(), [(&2, &"c")]);
+
+        let m = AtomicImmut::new(HashMap::new());
+        let old = m.load();
+        assert!(!m.retain(|_: &u8, _: &u8| false));
+        assert!(Arc::ptr_eq(&old, &m.load()));
+        assert!(m.insert(1, 1));
+        assert!(m.remove(&1));
+        assert!(m.load().is_empty());
+    }
+}
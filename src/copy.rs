@@ -0,0 +1,229 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use primitive::plain::{self as atomic, AtomicUsize, Ordering};
+
+use backend::Backoff;
+
+/// A thread-safe cell for `Copy` values stored inline behind a sequence lock.
+///
+/// Unlike `AtomicImmut`, neither `load` nor `store` allocates:
+/// the value is held in this cell itself and `load` copies it out.
+/// A reader retries if a writer has modified the value while copying,
+/// so this suits small values (say, up to a few cache lines) which are read far more often than written.
+/// For word-sized values, `AtomicImmutSmall` is more efficient.
+///
+/// Writers exclude each other, spinning following `Backoff`.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutCopy;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+///     z: f64,
+/// }
+///
+/// let point = AtomicImmutCopy::new(Point { x: 0.0, y: 0.0, z: 0.0 });
+/// point.update(|p| Point { x: p.x + 1.0, ..p });
+/// assert_eq!(point.load(), Point { x: 1.0, y: 0.0, z: 0.0 });
+/// ```
+pub struct AtomicImmutCopy<T> {
+    // Odd while a writer is modifying the value.
+    seq: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+impl<T: Copy> AtomicImmutCopy<T> {
+    /// Makes a new `AtomicImmutCopy` instance.
+    pub const fn new(value: T) -> Self {
+        AtomicImmutCopy {
+            seq: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Loads (copies) the value from this cell.
+    pub fn load(&self) -> T {
+        let mut snooze = Backoff::new().start();
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 == 0 {
+                // The copy may be torn by a concurrent writer,
+                // so it is not regarded as a `T` until the sequence number is validated.
+                let value =
+                    unsafe { ptr::read_volatile(self.value.get() as *const MaybeUninit<T>) };
+                atomic::fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == seq {
+                    return unsafe { value.assume_init() };
+                }
+            }
+            snooze.snooze();
+        }
+    }
+
+    /// Stores a value into this cell.
+    pub fn store(&self, value: T) {
+        self.swap(value);
+    }
+
+    /// Stores a value into this cell, returning the old value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutCopy;
+    ///
+    /// let value = AtomicImmutCopy::new([1u8; 32]);
+    /// assert_eq!(value.swap([2; 32]), [1; 32]);
+    /// assert_eq!(value.load(), [2; 32]);
+    /// ```
+    pub fn swap(&self, value: T) -> T {
+        let writer = self.lock();
+        let old = writer.get();
+        writer.set(value);
+        old
+    }
+
+    /// Updates the value of this cell by applying `f` to the current one.
+    ///
+    /// Writers are excluded while `f` is executed, so `f` is called exactly once.
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(T) -> T,
+    {
+        let writer = self.lock();
+        let new = f(writer.get());
+        writer.set(new);
+    }
+
+    /// Returns a mutable reference to the value.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes this cell, returning the value.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    fn lock(&self) -> Writer<'_, T> {
+        let mut snooze = Backoff::new().start();
+        loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 == 0
+                && self
+                    .seq
+                    .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return Writer { cell: self, seq };
+            }
+            snooze.snooze();
+        }
+    }
+}
+unsafe impl<T: Send> Send for AtomicImmutCopy<T> {}
+unsafe impl<T: Send> Sync for AtomicImmutCopy<T> {}
+impl<T: Copy + fmt::Debug> fmt::Debug for AtomicImmutCopy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicImmutCopy")
+            .field(&self.load())
+            .finish()
+    }
+}
+impl<T: Copy + Default> Default for AtomicImmutCopy<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<T: Copy> From<T> for AtomicImmutCopy<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// The exclusive write access of `AtomicImmutCopy`.
+///
+/// Dropping this (even by a panic) makes the sequence number even again.
+struct Writer<'a, T: 'a> {
+    cell: &'a AtomicImmutCopy<T>,
+    seq: usize,
+}
+impl<'a, T: Copy> Writer<'a, T> {
+    fn get(&self) -> T {
+        unsafe { *self.cell.value.get() }
+    }
+
+    fn set(&self, value: T) {
+        // Keeps the write after the odd sequence number from the view of readers.
+        atomic::fence(Ordering::Release);
+        unsafe { ptr::write_volatile(self.cell.value.get(), value) };
+    }
+}
+impl<'a, T> Drop for Writer<'a, T> {
+    fn drop(&mut self) {
+        self.cell
+            .seq
+            .store(self.seq.wrapping_add(2), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let mut v = AtomicImmutCopy::new((1u8, 2u64));
+        assert_eq!(v.load(), (1, 2));
+        assert_eq!(v.swap((3, 4)), (1, 2));
+        v.update(|(a, b)| (b as u8, a as u64));
+        assert_eq!(v.load(), (4, 3));
+        v.get_mut().0 = 5;
+        assert_eq!(v.into_inner(), (5, 3));
+    }
+
+    #[test]
+    fn loads_are_never_torn() {
+        let v = Arc::new(AtomicImmutCopy::new([0usize; 8]));
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers = (0..2)
+            .map(|_| {
+                let v = Arc::clone(&v);
+                let stop = Arc::clone(&stop);
+                thread::spawn(move || {
+                    while !stop.load(Ordering::SeqCst) {
+                        let a = v.load();
+                        assert!(a.iter().all(|&x| x == a[0]));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 0..10_000 {
+            v.store([i; 8]);
+        }
+        stop.store(true, Ordering::SeqCst);
+        for h in readers {
+            h.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn panicking_update_releases_writer() {
+        let v = AtomicImmutCopy::new(1);
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            v.update(|_| panic!());
+        }));
+        assert!(result.is_err());
+        v.store(2);
+        assert_eq!(v.load(), 2);
+    }
+}
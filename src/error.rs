@@ -0,0 +1,117 @@
+use core::any;
+use core::fmt;
+
+/// The error returned by `AtomicImmut::try_update_bounded`
+/// when the update has conflicted with other writers too many times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UpdateContended {
+    retries: usize,
+}
+impl UpdateContended {
+    pub(crate) fn new(retries: usize) -> Self {
+        UpdateContended { retries }
+    }
+
+    /// Returns the number of the retries performed before giving up.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+}
+impl fmt::Display for UpdateContended {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Update gave up after {} retries due to contention",
+            self.retries
+        )
+    }
+}
+#[cfg(feature = "std")]
+impl ::std::error::Error for UpdateContended {}
+
+/// The error returned when the value of an `AtomicImmutAny` is not of the requested type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeMismatch {
+    expected: &'static str,
+}
+impl TypeMismatch {
+    pub(crate) fn new<T: ?Sized>() -> Self {
+        TypeMismatch {
+            expected: any::type_name::<T>(),
+        }
+    }
+
+    /// Returns the name of the requested type.
+    pub fn expected(&self) -> &'static str {
+        self.expected
+    }
+}
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The value is not of the type `{}`", self.expected)
+    }
+}
+#[cfg(feature = "std")]
+impl ::std::error::Error for TypeMismatch {}
+
+/// The error returned by `AtomicImmut::rollback`
+/// when the history does not have enough values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RollbackError {
+    requested: usize,
+    available: usize,
+}
+impl RollbackError {
+    #[cfg(feature = "std")]
+    pub(crate) fn new(requested: usize, available: usize) -> Self {
+        RollbackError {
+            requested,
+            available,
+        }
+    }
+
+    /// Returns the number of the steps requested to roll back.
+    pub fn requested(&self) -> usize {
+        self.requested
+    }
+
+    /// Returns the number of the values available in the history.
+    pub fn available(&self) -> usize {
+        self.available
+    }
+}
+impl fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Cannot roll back {} steps: only {} values are in the history",
+            self.requested, self.available
+        )
+    }
+}
+#[cfg(feature = "std")]
+impl ::std::error::Error for RollbackError {}
+
+/// The error yielded by a `Snapshots` stream in place of the values dropped due to its full buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lagged {
+    missed: usize,
+}
+impl Lagged {
+    #[cfg(feature = "async")]
+    pub(crate) fn new(missed: usize) -> Self {
+        Lagged { missed }
+    }
+
+    /// Returns the number of the missed values.
+    pub fn missed(&self) -> usize {
+        self.missed
+    }
+}
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The stream missed {} values", self.missed)
+    }
+}
+#[cfg(feature = "std")]
+impl ::std::error::Error for Lagged {}
@@ -0,0 +1,328 @@
+//! Values which expire after a time-to-live.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use atomic_immut::expiring::ExpiringImmut;
+//!
+//! let endpoints = ExpiringImmut::new(Duration::from_secs(60));
+//! assert!(endpoints.load().is_none());
+//!
+//! endpoints.store(vec!["10.0.0.1:80"]);
+//! assert_eq!(*endpoints.load().unwrap(), ["10.0.0.1:80"]);
+//!
+//! endpoints.store_with_ttl(vec!["10.0.0.2:80"], Duration::from_secs(0));
+//! assert!(endpoints.load().is_none());
+//! ```
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use AtomicImmut;
+
+type Refresher<T> = Box<dyn Fn() -> Option<T> + Send + Sync>;
+
+struct Entry<T> {
+    value: Arc<T>,
+    expires_at: Instant,
+}
+impl<T> Clone for Entry<T> {
+    fn clone(&self) -> Self {
+        Entry {
+            value: Arc::clone(&self.value),
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// A thread-safe pointer for an immutable value which expires after a time-to-live.
+///
+/// Expired values are never returned by `load`
+/// unless serving stale values is enabled by `ExpiringBuilder::serve_stale`.
+pub struct ExpiringImmut<T> {
+    entry: AtomicImmut<Option<Entry<T>>>,
+    ttl: Duration,
+    refresher: Option<Refresher<T>>,
+    serve_stale: bool,
+    refreshing: AtomicBool,
+}
+impl<T> ExpiringImmut<T> {
+    /// Makes a new empty `ExpiringImmut` instance whose values live for `ttl` by default.
+    pub fn new(ttl: Duration) -> Self {
+        Self::builder(ttl).build()
+    }
+
+    /// Returns a builder of `ExpiringImmut` whose values live for `ttl` by default.
+    pub fn builder(ttl: Duration) -> ExpiringBuilder<T> {
+        ExpiringBuilder {
+            ttl,
+            refresher: None,
+            serve_stale: false,
+        }
+    }
+
+    /// Loads the value from this pointer.
+    ///
+    /// If the value has expired (or no value has been stored), the refresher (if any) is called
+    /// to get a new value, which is then stored and returned.
+    /// If there is no refresher or it fails, `None` is returned.
+    ///
+    /// When serving stale values is enabled, the expired value is returned instead
+    /// while another thread is refreshing it or if the refresh fails.
+    pub fn load(&self) -> Option<Arc<T>> {
+        let stale = match *self.entry.load() {
+            Some(ref entry) if Instant::now() < entry.expires_at => {
+                return Some(Arc::clone(&entry.value))
+            }
+            Some(ref entry) if self.serve_stale => Some(Arc::clone(&entry.value)),
+            _ => None,
+        };
+        let refresher = match self.refresher {
+            None => return stale,
+            Some(ref f) => f,
+        };
+
+        if stale.is_some() {
+            // Only one thread refreshes the value, while the others serve the stale one.
+            if self.refreshing.swap(true, Ordering::Acquire) {
+                return stale;
+            }
+            let _guard = RefreshGuard(&self.refreshing);
+            self.refresh_with(refresher).or(stale)
+        } else {
+            self.refresh_with(refresher)
+        }
+    }
+
+    /// Loads the value from this pointer even if it has expired.
+    pub fn load_stale(&self) -> Option<Arc<T>> {
+        self.entry
+            .peek()
+            .as_ref()
+            .map(|entry| Arc::clone(&entry.value))
+    }
+
+    /// Stores `value` which lives for the default time-to-live.
+    pub fn store(&self, value: T) {
+        self.store_with_ttl(value, self.ttl);
+    }
+
+    /// Stores `value` which lives for `ttl`.
+    pub fn store_with_ttl(&self, value: T, ttl: Duration) {
+        self.store_arc_with_ttl(Arc::new(value), ttl);
+    }
+
+    fn store_arc_with_ttl(&self, value: Arc<T>, ttl: Duration) {
+        let now = Instant::now();
+        let expires_at = now.checked_add(ttl).unwrap_or(now + MAX_TTL);
+        self.entry.store(Some(Entry { value, expires_at }));
+    }
+
+    /// Removes the value from this pointer.
+    pub fn invalidate(&self) {
+        self.entry.store(None);
+    }
+
+    /// Returns the time when the current value expires.
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.entry.peek().as_ref().map(|entry| entry.expires_at)
+    }
+
+    /// Returns `true` if the current value has expired or there is no value.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at().is_none_or(|t| t <= Instant::now())
+    }
+
+    /// Returns the default time-to-live.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn refresh_with(&self, refresher: &Refresher<T>) -> Option<Arc<T>> {
+        let value = Arc::new(refresher()?);
+        self.store_arc_with_ttl(Arc::clone(&value), self.ttl);
+        Some(value)
+    }
+}
+impl<T: fmt::Debug> fmt::Debug for ExpiringImmut<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entry = self.entry.load();
+        f.debug_struct("ExpiringImmut")
+            .field("value", &Option::as_ref(&entry).map(|e| &e.value))
+            .field("expires_at", &Option::as_ref(&entry).map(|e| e.expires_at))
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+/// Clears the `refreshing` flag even if the refresher panics.
+struct RefreshGuard<'a>(&'a AtomicBool);
+impl<'a> Drop for RefreshGuard<'a> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Ten years, which is long enough but does not overflow `Instant`.
+const MAX_TTL: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// A builder of `ExpiringImmut`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::time::Duration;
+/// use atomic_immut::expiring::ExpiringImmut;
+///
+/// let fetches = AtomicUsize::new(0);
+/// let discovery = ExpiringImmut::builder(Duration::from_secs(60))
+///     .refresher(move || Some(fetches.fetch_add(1, Ordering::SeqCst)))
+///     .serve_stale(true)
+///     .build();
+///
+/// assert_eq!(*discovery.load().unwrap(), 0);
+/// assert_eq!(*discovery.load().unwrap(), 0);
+///
+/// discovery.store_with_ttl(10, Duration::from_secs(0));
+/// assert_eq!(*discovery.load().unwrap(), 1);
+/// ```
+pub struct ExpiringBuilder<T> {
+    ttl: Duration,
+    refresher: Option<Refresher<T>>,
+    serve_stale: bool,
+}
+impl<T> ExpiringBuilder<T> {
+    /// Sets the function which makes a new value when the value has expired.
+    ///
+    /// The function returns `None` if it fails.
+    /// Unless serving stale values is enabled, it may be called by multiple threads at the same time.
+    pub fn refresher<F>(mut self, f: F) -> Self
+    where
+        F: Fn() -> Option<T> + Send + Sync + 'static,
+    {
+        self.refresher = Some(Box::new(f));
+        self
+    }
+
+    /// Sets whether expired values are served while refreshing them (`false` by default).
+    ///
+    /// If `true`, only one thread calls the refresher at a time,
+    /// and the others get the expired value meanwhile.
+    /// The expired value is also returned if the refresher fails.
+    pub fn serve_stale(mut self, serve_stale: bool) -> Self {
+        self.serve_stale = serve_stale;
+        self
+    }
+
+    /// Builds an empty `ExpiringImmut`.
+    pub fn build(self) -> ExpiringImmut<T> {
+        ExpiringImmut {
+            entry: AtomicImmut::new(None),
+            ttl: self.ttl,
+            refresher: self.refresher,
+            serve_stale: self.serve_stale,
+            refreshing: AtomicBool::new(false),
+        }
+    }
+}
+impl<T> fmt::Debug for ExpiringBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExpiringBuilder")
+            .field("ttl", &self.ttl)
+            .field("serve_stale", &self.serve_stale)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn expiration_works() {
+        let v = ExpiringImmut::new(Duration::from_millis(50));
+        v.store(1);
+        assert!(!v.is_expired());
+        assert_eq!(v.load().map(|v| *v), Some(1));
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(v.is_expired());
+        assert!(v.load().is_none());
+        assert_eq!(v.load_stale().map(|v| *v), Some(1));
+
+        v.store_with_ttl(2, Duration::from_secs(u64::MAX));
+        assert_eq!(v.load().map(|v| *v), Some(2));
+        v.invalidate();
+        assert!(v.load_stale().is_none());
+    }
+
+    #[test]
+    fn stale_value_is_served_while_refreshing() {
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let v = Arc::new(
+            ExpiringImmut::builder(Duration::from_secs(60))
+                .refresher(move || {
+                    entered_tx.send(()).unwrap();
+                    release_rx.lock().unwrap().recv().ok()?;
+                    Some(2)
+                })
+                .serve_stale(true)
+                .build(),
+        );
+        v.store_with_ttl(1, Duration::from_secs(0));
+
+        let refresher = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || v.load().map(|v| *v))
+        };
+        entered_rx.recv().unwrap();
+        assert_eq!(v.load().map(|v| *v), Some(1));
+
+        release_tx.send(()).unwrap();
+        assert_eq!(refresher.join().unwrap(), Some(2));
+        assert_eq!(v.load().map(|v| *v), Some(2));
+
+        // Failures of the refresher fall back to the stale value.
+        v.store_with_ttl(3, Duration::from_secs(0));
+        drop(release_tx);
+        let waiter = thread::spawn(move || entered_rx.recv());
+        assert_eq!(v.load().map(|v| *v), Some(3));
+        waiter.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn panicking_refresher_does_not_stop_refreshes() {
+        let calls = Arc::new(Mutex::new(0));
+        let v = Arc::new({
+            let calls = Arc::clone(&calls);
+            ExpiringImmut::builder(Duration::from_secs(60))
+                .refresher(move || {
+                    let mut calls = calls.lock().unwrap_or_else(|e| e.into_inner());
+                    *calls += 1;
+                    if *calls == 1 {
+                        panic!("refresh failed");
+                    }
+                    Some(*calls)
+                })
+                .serve_stale(true)
+                .build()
+        });
+        v.store_with_ttl(0, Duration::from_secs(0));
+
+        let panicked = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || v.load()).join()
+        };
+        assert!(panicked.is_err());
+        assert_eq!(v.load().map(|v| *v), Some(2));
+    }
+}
@@ -0,0 +1,97 @@
+use alloc::boxed::Box;
+use core::ptr;
+use primitive::plain::{AtomicPtr, Ordering};
+
+use history::History;
+use hook::Hooks;
+use rcu::Deferred;
+#[cfg(feature = "std")]
+use subscribe::Waiters;
+use timestamp::Timestamp;
+use validate::Validator;
+
+/// The optional state of an `AtomicImmut`.
+///
+/// Most pointers never use any of them,
+/// so they are allocated on the first use (see `LazyExtras`) to keep `AtomicImmut` small.
+pub(crate) struct Extras<T: ?Sized> {
+    pub hooks: Hooks<T>,
+    pub history: History<T>,
+    pub validator: Validator<T>,
+    pub name: Option<Box<str>>,
+    pub updated: Timestamp,
+    pub deferred: Deferred,
+    #[cfg(feature = "std")]
+    pub waiters: Waiters,
+}
+impl<T: ?Sized> Extras<T> {
+    fn new() -> Self {
+        Extras {
+            hooks: Hooks::new(),
+            history: History::new(),
+            validator: Validator::none(),
+            name: None,
+            updated: Timestamp::new(),
+            deferred: Deferred::new(),
+            #[cfg(feature = "std")]
+            waiters: Waiters::new(),
+        }
+    }
+}
+
+/// An `Extras` which is allocated on the first use.
+pub(crate) struct LazyExtras<T: ?Sized> {
+    ptr: AtomicPtr<Extras<T>>,
+}
+impl<T: ?Sized> LazyExtras<T> {
+    pub fn new() -> Self {
+        LazyExtras {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the extras if they have been allocated.
+    ///
+    /// `SeqCst` pairs with `get_or_init`:
+    /// a writer which advances the version and then finds no extras
+    /// is ordered before a subscriber which allocates them and then checks the version.
+    pub fn get(&self) -> Option<&Extras<T>> {
+        unsafe { self.ptr.load(Ordering::SeqCst).as_ref() }
+    }
+
+    /// Returns the extras, allocating them if needed.
+    #[cfg(feature = "std")]
+    pub fn get_or_init(&self) -> &Extras<T> {
+        if let Some(extras) = self.get() {
+            return extras;
+        }
+        let new = Box::into_raw(Box::new(Extras::new()));
+        match self
+            .ptr
+            .compare_exchange(ptr::null_mut(), new, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => unsafe { &*new },
+            Err(current) => {
+                drop(unsafe { Box::from_raw(new) });
+                unsafe { &*current }
+            }
+        }
+    }
+
+    /// Returns the extras, allocating them if needed.
+    pub fn get_mut(&mut self) -> &mut Extras<T> {
+        let ptr = self.ptr.get_mut();
+        if ptr.is_null() {
+            *ptr = Box::into_raw(Box::new(Extras::new()));
+        }
+        unsafe { &mut **ptr }
+    }
+}
+impl<T: ?Sized> Drop for LazyExtras<T> {
+    fn drop(&mut self) {
+        let ptr = *self.ptr.get_mut();
+        if !ptr.is_null() {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
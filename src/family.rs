@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use AtomicImmut;
+
+/// The cells of an `AtomicImmutFamily`.
+pub type FamilyCells<K, T> = HashMap<K, Arc<AtomicImmut<T>>>;
+
+/// A concurrent map from keys to independent `AtomicImmut` cells,
+/// which are created lazily by a factory function.
+///
+/// The cells are indexed by an `AtomicImmut` holding a map,
+/// so looking up an existing cell never blocks,
+/// and storing a value into a cell does not affect the other cells.
+/// Only the creation and removal of cells replace the index.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutFamily;
+///
+/// let quotas = AtomicImmutFamily::new(|tenant: &String| tenant.len() * 10);
+/// assert_eq!(*quotas.load(&"foo".to_owned()), 30);
+///
+/// quotas.get(&"foo".to_owned()).store(100);
+/// assert_eq!(*quotas.load(&"foo".to_owned()), 100);
+/// assert_eq!(*quotas.load(&"quux".to_owned()), 40);
+/// assert_eq!(quotas.len(), 2);
+/// ```
+pub struct AtomicImmutFamily<K, T> {
+    cells: AtomicImmut<FamilyCells<K, T>>,
+    factory: Box<dyn Fn(&K) -> T + Send + Sync>,
+}
+impl<K, T> AtomicImmutFamily<K, T>
+where
+    K: Clone + Hash + Eq,
+{
+    /// Makes a new `AtomicImmutFamily` instance
+    /// which creates the initial value of the cell of a key by calling `factory`.
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn(&K) -> T + Send + Sync + 'static,
+    {
+        AtomicImmutFamily {
+            cells: AtomicImmut::new(HashMap::new()),
+            factory: Box::new(factory),
+        }
+    }
+
+    /// Returns the cell of `key`, creating it if it does not exist.
+    ///
+    /// When threads race to create the same cell, all of them get the same one
+    /// (though `factory` may be called more than once).
+    pub fn get(&self, key: &K) -> Arc<AtomicImmut<T>> {
+        if let Some(cell) = self.get_existing(key) {
+            return cell;
+        }
+
+        let cell = Arc::new(AtomicImmut::new((self.factory)(key)));
+        let result = self.cells.try_update_result(|cells| {
+            if let Some(existing) = cells.get(key) {
+                return Err(Arc::clone(existing));
+            }
+            let mut cells = cells.clone();
+            cells.insert(key.clone(), Arc::clone(&cell));
+            Ok(cells)
+        });
+        match result {
+            Ok(()) => cell,
+            Err(existing) => existing,
+        }
+    }
+
+    /// Returns the cell of `key` if it exists.
+    pub fn get_existing(&self, key: &K) -> Option<Arc<AtomicImmut<T>>> {
+        self.cells.peek().get(key).cloned()
+    }
+
+    /// Loads the value of the cell of `key`, creating the cell if it does not exist.
+    pub fn load(&self, key: &K) -> Arc<T> {
+        if let Some(value) = self.cells.peek().get(key).map(|cell| cell.load()) {
+            return value;
+        }
+        self.get(key).load()
+    }
+
+    /// Removes the cell of `key`, returning it if it existed.
+    ///
+    /// Holders of the removed cell can keep using it,
+    /// but the next `get` creates a new cell for the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutFamily;
+    ///
+    /// let family = AtomicImmutFamily::new(|_: &u32| 0);
+    /// family.get(&1).store(5);
+    ///
+    /// let removed = family.remove(&1).unwrap();
+    /// assert_eq!(*removed.load(), 5);
+    /// assert_eq!(*family.load(&1), 0);
+    /// assert!(family.remove(&2).is_none());
+    /// ```
+    pub fn remove(&self, key: &K) -> Option<Arc<AtomicImmut<T>>> {
+        let mut removed = None;
+        self.cells.try_update(|cells| {
+            removed = cells.get(key).cloned();
+            removed.as_ref()?;
+            let mut cells = cells.clone();
+            cells.remove(key);
+            Some(cells)
+        });
+        removed
+    }
+
+    /// Returns a snapshot of the existing cells.
+    ///
+    /// This is used to iterate over the cells.
+    /// Cells created or removed after this call are not reflected in the snapshot,
+    /// but the values of the cells are live.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutFamily;
+    ///
+    /// let family = AtomicImmutFamily::new(|&k: &u32| k * 2);
+    /// family.get(&1);
+    /// family.get(&2);
+    ///
+    /// let mut values = family
+    ///     .snapshot()
+    ///     .iter()
+    ///     .map(|(k, cell)| (*k, *cell.load()))
+    ///     .collect::<Vec<_>>();
+    /// values.sort();
+    /// assert_eq!(values, [(1, 2), (2, 4)]);
+    /// ```
+    pub fn snapshot(&self) -> Arc<FamilyCells<K, T>> {
+        self.cells.load()
+    }
+
+    /// Returns the number of the existing cells.
+    pub fn len(&self) -> usize {
+        self.cells.peek().len()
+    }
+
+    /// Returns `true` if there are no cells.
+    pub fn is_empty(&self) -> bool {
+        self.cells.peek().is_empty()
+    }
+}
+impl<K: fmt::Debug, T: fmt::Debug> fmt::Debug for AtomicImmutFamily<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AtomicImmutFamily")
+            .field("cells", &*self.cells.peek())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn concurrent_get_returns_same_cell() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let family = {
+            let created = Arc::clone(&created);
+            Arc::new(AtomicImmutFamily::new(move |&k: &usize| {
+                created.fetch_add(1, Ordering::SeqCst);
+                k
+            }))
+        };
+        let handles = (0..4)
+            .map(|_| {
+                let family = Arc::clone(&family);
+                thread::spawn(move || {
+                    (0..50)
+                        .map(|k| {
+                            let cell = family.get(&k);
+                            cell.update(|v| v + 1000);
+                            cell
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let cells = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>();
+        for k in 0..50 {
+            assert!(cells.iter().all(|c| Arc::ptr_eq(&c[k], &cells[0][k])));
+            assert_eq!(*family.load(&k), k + 4000);
+        }
+        assert_eq!(family.len(), 50);
+        assert!(created.load(Ordering::SeqCst) >= 50);
+    }
+}
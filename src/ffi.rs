@@ -0,0 +1,207 @@
+//! C-compatible functions to share byte buffers through an `AtomicImmut`.
+//!
+//! A handle (`BytesHandle`) is opaque to C, and each loaded snapshot owns a strong reference
+//! to the buffer, so it stays valid however the handle is updated (or freed) afterwards
+//! until it is released.
+//!
+//! To link them into a C/C++ program, build the crate as a `cdylib` or `staticlib`
+//! with the `ffi` feature enabled
+//! (e.g., `cargo rustc --release --features ffi --crate-type cdylib`),
+//! and declare the functions as follows:
+//!
+//! ```c
+//! typedef struct BytesHandle BytesHandle;
+//! typedef struct { const uint8_t *data; size_t len; } AtomicImmutSnapshot;
+//!
+//! BytesHandle *atomic_immut_new(const uint8_t *data, size_t len);
+//! void atomic_immut_free(BytesHandle *handle);
+//! AtomicImmutSnapshot atomic_immut_load(const BytesHandle *handle);
+//! void atomic_immut_store(const BytesHandle *handle, const uint8_t *data, size_t len);
+//! void atomic_immut_release(AtomicImmutSnapshot snapshot);
+//! ```
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::ffi::*;
+//!
+//! unsafe {
+//!     let handle = atomic_immut_new(b"foo".as_ptr(), 3);
+//!     let snapshot = atomic_immut_load(handle);
+//!     atomic_immut_store(handle, b"bar".as_ptr(), 3);
+//!     atomic_immut_free(handle);
+//!
+//!     // The snapshot outlives the handle.
+//!     assert_eq!(snapshot.as_bytes(), b"foo");
+//!     atomic_immut_release(snapshot);
+//! }
+//! ```
+use primitive::Arc;
+use std::ptr;
+use std::slice;
+
+use AtomicImmut;
+
+/// An opaque handle of an `AtomicImmut<[u8]>`.
+///
+/// This is created by `atomic_immut_new` and must be freed by `atomic_immut_free`.
+#[derive(Debug)]
+pub struct BytesHandle {
+    inner: AtomicImmut<[u8]>,
+}
+
+/// A byte buffer loaded by `atomic_immut_load`.
+///
+/// This owns a strong reference to the buffer, which must be released by `atomic_immut_release`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct AtomicImmutSnapshot {
+    /// The pointer to the first byte of the buffer.
+    pub data: *const u8,
+
+    /// The length of the buffer in bytes.
+    pub len: usize,
+}
+impl AtomicImmutSnapshot {
+    /// Returns the bytes of this snapshot.
+    ///
+    /// # Safety
+    ///
+    /// This snapshot must not have been released.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        slice::from_raw_parts(self.data, self.len)
+    }
+}
+
+/// Makes a new handle which has a copy of the given bytes.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes (or may be null if `len` is `0`).
+#[no_mangle]
+pub unsafe extern "C" fn atomic_immut_new(data: *const u8, len: usize) -> *mut BytesHandle {
+    let inner = AtomicImmut::from_arc(to_arc(data, len));
+    Box::into_raw(Box::new(BytesHandle { inner }))
+}
+
+/// Frees a handle.
+///
+/// The snapshots loaded from the handle remain valid until they are released.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `atomic_immut_new` and not freed yet (or may be null).
+#[no_mangle]
+pub unsafe extern "C" fn atomic_immut_free(handle: *mut BytesHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Loads the current bytes of a handle.
+///
+/// # Safety
+///
+/// `handle` must be a live handle returned by `atomic_immut_new`.
+#[no_mangle]
+pub unsafe extern "C" fn atomic_immut_load(handle: *const BytesHandle) -> AtomicImmutSnapshot {
+    let raw = (*handle).inner.load_raw();
+    AtomicImmutSnapshot {
+        data: raw.cast(),
+        len: raw.len(),
+    }
+}
+
+/// Stores a copy of the given bytes into a handle.
+///
+/// # Safety
+///
+/// `handle` must be a live handle returned by `atomic_immut_new`,
+/// and `data` must be valid for reads of `len` bytes (or may be null if `len` is `0`).
+#[no_mangle]
+pub unsafe extern "C" fn atomic_immut_store(
+    handle: *const BytesHandle,
+    data: *const u8,
+    len: usize,
+) {
+    (*handle).inner.store_arc(to_arc(data, len));
+}
+
+/// Releases a snapshot loaded by `atomic_immut_load`.
+///
+/// # Safety
+///
+/// `snapshot` must have been returned by `atomic_immut_load` and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn atomic_immut_release(snapshot: AtomicImmutSnapshot) {
+    drop(Arc::from_raw(ptr::slice_from_raw_parts(
+        snapshot.data,
+        snapshot.len,
+    )));
+}
+
+unsafe fn to_arc(data: *const u8, len: usize) -> Arc<[u8]> {
+    if len == 0 {
+        Arc::from(&[][..])
+    } else {
+        Arc::from(slice::from_raw_parts(data, len))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn it_works() {
+        unsafe {
+            let handle = atomic_immut_new(ptr::null(), 0);
+            let empty = atomic_immut_load(handle);
+            assert_eq!(empty.as_bytes(), b"");
+
+            atomic_immut_store(handle, b"foo".as_ptr(), 3);
+            let foo = atomic_immut_load(handle);
+            assert_eq!(foo.as_bytes(), b"foo");
+            assert_eq!(Arc::strong_count(&(*handle).inner.load()), 3);
+
+            atomic_immut_release(foo);
+            assert_eq!(Arc::strong_count(&(*handle).inner.load()), 2);
+
+            atomic_immut_free(handle);
+            assert_eq!(empty.as_bytes(), b"");
+            atomic_immut_release(empty);
+            atomic_immut_free(ptr::null_mut());
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    struct Handle(*mut BytesHandle);
+    unsafe impl Send for Handle {}
+
+    #[test]
+    fn concurrent_loads_work() {
+        let handle = Handle(unsafe { atomic_immut_new(b"0".as_ptr(), 1) });
+        let readers = (0..4)
+            .map(|_| {
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        unsafe {
+                            let snapshot = atomic_immut_load(handle.0);
+                            assert_eq!(snapshot.len, 1);
+                            atomic_immut_release(snapshot);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 0..100u8 {
+            let byte = b'0' + i % 10;
+            unsafe { atomic_immut_store(handle.0, &byte, 1) };
+        }
+        for r in readers {
+            r.join().unwrap();
+        }
+        unsafe { atomic_immut_free(handle.0) };
+    }
+}
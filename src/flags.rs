@@ -0,0 +1,424 @@
+//! Feature flags built on `AtomicImmut`.
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::flags::{FlagSet, FlagValue};
+//!
+//! let flags = FlagSet::new();
+//! flags.set("new_ui", true);
+//! flags.set("new_ranking", FlagValue::Percent(50.0));
+//!
+//! assert!(flags.bool_flag("new_ui"));
+//! assert!(!flags.bool_flag("unknown"));
+//!
+//! // Stable for the same input.
+//! let enabled = flags.percent_flag("new_ranking", "user-42");
+//! assert_eq!(flags.percent_flag("new_ranking", "user-42"), enabled);
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use {AtomicImmut, HookId};
+
+/// The snapshot of the flags of a `FlagSet`.
+pub type Flags = HashMap<String, FlagValue>;
+
+/// The value of a flag.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlagValue {
+    /// An on/off flag.
+    Bool(bool),
+
+    /// A flag enabled for the given percentage (`0.0` to `100.0`) of inputs.
+    Percent(f64),
+
+    /// An integer flag.
+    Int(i64),
+
+    /// A string flag.
+    Str(String),
+}
+impl From<bool> for FlagValue {
+    fn from(f: bool) -> Self {
+        FlagValue::Bool(f)
+    }
+}
+impl From<i64> for FlagValue {
+    fn from(f: i64) -> Self {
+        FlagValue::Int(f)
+    }
+}
+impl From<String> for FlagValue {
+    fn from(f: String) -> Self {
+        FlagValue::Str(f)
+    }
+}
+impl<'a> From<&'a str> for FlagValue {
+    fn from(f: &'a str) -> Self {
+        FlagValue::Str(f.to_owned())
+    }
+}
+
+/// A set of flags which can be replaced atomically.
+///
+/// Readers always see a consistent snapshot of all the flags.
+#[derive(Default)]
+pub struct FlagSet {
+    flags: AtomicImmut<Flags>,
+}
+impl FlagSet {
+    /// Makes a new empty `FlagSet` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a new `FlagSet` instance which has `flags`.
+    pub fn with_flags(flags: Flags) -> Self {
+        FlagSet {
+            flags: AtomicImmut::new(flags),
+        }
+    }
+
+    /// Returns the snapshot of all the flags.
+    pub fn snapshot(&self) -> Arc<Flags> {
+        self.flags.load()
+    }
+
+    /// Returns the value of the flag `key`.
+    pub fn get(&self, key: &str) -> Option<FlagValue> {
+        self.flags.peek().get(key).cloned()
+    }
+
+    /// Returns `true` if the flag `key` is on.
+    ///
+    /// Missing and non-`Bool` flags are regarded as off.
+    pub fn bool_flag(&self, key: &str) -> bool {
+        matches!(self.flags.peek().get(key), Some(&FlagValue::Bool(true)))
+    }
+
+    /// Returns `true` if the flag `key` is enabled for `hash_input` (e.g., a user ID).
+    ///
+    /// This is equivalent to `self.flag(key).is_enabled_for(hash_input)`.
+    pub fn percent_flag<H: AsRef<[u8]>>(&self, key: &str, hash_input: H) -> bool {
+        self.flag(key).is_enabled_for(hash_input)
+    }
+
+    /// Returns the flag `key` in the current snapshot.
+    ///
+    /// The returned `Flag` keeps the snapshot,
+    /// so it gives consistent answers even if the flags are replaced meanwhile.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::flags::{FlagSet, FlagValue};
+    ///
+    /// let flags = FlagSet::new();
+    /// flags.set("checkout", FlagValue::Percent(25.0));
+    ///
+    /// let flag = flags.flag("checkout");
+    /// let users = (0..1000).filter(|id| flag.is_enabled_for(id.to_string())).count();
+    /// assert!(users > 150 && users < 350);
+    ///
+    /// // Dialing the rollout up keeps the enabled users enabled.
+    /// flags.set("checkout", FlagValue::Percent(50.0));
+    /// let dialed = flags.flag("checkout");
+    /// assert!((0..1000)
+    ///     .map(|id| id.to_string())
+    ///     .filter(|id| flag.is_enabled_for(id))
+    ///     .all(|id| dialed.is_enabled_for(id)));
+    /// ```
+    pub fn flag<'a>(&self, key: &'a str) -> Flag<'a> {
+        Flag {
+            key,
+            snapshot: self.snapshot(),
+        }
+    }
+
+    /// Returns the value of the `Int` flag `key`.
+    pub fn int_flag(&self, key: &str) -> Option<i64> {
+        match self.flags.peek().get(key) {
+            Some(&FlagValue::Int(n)) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the `Str` flag `key`.
+    pub fn str_flag(&self, key: &str) -> Option<String> {
+        match self.flags.peek().get(key) {
+            Some(FlagValue::Str(s)) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    /// Sets the value of the flag `key`.
+    pub fn set<K, V>(&self, key: K, value: V)
+    where
+        K: Into<String>,
+        V: Into<FlagValue>,
+    {
+        let key = key.into();
+        let value = value.into();
+        self.flags.update(|flags| {
+            let mut flags = flags.clone();
+            flags.insert(key.clone(), value.clone());
+            flags
+        });
+    }
+
+    /// Removes the flag `key`, returning `true` if it existed.
+    pub fn remove(&self, key: &str) -> bool {
+        self.flags.try_update(|flags| {
+            if !flags.contains_key(key) {
+                return None;
+            }
+            let mut flags = flags.clone();
+            flags.remove(key);
+            Some(flags)
+        })
+    }
+
+    /// Replaces all the flags with `flags`.
+    pub fn replace(&self, flags: Flags) {
+        self.flags.store(flags);
+    }
+
+    /// Replaces all the flags with the ones returned by `loader`.
+    ///
+    /// If `loader` fails, the flags are left untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::flags::{FlagSet, Flags};
+    ///
+    /// let flags = FlagSet::new();
+    /// flags.load_from(|| -> Result<Flags, String> {
+    ///     Ok(vec![("verbose".to_owned(), true.into())].into_iter().collect())
+    /// }).unwrap();
+    /// assert!(flags.bool_flag("verbose"));
+    ///
+    /// assert!(flags.load_from(|| Err("unreachable")).is_err());
+    /// assert!(flags.bool_flag("verbose"));
+    /// ```
+    pub fn load_from<F, E>(&self, loader: F) -> Result<(), E>
+    where
+        F: FnOnce() -> Result<Flags, E>,
+    {
+        self.replace(loader()?);
+        Ok(())
+    }
+
+    /// Registers a hook which is called with the old and new values of the flag `key`
+    /// every time it changes (including additions and removals).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use atomic_immut::flags::{FlagSet, FlagValue};
+    ///
+    /// let flags = FlagSet::new();
+    /// let changes = Arc::new(Mutex::new(Vec::new()));
+    /// {
+    ///     let changes = Arc::clone(&changes);
+    ///     flags.on_change("debug", move |_old, new| changes.lock().unwrap().push(new.cloned()));
+    /// }
+    ///
+    /// flags.set("debug", true);
+    /// flags.set("other", true);
+    /// flags.set("debug", true);
+    /// flags.remove("debug");
+    /// assert_eq!(*changes.lock().unwrap(), [Some(FlagValue::Bool(true)), None]);
+    /// ```
+    pub fn on_change<K, F>(&self, key: K, f: F) -> HookId
+    where
+        K: Into<String>,
+        F: Fn(Option<&FlagValue>, Option<&FlagValue>) + Send + Sync + 'static,
+    {
+        let key = key.into();
+        self.flags.register_on_store(move |old, new| {
+            let old = old.get(&key);
+            let new = new.get(&key);
+            if old != new {
+                f(old, new);
+            }
+        })
+    }
+
+    /// Unregisters the hook identified by `id`.
+    pub fn unregister(&self, id: HookId) -> bool {
+        self.flags.unregister(id)
+    }
+
+    /// Returns the underlying `AtomicImmut` (e.g., to subscribe to the changes of all flags).
+    pub fn as_immut(&self) -> &AtomicImmut<Flags> {
+        &self.flags
+    }
+}
+impl fmt::Debug for FlagSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FlagSet")
+            .field("flags", &*self.flags.peek())
+            .finish()
+    }
+}
+
+/// A flag in a snapshot of a `FlagSet`.
+///
+/// This is created by `FlagSet::flag`.
+#[derive(Debug, Clone)]
+pub struct Flag<'a> {
+    key: &'a str,
+    snapshot: Arc<Flags>,
+}
+impl<'a> Flag<'a> {
+    /// Returns the key of the flag.
+    pub fn key(&self) -> &'a str {
+        self.key
+    }
+
+    /// Returns the value of the flag.
+    pub fn value(&self) -> Option<&FlagValue> {
+        self.snapshot.get(self.key)
+    }
+
+    /// Returns the bucket of `id` in the flag (`0` to `9999`).
+    ///
+    /// The bucket is a stable hash of the key and `id`,
+    /// so it is the same across processes and independent between flags.
+    pub fn bucket_for<H: AsRef<[u8]>>(&self, id: H) -> u32 {
+        (fnv1a(id.as_ref(), fnv1a(self.key.as_bytes(), FNV_OFFSET_BASIS)) % BUCKETS) as u32
+    }
+
+    /// Returns `true` if the flag is enabled for `id` (e.g., a user ID).
+    ///
+    /// `Percent` flags are enabled for the IDs whose buckets fall in the percentage,
+    /// so the result for the same ID does not change until the percentage is changed,
+    /// and raising the percentage never disables an enabled ID.
+    /// `Bool` flags are enabled (or disabled) for all IDs,
+    /// and the other (and missing) flags are disabled.
+    pub fn is_enabled_for<H: AsRef<[u8]>>(&self, id: H) -> bool {
+        match self.value() {
+            Some(&FlagValue::Bool(b)) => b,
+            Some(&FlagValue::Percent(p)) => f64::from(self.bucket_for(id)) < p * 100.0,
+            _ => false,
+        }
+    }
+
+    /// Assigns `id` to one of `variants` if the flag is enabled for it.
+    ///
+    /// The IDs are split evenly between the variants,
+    /// and the variant of an ID is stable while the number of variants is unchanged
+    /// (regardless of the rollout percentage).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::flags::{FlagSet, FlagValue};
+    ///
+    /// let flags = FlagSet::new();
+    /// flags.set("button", FlagValue::Percent(100.0));
+    ///
+    /// let flag = flags.flag("button");
+    /// let variant = flag.variant_for("user-7", &["red", "blue"]);
+    /// assert!(variant.is_some());
+    /// assert_eq!(flag.variant_for("user-7", &["red", "blue"]), variant);
+    ///
+    /// flags.set("button", false);
+    /// assert_eq!(flags.flag("button").variant_for("user-7", &["red", "blue"]), None);
+    /// ```
+    pub fn variant_for<'v, H, V>(&self, id: H, variants: &'v [V]) -> Option<&'v V>
+    where
+        H: AsRef<[u8]>,
+    {
+        if variants.is_empty() || !self.is_enabled_for(id.as_ref()) {
+            return None;
+        }
+
+        // Salted differently from `bucket_for`, so that the variants are not skewed
+        // by the rollout percentage.
+        let salt = fnv1a(b"variant", fnv1a(self.key.as_bytes(), FNV_OFFSET_BASIS));
+        let i = fnv1a(id.as_ref(), salt) % variants.len() as u64;
+        Some(&variants[i as usize])
+    }
+}
+
+const BUCKETS: u64 = 10_000;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// The 64-bit FNV-1a hash, which is stable across processes and platforms (unlike `DefaultHasher`).
+fn fnv1a(bytes: &[u8], basis: u64) -> u64 {
+    bytes.iter().fold(basis, |h, &b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percent_flag_works() {
+        let flags = FlagSet::new();
+        let enabled = |flags: &FlagSet| {
+            (0..10_000)
+                .filter(|i| flags.percent_flag("f", i.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        flags.set("f", FlagValue::Percent(0.0));
+        assert!(enabled(&flags).is_empty());
+
+        flags.set("f", FlagValue::Percent(10.0));
+        let ten = enabled(&flags);
+        assert!(ten.len() > 900 && ten.len() < 1100, "{}", ten.len());
+
+        flags.set("f", FlagValue::Percent(30.0));
+        let thirty = enabled(&flags);
+        assert!(ten.iter().all(|i| thirty.contains(i)));
+
+        flags.set("f", FlagValue::Percent(100.0));
+        assert_eq!(enabled(&flags).len(), 10_000);
+
+        flags.set("f", "foo");
+        assert!(enabled(&flags).is_empty());
+        assert_eq!(flags.str_flag("f"), Some("foo".to_owned()));
+    }
+
+    #[test]
+    fn variants_are_balanced() {
+        let flags = FlagSet::new();
+        flags.set("f", FlagValue::Percent(50.0));
+        let flag = flags.flag("f");
+
+        let mut counts = [0; 3];
+        for i in 0..10_000 {
+            if let Some(&v) = flag.variant_for(i.to_string(), &[0, 1, 2]) {
+                counts[v] += 1;
+            }
+        }
+        let total: usize = counts.iter().sum();
+        assert!(total > 4500 && total < 5500, "{}", total);
+        assert!(counts.iter().all(|&c| c > 1400 && c < 1950), "{:?}", counts);
+
+        // The key is mixed into buckets.
+        let other = flags.flag("g");
+        assert!((0..100).any(|i| flag.bucket_for(i.to_string()) != other.bucket_for(i.to_string())));
+    }
+
+    #[test]
+    fn typed_getters_work() {
+        let flags = FlagSet::new();
+        flags.set("n", 3i64);
+        assert_eq!(flags.int_flag("n"), Some(3));
+        assert_eq!(flags.int_flag("m"), None);
+        assert!(!flags.bool_flag("n"));
+        assert!(flags.remove("n"));
+        assert!(!flags.remove("n"));
+        assert_eq!(flags.get("n"), None);
+    }
+}
@@ -0,0 +1,320 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::Ordering;
+use primitive::Arc;
+
+use backend::{Backend, Backoff};
+use primitive::AtomicUsize;
+use AtomicImmut;
+
+/// A group of `AtomicImmut`s which are read and written consistently.
+///
+/// A `Group` is a sequence lock which is shared by the writers and readers of several pointers:
+/// writes executed by `Group::write` are serialized and never observed partially
+/// by reads executed by `Group::read` (which retry if a write has happened concurrently).
+///
+/// Note that the consistency is guaranteed only if all the writes to the member pointers
+/// are done through `Group::write`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate atomic_immut;
+///
+/// use atomic_immut::{AtomicImmut, Group};
+///
+/// # fn main() {
+/// let group = Group::new();
+/// let routes = AtomicImmut::new(vec!["a"]);
+/// let peers = AtomicImmut::new(vec![1]);
+///
+/// group.write(|| {
+///     routes.store(vec!["a", "b"]);
+///     peers.store(vec![1, 2]);
+/// });
+///
+/// let (routes, peers) = snapshot!(group, (routes, peers));
+/// assert_eq!(routes.len(), peers.len());
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Group {
+    seq: AtomicUsize,
+}
+impl Group {
+    /// Makes a new `Group` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Executes `f` which writes to the member pointers, excluding other writers.
+    ///
+    /// Readers of this group retry until `f` finishes.
+    pub fn write<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let mut snooze = Backoff::new().start();
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if !is_writing(seq) {
+                // `Acquire` pairs with the `Release` store of the previous writer.
+                // The member pointers are replaced with `Release` ordering,
+                // so a reader which sees any of them also sees this odd sequence number.
+                match self.seq.compare_exchange_weak(
+                    seq,
+                    seq.wrapping_add(1),
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(actual) => seq = actual,
+                }
+            } else {
+                snooze.snooze();
+                seq = self.seq.load(Ordering::Relaxed);
+            }
+        }
+
+        struct Unlock<'a>(&'a AtomicUsize, usize);
+        impl<'a> Drop for Unlock<'a> {
+            fn drop(&mut self) {
+                self.0.store(self.1, Ordering::Release);
+            }
+        }
+        let _unlock = Unlock(&self.seq, seq.wrapping_add(2));
+        f()
+    }
+
+    /// Executes a transaction which reads and writes several member pointers atomically.
+    ///
+    /// `f` reads values through `Transaction::read` and stages new values through `Transaction::write`.
+    /// After `f` returns, the staged values are stored all together (excluding other writers of this group)
+    /// if none of the read pointers has been changed since it was read.
+    /// Otherwise, the staged values are discarded and `f` is called again.
+    ///
+    /// Like `read`, `f` may also be called again if another write has happened while executing it,
+    /// so it should not have side effects other than reading and staging values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::{AtomicImmut, Group};
+    ///
+    /// let group = Group::new();
+    /// let config = AtomicImmut::new(vec![3, 1, 2]);
+    /// let index = AtomicImmut::new(vec![1, 2, 3]);
+    ///
+    /// group.transaction(|tx| {
+    ///     let mut new_config = (*tx.read(&config)).clone();
+    ///     new_config.push(0);
+    ///     let mut new_index = new_config.clone();
+    ///     new_index.sort();
+    ///     tx.write(&config, new_config);
+    ///     tx.write(&index, new_index);
+    /// });
+    /// assert_eq!(*config.load(), [3, 1, 2, 0]);
+    /// assert_eq!(*index.load(), [0, 1, 2, 3]);
+    /// ```
+    pub fn transaction<'a, F, R>(&self, mut f: F) -> R
+    where
+        F: FnMut(&mut Transaction<'a>) -> R,
+    {
+        loop {
+            // Executes `f` as a reader so that it never observes a partially committed transaction.
+            let (result, tx) = self.read(|| {
+                let mut tx = Transaction {
+                    reads: Vec::new(),
+                    writes: Vec::new(),
+                };
+                let result = f(&mut tx);
+                (result, tx)
+            });
+            if self.write(|| tx.commit()) {
+                return result;
+            }
+        }
+    }
+
+    /// Executes `f` which reads the member pointers, and returns its result.
+    ///
+    /// If a write has happened while executing `f`, `f` is called again,
+    /// so the result never reflects a partially executed write.
+    pub fn read<F, R>(&self, mut f: F) -> R
+    where
+        F: FnMut() -> R,
+    {
+        let mut snooze = Backoff::new().start();
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if is_writing(seq) {
+                snooze.snooze();
+                continue;
+            }
+            let result = f();
+            // The member pointers are loaded with `Acquire` ordering in `f`,
+            // so this load is never reordered before them.
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return result;
+            }
+        }
+    }
+}
+
+/// A transaction executed by `Group::transaction`.
+pub struct Transaction<'a> {
+    reads: Vec<(&'a dyn Versioned, u64)>,
+    writes: Vec<Box<dyn FnOnce() + 'a>>,
+}
+impl<'a> Transaction<'a> {
+    /// Loads the value of `cell`, and records it to validate the transaction on commit.
+    pub fn read<T, B>(&mut self, cell: &'a AtomicImmut<T, B>) -> Arc<T>
+    where
+        T: ?Sized,
+        B: Backend,
+    {
+        let (value, version) = cell.load_versioned();
+        self.reads.push((cell, version));
+        value
+    }
+
+    /// Stages `value` which will be stored into `cell` when this transaction is committed.
+    pub fn write<T, B>(&mut self, cell: &'a AtomicImmut<T, B>, value: T)
+    where
+        T: 'a,
+        B: Backend,
+    {
+        self.write_arc(cell, Arc::new(value));
+    }
+
+    /// Stages an already shared value which will be stored into `cell` when this transaction is committed.
+    pub fn write_arc<T, B>(&mut self, cell: &'a AtomicImmut<T, B>, value: Arc<T>)
+    where
+        T: ?Sized + 'a,
+        B: Backend,
+    {
+        self.writes.push(Box::new(move || cell.store_arc(value)));
+    }
+
+    fn commit(self) -> bool {
+        if self
+            .reads
+            .iter()
+            .any(|&(cell, version)| cell.version() != version)
+        {
+            return false;
+        }
+        for write in self.writes {
+            write();
+        }
+        true
+    }
+}
+impl<'a> fmt::Debug for Transaction<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transaction")
+            .field("reads", &self.reads.len())
+            .field("writes", &self.writes.len())
+            .finish()
+    }
+}
+
+trait Versioned {
+    fn version(&self) -> u64;
+}
+impl<T: ?Sized, B: Backend> Versioned for AtomicImmut<T, B> {
+    fn version(&self) -> u64 {
+        AtomicImmut::version(self)
+    }
+}
+
+fn is_writing(seq: usize) -> bool {
+    seq & 1 == 1
+}
+
+/// Loads the values of several `AtomicImmut`s consistently with respect to a `Group`.
+///
+/// `snapshot!(group, (a, b, ...))` is equivalent to `group.read(|| (a.load(), b.load(), ...))`.
+///
+/// See `Group` for an example.
+#[macro_export]
+macro_rules! snapshot {
+    ($group:expr, ($($cell:expr),+ $(,)*)) => {
+        $group.read(|| ($($cell.load(),)+))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+    use AtomicImmut;
+
+    #[test]
+    fn group_works() {
+        let group = Arc::new(Group::new());
+        let a = Arc::new(AtomicImmut::new(0));
+        let b = Arc::new(AtomicImmut::new(0));
+        let handle = {
+            let group = Arc::clone(&group);
+            let a = Arc::clone(&a);
+            let b = Arc::clone(&b);
+            thread::spawn(move || {
+                for i in 1..=1000 {
+                    group.write(|| {
+                        a.store(i);
+                        b.store(i);
+                    });
+                }
+            })
+        };
+        loop {
+            let (a, b) = snapshot!(group, (a, b));
+            assert_eq!(a, b);
+            if *a == 1000 {
+                break;
+            }
+        }
+        handle.join().unwrap();
+        assert_eq!(group.write(|| 10), 10);
+    }
+
+    #[test]
+    fn transaction_works() {
+        let group = Arc::new(Group::new());
+        let a = Arc::new(AtomicImmut::new(0));
+        let b = Arc::new(AtomicImmut::new(0));
+        let thread_count = 4;
+        let handles = (0..thread_count)
+            .map(|_| {
+                let group = Arc::clone(&group);
+                let a = Arc::clone(&a);
+                let b = Arc::clone(&b);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        // The closure may observe an inconsistent state before it is retried,
+                        // so only the result of the committed transaction is checked.
+                        let (x, y) = group.transaction(|tx| {
+                            let x = *tx.read(&a);
+                            let y = *tx.read(&b);
+                            tx.write(&a, x + 1);
+                            tx.write(&b, y + 2);
+                            (x, y)
+                        });
+                        assert_eq!(x * 2, y);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let (a, b) = snapshot!(group, (a, b));
+        assert_eq!(*a, thread_count * 100);
+        assert_eq!(*b, thread_count * 200);
+    }
+}
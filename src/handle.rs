@@ -0,0 +1,142 @@
+use core::fmt;
+use primitive::Arc;
+
+use backend::{Backend, DefaultBackend};
+use {AtomicImmut, Peek};
+
+/// A cloneable handle which can only read the value of a shared `AtomicImmut`.
+///
+/// This is created by `AtomicImmut::split` or `WriteHandle::read_handle`.
+pub struct ReadHandle<T: ?Sized, B = DefaultBackend> {
+    cell: Arc<AtomicImmut<T, B>>,
+}
+impl<T: ?Sized, B: Backend> ReadHandle<T, B> {
+    /// Loads the current value.
+    ///
+    /// See `AtomicImmut::load` for details.
+    pub fn load(&self) -> Arc<T> {
+        self.cell.load()
+    }
+
+    /// Returns a guard which gives a reference to the current value.
+    ///
+    /// See `AtomicImmut::peek` for details.
+    pub fn peek(&self) -> Peek<'_, T, B> {
+        self.cell.peek()
+    }
+
+    /// Returns the version of the current value.
+    ///
+    /// See `AtomicImmut::version` for details.
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+}
+impl<T: ?Sized + fmt::Debug, B: Backend> fmt::Debug for ReadHandle<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadHandle")
+            .field("cell", &self.cell)
+            .finish()
+    }
+}
+impl<T: ?Sized, B> Clone for ReadHandle<T, B> {
+    fn clone(&self) -> Self {
+        ReadHandle {
+            cell: Arc::clone(&self.cell),
+        }
+    }
+}
+
+/// A unique handle which can only write the value of a shared `AtomicImmut`.
+///
+/// This is created by `AtomicImmut::split`.
+///
+/// Since there is exactly one writer, updates never conflict with other writers
+/// (so `update` calls the function exactly once).
+pub struct WriteHandle<T: ?Sized, B = DefaultBackend> {
+    cell: Arc<AtomicImmut<T, B>>,
+}
+impl<T, B: Backend> WriteHandle<T, B> {
+    /// Stores a value.
+    pub fn store(&mut self, value: T) {
+        self.cell.store(value);
+    }
+
+    /// Stores a value, returning the old value.
+    pub fn swap(&mut self, value: T) -> Arc<T> {
+        self.cell.swap(value)
+    }
+
+    /// Updates the value by calling `f` on the current value to get a new value.
+    pub fn update<F>(&mut self, f: F)
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let new = f(&self.cell.peek());
+        self.cell.store(new);
+    }
+}
+impl<T: ?Sized, B: Backend> WriteHandle<T, B> {
+    /// Stores an already shared value.
+    pub fn store_arc(&mut self, value: Arc<T>) {
+        self.cell.store_arc(value);
+    }
+
+    /// Returns a new `ReadHandle` of the same `AtomicImmut`.
+    pub fn read_handle(&self) -> ReadHandle<T, B> {
+        ReadHandle {
+            cell: Arc::clone(&self.cell),
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B: Backend> fmt::Debug for WriteHandle<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteHandle")
+            .field("cell", &self.cell)
+            .finish()
+    }
+}
+
+pub(crate) fn split<T: ?Sized, B>(
+    cell: AtomicImmut<T, B>,
+) -> (ReadHandle<T, B>, WriteHandle<T, B>) {
+    let cell = Arc::new(cell);
+    let reader = ReadHandle {
+        cell: Arc::clone(&cell),
+    };
+    (reader, WriteHandle { cell })
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use AtomicImmut;
+
+    #[test]
+    fn split_works() {
+        let (reader, mut writer) = AtomicImmut::new(0).split();
+        let handles = (0..4)
+            .map(|_| {
+                let reader = reader.clone();
+                thread::spawn(move || {
+                    let mut last = 0;
+                    while last < 100 {
+                        let current = *reader.load();
+                        assert!(current >= last);
+                        last = current;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..100 {
+            writer.update(|v| v + 1);
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*writer.read_handle().load(), 100);
+        assert_eq!(reader.version(), 100);
+    }
+}
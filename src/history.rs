@@ -0,0 +1,96 @@
+use core::fmt;
+use primitive::Arc;
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
+
+/// The bounded history of the values replaced in an `AtomicImmut`.
+///
+/// The values are recorded while holding the writer lock,
+/// so the history is ordered exactly as the stores.
+pub(crate) struct History<T: ?Sized> {
+    #[cfg(feature = "std")]
+    capacity: usize,
+    #[cfg(feature = "std")]
+    entries: Mutex<VecDeque<Arc<T>>>,
+    #[cfg(not(feature = "std"))]
+    _value: core::marker::PhantomData<fn(&T)>,
+}
+impl<T: ?Sized> History<T> {
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn new() -> Self {
+        History {
+            _value: core::marker::PhantomData,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn with_capacity(capacity: usize) -> Self {
+        History {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Records `old`, which has just been replaced. Must be called while holding the writer lock.
+    #[cfg(feature = "std")]
+    pub fn record(&self, old: &Arc<T>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries();
+        if entries.len() == self.capacity {
+            entries.pop_back();
+        }
+        entries.push_front(Arc::clone(old));
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn record(&self, _old: &Arc<T>) {}
+
+    /// Returns the recorded values, the most recently replaced one first.
+    #[cfg(feature = "std")]
+    pub fn snapshot(&self) -> Vec<Arc<T>> {
+        self.entries().iter().cloned().collect()
+    }
+
+    /// Removes the `steps` most recent values and returns the oldest of them.
+    /// Must be called while holding the writer lock.
+    #[cfg(feature = "std")]
+    pub fn rewind(&self, steps: usize) -> Result<Arc<T>, usize> {
+        let mut entries = self.entries();
+        if steps == 0 || entries.len() < steps {
+            return Err(entries.len());
+        }
+        let mut removed = entries.drain(..steps).collect::<Vec<_>>();
+        Ok(removed.pop().expect("never fails"))
+    }
+
+    #[cfg(feature = "std")]
+    fn entries(&self) -> ::std::sync::MutexGuard<'_, VecDeque<Arc<T>>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "std")]
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn capacity(&self) -> usize {
+        0
+    }
+}
+impl<T: ?Sized> fmt::Debug for History<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("History")
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
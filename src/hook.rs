@@ -0,0 +1,111 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use primitive::Arc;
+#[cfg(feature = "std")]
+use std::sync::{PoisonError, RwLock};
+
+/// The identifier of a hook registered by `AtomicImmut::register_on_store`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HookId(u64);
+
+#[cfg(feature = "std")]
+pub(crate) type HookFn<T> = Arc<dyn Fn(&Arc<T>, &Arc<T>) + Send + Sync>;
+
+#[cfg(feature = "std")]
+type HookList<T> = Arc<Vec<(HookId, HookFn<T>)>>;
+
+/// The hooks called after every successful store.
+pub(crate) struct Hooks<T: ?Sized> {
+    #[cfg(feature = "std")]
+    count: AtomicUsize,
+    #[cfg(feature = "std")]
+    next_id: AtomicU64,
+    #[cfg(feature = "std")]
+    hooks: RwLock<HookList<T>>,
+    #[cfg(not(feature = "std"))]
+    _value: core::marker::PhantomData<fn(&T)>,
+}
+impl<T: ?Sized> Hooks<T> {
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Hooks {
+            count: AtomicUsize::new(0),
+            next_id: AtomicU64::new(0),
+            hooks: RwLock::new(Arc::new(Vec::new())),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn new() -> Self {
+        Hooks {
+            _value: core::marker::PhantomData,
+        }
+    }
+
+    /// Returns a clone of `new` if there are hooks which need it.
+    #[cfg(feature = "std")]
+    pub fn watch(&self, new: &Arc<T>) -> Option<Arc<T>> {
+        // Just a hint for the fast path: hooks registered concurrently may or may not be called.
+        if self.count.load(Ordering::Relaxed) == 0 {
+            None
+        } else {
+            Some(Arc::clone(new))
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn watch(&self, _new: &Arc<T>) -> Option<Arc<T>> {
+        None
+    }
+
+    #[cfg(feature = "std")]
+    pub fn call(&self, old: &Arc<T>, new: &Arc<T>) {
+        // Hooks are called without the lock so that they can (un)register hooks.
+        let hooks = Arc::clone(&self.hooks.read().unwrap_or_else(PoisonError::into_inner));
+        for (_, hook) in hooks.iter() {
+            hook(old, new);
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn call(&self, _old: &Arc<T>, _new: &Arc<T>) {}
+
+    #[cfg(feature = "std")]
+    pub fn register(&self, hook: HookFn<T>) -> HookId {
+        let id = HookId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut hooks = self.hooks.write().unwrap_or_else(PoisonError::into_inner);
+        let mut new = Vec::clone(&hooks);
+        new.push((id, hook));
+        *hooks = Arc::new(new);
+        self.count.store(hooks.len(), Ordering::Relaxed);
+        id
+    }
+
+    #[cfg(feature = "std")]
+    pub fn unregister(&self, id: HookId) -> bool {
+        let mut hooks = self.hooks.write().unwrap_or_else(PoisonError::into_inner);
+        if !hooks.iter().any(|&(i, _)| i == id) {
+            return false;
+        }
+        let new = hooks.iter().filter(|&&(i, _)| i != id).cloned().collect();
+        *hooks = Arc::new(new);
+        self.count.store(hooks.len(), Ordering::Relaxed);
+        true
+    }
+
+    #[cfg(feature = "std")]
+    fn len(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn len(&self) -> usize {
+        0
+    }
+}
+impl<T: ?Sized> fmt::Debug for Hooks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hooks").field("len", &self.len()).finish()
+    }
+}
@@ -0,0 +1,161 @@
+//! Hot-swappable functions.
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::hotswap::HotSwapFn;
+//!
+//! let handler = HotSwapFn::new(|name: &str| format!("Hello, {}!", name));
+//! assert_eq!(handler.call("world"), "Hello, world!");
+//!
+//! handler.replace(|name: &str| format!("Bye, {}!", name));
+//! assert_eq!(handler.call("world"), "Bye, world!");
+//! ```
+use core::fmt;
+use primitive::Arc;
+
+use AtomicImmut;
+
+/// The function held by `HotSwapFn<Args, Out>`.
+pub type DynFn<Args, Out> = dyn Fn(Args) -> Out + Send + Sync;
+
+/// A function which can be replaced at runtime.
+///
+/// Functions taking multiple arguments are represented by `Args` tuples.
+pub struct HotSwapFn<Args, Out> {
+    f: AtomicImmut<DynFn<Args, Out>>,
+}
+impl<Args: 'static, Out: 'static> HotSwapFn<Args, Out> {
+    /// Makes a new `HotSwapFn` instance which calls `f`.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Args) -> Out + Send + Sync + 'static,
+    {
+        Self::from_arc(dyn_arc!(f))
+    }
+
+    /// Makes a new `HotSwapFn` instance which calls the shared function `f`.
+    pub fn from_arc(f: Arc<DynFn<Args, Out>>) -> Self {
+        HotSwapFn {
+            f: AtomicImmut::from_arc(f),
+        }
+    }
+
+    /// Makes a new `HotSwapFn` instance which does nothing but returns `Out::default()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::hotswap::HotSwapFn;
+    ///
+    /// let on_event = HotSwapFn::<u32, ()>::noop();
+    /// on_event.call(1);
+    ///
+    /// let limit = HotSwapFn::<(), usize>::noop();
+    /// assert_eq!(limit.call(()), 0);
+    /// ```
+    pub fn noop() -> Self
+    where
+        Out: Default,
+    {
+        Self::new(|_| Out::default())
+    }
+
+    /// Calls the current function with `args`.
+    ///
+    /// The function is kept alive until the call finishes
+    /// even if it is replaced meanwhile (and replacing never waits for running calls).
+    pub fn call(&self, args: Args) -> Out {
+        let f = self.f.load();
+        f(args)
+    }
+
+    /// Replaces the function with `f`, returning the previous one.
+    ///
+    /// Calls started after this method returns invoke `f`.
+    pub fn replace<F>(&self, f: F) -> Arc<DynFn<Args, Out>>
+    where
+        F: Fn(Args) -> Out + Send + Sync + 'static,
+    {
+        self.replace_arc(dyn_arc!(f))
+    }
+
+    /// Replaces the function with the shared function `f`, returning the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::hotswap::{DynFn, HotSwapFn};
+    ///
+    /// let double: Arc<DynFn<i32, i32>> = Arc::new(|x| x * 2);
+    /// let f = HotSwapFn::new(|x: i32| x + 1);
+    /// let previous = f.replace_arc(Arc::clone(&double));
+    /// assert_eq!(f.call(10), 20);
+    ///
+    /// f.replace_arc(previous);
+    /// assert_eq!(f.call(10), 11);
+    /// ```
+    pub fn replace_arc(&self, f: Arc<DynFn<Args, Out>>) -> Arc<DynFn<Args, Out>> {
+        self.f.swap_arc(f)
+    }
+
+    /// Returns the current function.
+    pub fn get(&self) -> Arc<DynFn<Args, Out>> {
+        self.f.load()
+    }
+
+    /// Returns the underlying `AtomicImmut` (e.g., to subscribe to replacements).
+    pub fn as_immut(&self) -> &AtomicImmut<DynFn<Args, Out>> {
+        &self.f
+    }
+}
+impl<Args: 'static, Out: Default + 'static> Default for HotSwapFn<Args, Out> {
+    fn default() -> Self {
+        Self::noop()
+    }
+}
+impl<Args, Out> fmt::Debug for HotSwapFn<Args, Out> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HotSwapFn").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn replace_does_not_wait_for_running_calls() {
+        let f = Arc::new(HotSwapFn::<(), usize>::default());
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        f.replace(move |()| {
+            entered_tx.send(()).unwrap();
+            release_rx.lock().unwrap().recv().unwrap();
+            1
+        });
+
+        let handle = {
+            let f = Arc::clone(&f);
+            thread::spawn(move || f.call(()))
+        };
+        entered_rx.recv().unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        {
+            let calls = Arc::clone(&calls);
+            f.replace(move |()| calls.fetch_add(1, Ordering::SeqCst) + 2);
+        }
+        assert_eq!(f.call(()), 2);
+
+        release_tx.send(()).unwrap();
+        assert_eq!(handle.join().unwrap(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,204 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use AtomicImmut;
+
+/// An `AtomicImmut` whose initial value is computed on the first access.
+///
+/// The first access (e.g., `load`) runs `init` exactly once, and other threads accessing
+/// this cell meanwhile wait for it to finish.
+/// After that, this behaves exactly like the `AtomicImmut` which it dereferences to.
+///
+/// If `init` panics, the panic is propagated and subsequent accesses panic as well.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutLazy;
+///
+/// let value = AtomicImmutLazy::new(|| vec![1, 2, 3]);
+/// assert!(!value.is_initialized());
+///
+/// assert_eq!(*value.load(), [1, 2, 3]);
+/// assert!(value.is_initialized());
+///
+/// value.store(vec![4]);
+/// assert_eq!(*value.load(), [4]);
+/// ```
+pub struct AtomicImmutLazy<T, F = fn() -> T> {
+    cell: OnceLock<AtomicImmut<T>>,
+    init: UnsafeCell<Option<F>>,
+}
+impl<T, F: FnOnce() -> T> AtomicImmutLazy<T, F> {
+    /// Makes a new `AtomicImmutLazy` instance which is initialized by `init` on the first access.
+    pub const fn new(init: F) -> Self {
+        AtomicImmutLazy {
+            cell: OnceLock::new(),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+
+    /// Returns `true` if the initial value has been computed.
+    pub fn is_initialized(&self) -> bool {
+        self.cell.get().is_some()
+    }
+
+    /// Returns the underlying `AtomicImmut`, initializing it if needed.
+    pub fn force(&self) -> &AtomicImmut<T> {
+        self.cell.get_or_init(|| {
+            // `OnceLock` runs this closure at most once at a time,
+            // so no other thread accesses `init` concurrently.
+            let init = unsafe { (*self.init.get()).take() };
+            let init = init.expect("The initializer of `AtomicImmutLazy` has panicked");
+            AtomicImmut::new(init())
+        })
+    }
+}
+impl<T, F: FnOnce() -> T> Deref for AtomicImmutLazy<T, F> {
+    type Target = AtomicImmut<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.force()
+    }
+}
+impl<T: fmt::Debug, F> fmt::Debug for AtomicImmutLazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.cell.get() {
+            Some(cell) => fmt::Debug::fmt(cell, f),
+            None => f.write_str("AtomicImmutLazy { <uninitialized> }"),
+        }
+    }
+}
+unsafe impl<T: Send + Sync, F: Send> Sync for AtomicImmutLazy<T, F> {}
+
+/// An `AtomicImmut` which can be declared as a `static` item.
+///
+/// This is usually declared via the `atomic_immut!` macro.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::StaticImmut;
+///
+/// static VALUE: StaticImmut<Vec<u8>> = StaticImmut::new(|| vec![1, 2, 3]);
+///
+/// assert_eq!(*VALUE.load(), [1, 2, 3]);
+/// VALUE.store(vec![4]);
+/// assert_eq!(*VALUE.load(), [4]);
+/// ```
+pub type StaticImmut<T> = AtomicImmutLazy<T>;
+
+/// Declares `static` `AtomicImmut`s whose initial values are computed on the first access.
+///
+/// Each declared item is a `StaticImmut<T>` (i.e., `AtomicImmutLazy<T>`), which dereferences to `AtomicImmut<T>`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate atomic_immut;
+///
+/// #[derive(Debug, Default)]
+/// struct Config {
+///     timeout: u32,
+/// }
+///
+/// atomic_immut! {
+///     static CONFIG: Config = Config::default();
+///     pub static NAMES: Vec<String> = vec!["foo".to_owned()];
+/// }
+///
+/// fn main() {
+///     assert_eq!(CONFIG.load().timeout, 0);
+///     CONFIG.store(Config { timeout: 10 });
+///     assert_eq!(CONFIG.load().timeout, 10);
+///     assert_eq!(NAMES.load().len(), 1);
+/// }
+/// ```
+#[macro_export]
+macro_rules! atomic_immut {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident : $t:ty = $init:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::StaticImmut<$t> = $crate::StaticImmut::new({
+                fn init() -> $t {
+                    $init
+                }
+                init
+            });
+        )*
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+
+    static INITS: AtomicUsize = AtomicUsize::new(0);
+
+    atomic_immut! {
+        /// A counter.
+        static COUNTER: usize = {
+            INITS.fetch_add(1, Ordering::SeqCst);
+            0
+        };
+    }
+
+    #[test]
+    fn atomic_immut_macro_works() {
+        assert_eq!(
+            format!("{:?}", COUNTER),
+            "AtomicImmutLazy { <uninitialized> }"
+        );
+        let handles = (0..4)
+            .map(|_| thread::spawn(|| COUNTER.update(|v| v + 1)))
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*COUNTER.load(), 4);
+        assert_eq!(INITS.load(Ordering::SeqCst), 1);
+        assert_eq!(format!("{:?}", COUNTER), "AtomicImmut { value: 4 }");
+    }
+
+    #[test]
+    fn lazy_works() {
+        let inits = Arc::new(AtomicUsize::new(0));
+        let lazy = {
+            let inits = Arc::clone(&inits);
+            Arc::new(AtomicImmutLazy::new(move || {
+                inits.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(10));
+                10
+            }))
+        };
+        let handles = (0..4)
+            .map(|_| {
+                let lazy = Arc::clone(&lazy);
+                thread::spawn(move || *lazy.load())
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), 10);
+        }
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn lazy_init_panic_is_propagated() {
+        let lazy = AtomicImmutLazy::new(|| -> usize { panic!("oops") });
+        let result = panic::catch_unwind(AssertUnwindSafe(|| *lazy.load()));
+        assert!(result.is_err());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| *lazy.load()));
+        assert!(result.is_err());
+        assert!(!lazy.is_initialized());
+    }
+}
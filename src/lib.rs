@@ -19,11 +19,215 @@
 //! while v.load().len() == 1 {}
 //! assert_eq!(&*v.load(), &vec![0, 1]);
 //! ```
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(missing_docs)]
-use std::mem;
-use std::ptr;
-use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
-use std::sync::Arc;
+#![cfg_attr(feature = "nightly", feature(strict_provenance_lints))]
+#![cfg_attr(
+    feature = "nightly",
+    deny(fuzzy_provenance_casts, lossy_provenance_casts)
+)]
+#[cfg(all(feature = "portable-atomic", feature = "std"))]
+compile_error!(
+    "The `portable-atomic` feature is for `no_std` targets: disable the default features"
+);
+
+extern crate alloc;
+#[cfg(feature = "bincode")]
+extern crate bincode;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "epoch")]
+extern crate crossbeam_epoch;
+#[cfg(all(test, feature = "async"))]
+extern crate futures;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(feature = "async")]
+extern crate futures_sink;
+#[cfg(feature = "im")]
+extern crate im;
+#[cfg(loom)]
+extern crate loom;
+#[cfg(feature = "shm")]
+extern crate memmap2;
+#[cfg(feature = "reload")]
+extern crate notify;
+#[cfg(feature = "portable-atomic")]
+extern crate portable_atomic;
+#[cfg(feature = "portable-atomic")]
+extern crate portable_atomic_util;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(any(feature = "json", all(test, feature = "serde")))]
+extern crate serde_json;
+#[cfg(all(unix, feature = "signal"))]
+extern crate signal_hook;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+pub use any::{AnyValue, AtomicImmutAny};
+pub use builder::AtomicImmutBuilder;
+pub use cache::Cache;
+pub use copy::AtomicImmutCopy;
+pub use error::{Lagged, RollbackError, TypeMismatch, UpdateContended};
+#[cfg(feature = "std")]
+pub use family::AtomicImmutFamily;
+pub use group::{Group, Transaction};
+pub use handle::{ReadHandle, WriteHandle};
+pub use hook::HookId;
+#[cfg(feature = "std")]
+pub use lazy::{AtomicImmutLazy, StaticImmut};
+pub use local::LocalImmut;
+pub use map::{Map, MapGuard};
+pub use on_drop::{DropHook, OnDrop};
+pub use option::AtomicImmutOption;
+#[cfg(feature = "im")]
+pub use persistent::{AtomicImmutHashMap, AtomicImmutVector};
+#[cfg(feature = "std")]
+pub use queue::UpdateQueue;
+pub use scoped::OverrideGuard;
+#[cfg(feature = "bytes")]
+pub use shared_bytes::AtomicImmutBytes;
+#[cfg(feature = "async")]
+pub use sink::StoreSink;
+pub use small::{AtomicImmutSmall, SmallValue};
+pub use stats::Stats;
+#[cfg(feature = "async")]
+pub use stream::{Snapshots, StreamPolicy};
+#[cfg(feature = "async")]
+pub use subscribe::Changed;
+pub use subscribe::Subscriber;
+#[cfg(feature = "std")]
+pub use validate::ValidationError;
+pub use weak::AtomicImmutWeak;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem;
+use core::ops::Deref;
+use core::ptr;
+use core::sync::atomic::Ordering;
+use primitive::Arc;
+#[cfg(feature = "std")]
+use std::panic::AssertUnwindSafe;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant, SystemTime};
+
+use backend::{Backend, DefaultBackend};
+use checks::{Checks, ReadToken};
+use extras::LazyExtras;
+#[cfg(feature = "std")]
+use history::History;
+use lock::WriterLock;
+use padded::CachePadded;
+use primitive::AtomicPtr;
+use rcu::Callback;
+use stats::StatsCounter;
+use subscribe::Notifier;
+use trace::Tracer;
+#[cfg(feature = "std")]
+use validate::Validator;
+
+/// Makes an `Arc` of a trait object from a sized value.
+///
+/// `portable_atomic_util::Arc` does not support unsized coercions, so the value is boxed first.
+#[cfg(not(feature = "portable-atomic"))]
+macro_rules! dyn_arc {
+    ($value:expr) => {
+        Arc::new($value)
+    };
+}
+#[cfg(feature = "portable-atomic")]
+macro_rules! dyn_arc {
+    ($value:expr) => {
+        Arc::from(::alloc::boxed::Box::new($value) as ::alloc::boxed::Box<_>)
+    };
+}
+
+pub mod backend;
+#[cfg(feature = "std")]
+pub mod codec;
+pub mod collections;
+#[cfg(feature = "std")]
+pub mod expiring;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod flags;
+pub mod hotswap;
+#[cfg(feature = "num")]
+pub mod num;
+#[cfg(feature = "persist")]
+pub mod persist;
+#[cfg(feature = "std")]
+pub mod reclaim;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "reload")]
+pub mod reload;
+#[cfg(feature = "shm")]
+pub mod shm;
+pub mod sync;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "std")]
+pub mod throttle;
+
+mod any;
+mod builder;
+mod cache;
+mod checks;
+mod copy;
+mod error;
+mod extras;
+#[cfg(feature = "std")]
+mod family;
+mod group;
+mod handle;
+mod history;
+mod hook;
+#[cfg(feature = "std")]
+#[macro_use]
+mod lazy;
+mod local;
+mod lock;
+mod map;
+mod on_drop;
+mod option;
+mod padded;
+#[cfg(feature = "im")]
+mod persistent;
+mod primitive;
+#[cfg(feature = "std")]
+mod queue;
+mod rcu;
+mod scoped;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "bytes")]
+mod shared_bytes;
+#[cfg(feature = "async")]
+mod sink;
+mod small;
+mod stats;
+#[cfg(feature = "async")]
+mod stream;
+mod subscribe;
+mod timestamp;
+mod trace;
+mod validate;
+mod weak;
 
 /// A thread-safe pointer for immutable value.
 ///
@@ -35,6 +239,13 @@ use std::sync::Arc;
 /// `AtomicImmut` is useful for sharing rarely updated and
 /// complex (e.g., hashmap) data structures between threads.
 ///
+/// The synchronization strategy between readers and writers is determined by
+/// the backend type parameter `B` (see the [`backend`](backend/index.html) module).
+///
+/// The state of the optional features (e.g., hooks, history, validators and subscribers)
+/// is allocated on the first use,
+/// so a pointer which uses none of them only consists of a few words besides the backend.
+///
 /// # Examples
 ///
 /// ```
@@ -59,39 +270,185 @@ use std::sync::Arc;
 /// assert_eq!(v.load().get("foo"), Some(&0));
 /// assert_eq!(v.load().get("bar"), Some(&1));
 /// ```
-#[derive(Debug)]
-pub struct AtomicImmut<T> {
-    ptr: AtomicPtr<T>,
-    rwlock: SpinRwLock,
+pub struct AtomicImmut<T: ?Sized, B = DefaultBackend> {
+    ptr: CachePadded<AtomicPtr<Slot<T>>>,
+    backend: B,
+    notifier: Notifier,
+    stats: StatsCounter,
+    tracer: Tracer<T>,
+    extras: LazyExtras<T>,
+    checks: Checks,
+    writer: WriterLock,
 }
 impl<T> AtomicImmut<T> {
     /// Makes a new `AtomicImmut` instance.
     pub fn new(value: T) -> Self {
-        let ptr = AtomicPtr::new(to_arc_ptr(value));
-        let rwlock = SpinRwLock::new();
-        AtomicImmut { ptr, rwlock }
+        Self::with_backend(value, DefaultBackend::new())
     }
 
-    /// Loads the value from this pointer.
+    /// Makes a new `AtomicImmut` instance whose values always pass `validator`.
+    ///
+    /// Every value to be stored is validated before it becomes visible,
+    /// whichever method stores it.
+    /// `try_store` and `try_store_arc` return the rejection as an error,
+    /// while the other methods (e.g., `store` and `update`) panic without storing the value.
+    /// `get_mut` always returns `None` since it could bypass the validation.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValidationError` if `initial` is rejected by `validator`.
     ///
     /// # Examples
     ///
     /// ```
     /// use atomic_immut::AtomicImmut;
     ///
-    /// let value = AtomicImmut::new(5);
+    /// let port = AtomicImmut::with_validator(80, |&p: &u32| {
+    ///     if p < 65536 { Ok(()) } else { Err("out of range") }
+    /// }).unwrap();
+    ///
+    /// assert!(port.try_store(8080).is_ok());
+    /// assert!(port.try_store(100_000).is_err());
+    /// assert_eq!(*port.load(), 8080);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_validator<F, E>(initial: T, validator: F) -> Result<Self, ValidationError>
+    where
+        F: Fn(&T) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let validator = Validator::new(Arc::new(move |v: &T| {
+            validator(v).map_err(ValidationError::new)
+        }));
+        validator.validate(&initial)?;
+        let mut this = Self::new(initial);
+        this.extras.get_mut().validator = validator;
+        Ok(this)
+    }
+
+    /// Makes a new `AtomicImmut` instance which holds `f(source)`,
+    /// and recomputes it every time a new value is stored into `source`.
+    ///
+    /// The recomputation is done on the thread which stores the value into `source`
+    /// (using the hook mechanism of `register_on_store`).
+    /// After all the `Arc`s of the returned instance are dropped, the recomputation stops.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let source = Arc::new(AtomicImmut::new(vec![1, 2, 3]));
+    /// let sum = AtomicImmut::derive_from(&source, |v: &Vec<i32>| v.iter().sum::<i32>());
+    /// assert_eq!(*sum.load(), 6);
+    ///
+    /// source.store(vec![4, 5]);
+    /// assert_eq!(*sum.load(), 9);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn derive_from<S, SB, F>(source: &Arc<AtomicImmut<S, SB>>, f: F) -> Arc<Self>
+    where
+        S: ?Sized + Send + Sync + 'static,
+        SB: Backend + Send + Sync + 'static,
+        F: Fn(&S) -> T + Send + Sync + 'static,
+        T: Send + Sync + 'static,
+    {
+        let derived = Arc::new(AtomicImmut::new(f(&source.load())));
+        let weak_source = Arc::downgrade(source);
+        let weak_derived = Arc::downgrade(&derived);
+        let lock = Mutex::new(());
+        source.register_on_store(move |_, _| {
+            // Hooks of concurrent stores may be called in any order,
+            // so the latest value is loaded from `source` instead of using the given one.
+            let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+            if let (Some(source), Some(derived)) = (weak_source.upgrade(), weak_derived.upgrade()) {
+                derived.store(f(&source.load()));
+            }
+        });
+        derived
+    }
+
+    /// Makes a new `AtomicImmut` instance which holds `f(a, b)`,
+    /// and recomputes it every time a new value is stored into `a` or `b`.
+    ///
+    /// See `derive_from` for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let a = Arc::new(AtomicImmut::new(1));
+    /// let b = Arc::new(AtomicImmut::new(2));
+    /// let sum = AtomicImmut::combine(&a, &b, |a: &i32, b: &i32| a + b);
+    /// assert_eq!(*sum.load(), 3);
+    ///
+    /// a.store(10);
+    /// assert_eq!(*sum.load(), 12);
+    ///
+    /// b.store(20);
+    /// assert_eq!(*sum.load(), 30);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn combine<A, AB, C, CB, F>(
+        a: &Arc<AtomicImmut<A, AB>>,
+        b: &Arc<AtomicImmut<C, CB>>,
+        f: F,
+    ) -> Arc<Self>
+    where
+        A: ?Sized + Send + Sync + 'static,
+        AB: Backend + Send + Sync + 'static,
+        C: ?Sized + Send + Sync + 'static,
+        CB: Backend + Send + Sync + 'static,
+        F: Fn(&A, &C) -> T + Send + Sync + 'static,
+        T: Send + Sync + 'static,
+    {
+        let derived = Arc::new(AtomicImmut::new(f(&a.load(), &b.load())));
+        let weak_a = Arc::downgrade(a);
+        let weak_b = Arc::downgrade(b);
+        let weak_derived = Arc::downgrade(&derived);
+        let lock = Mutex::new(());
+        let recompute = Arc::new(move || {
+            let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+            if let (Some(a), Some(b), Some(derived)) =
+                (weak_a.upgrade(), weak_b.upgrade(), weak_derived.upgrade())
+            {
+                derived.store(f(&a.load(), &b.load()));
+            }
+        });
+        {
+            let recompute = Arc::clone(&recompute);
+            a.register_on_store(move |_, _| recompute());
+        }
+        b.register_on_store(move |_, _| recompute());
+        derived
+    }
+}
+impl<T, B: Backend> AtomicImmut<T, B> {
+    /// Makes a new `AtomicImmut` instance which uses the given synchronization backend.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    /// use atomic_immut::backend::StdBackend;
+    ///
+    /// let value = AtomicImmut::with_backend(5, StdBackend::new());
     /// assert_eq!(*value.load(), 5);
     /// ```
-    pub fn load(&self) -> Arc<T> {
-        let _guard = self.rwlock.rlock();
-        let ptr = self.ptr.load(Ordering::SeqCst);
-        let value = unsafe { Arc::from_raw(ptr) };
-        mem::forget(Arc::clone(&value));
-        value
+    pub fn with_backend(value: T, backend: B) -> Self {
+        Self::from_arc_with_backend(Arc::new(value), backend)
     }
 
     /// Stores a value into this pointer.
     ///
+    /// # Panics
+    ///
+    /// Panics if `value` is rejected by the validator of this pointer (see `with_validator`).
+    /// Use `try_store` to handle the rejection instead.
+    ///
     /// # Examples
     ///
     /// ```
@@ -107,10 +464,68 @@ impl<T> AtomicImmut<T> {
         self.swap(value);
     }
 
+    /// Stores a value into this pointer if it passes the validator (see `with_validator`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::with_validator(0, |&v: &i32| {
+    ///     if v >= 0 { Ok(()) } else { Err("negative") }
+    /// }).unwrap();
+    ///
+    /// let e = value.try_store(-1).unwrap_err();
+    /// assert_eq!(e.to_string(), "Invalid value: negative");
+    /// assert_eq!(*value.load(), 0);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn try_store(&self, value: T) -> Result<(), ValidationError> {
+        self.try_store_arc(Arc::new(value))
+    }
+
+    /// Stores a value into this pointer like `store`,
+    /// but the old value is dropped on the background reclaimer thread.
+    ///
+    /// This is useful when the destructor of the value is expensive.
+    /// See the [`reclaim`](reclaim/index.html) module for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is rejected by the validator of this pointer (see `with_validator`).
+    /// Use `try_store` to handle the rejection instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::{reclaim, AtomicImmut};
+    ///
+    /// let value = AtomicImmut::new(vec![0; 1024]);
+    /// value.store_deferred(vec![1; 1024]);
+    /// assert_eq!(value.load()[0], 1);
+    ///
+    /// reclaim::flush();
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn store_deferred(&self, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        reclaim::defer_drop(self.swap(value));
+    }
+
     /// Updates the value of this pointer by calling `f` on the value to get a new value.
     ///
     /// The function `f` may be called more than once when there is a conflict with other threads.
     ///
+    /// If `f` panics, the panic propagates to the caller and this pointer is left unchanged
+    /// (nothing leaks and no lock is left held, so the pointer remains usable).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value returned by `f` is rejected by the validator of this pointer (see `with_validator`).
+    /// The same goes for the other update methods (e.g., `try_update` and `fetch_update`).
+    ///
     /// # Examples
     ///
     /// ```
@@ -122,28 +537,105 @@ impl<T> AtomicImmut<T> {
     /// value.update(|v| *v * 2);
     /// assert_eq!(*value.load(), 10);
     /// ```
-    pub fn update<F>(&self, f: F)
+    pub fn update<F>(&self, mut f: F)
     where
-        F: for<'a> Fn(&'a T) -> T,
+        F: for<'a> FnMut(&'a T) -> T,
     {
-        loop {
-            let old = self.load();
+        self.try_update(|v| Some(f(v)));
+    }
 
-            let new = to_arc_ptr(f(&old));
-            let old = Arc::into_raw(old) as *mut _;
-            unsafe { Arc::from_raw(old) };
+    /// Updates the value of this pointer like `update`, but catches a panic of `f`.
+    ///
+    /// If `f` panics, this pointer is left unchanged and the panic payload is returned as an error.
+    /// Since the values of this pointer are immutable, a panic can never leave them half-updated,
+    /// so `f` is not required to be `UnwindSafe`
+    /// (but the states captured by `f` itself may be, as usual).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(vec![1, 2]);
+    /// let result = value.update_catch(|v| {
+    ///     if v.len() > 1 {
+    ///         panic!("too long");
+    ///     }
+    ///     v.clone()
+    /// });
+    /// assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"too long"));
+    /// assert_eq!(*value.load(), [1, 2]);
+    ///
+    /// assert!(value.update_catch(|v| v[..1].to_vec()).is_ok());
+    /// assert_eq!(*value.load(), [1]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn update_catch<F>(&self, mut f: F) -> ::std::thread::Result<()>
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+    {
+        self.try_update_result(|v| ::std::panic::catch_unwind(AssertUnwindSafe(|| f(v))))
+    }
 
-            let _guard = self.rwlock.wlock();
-            if self.ptr.compare_and_swap(old, new, Ordering::SeqCst) == old {
-                unsafe { Arc::from_raw(old) };
-                break;
-            } else {
-                unsafe { Arc::from_raw(new) };
-            }
+    /// Same as `update` except that this method returns the number of the retries
+    /// caused by conflicts with other writers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// assert_eq!(value.update_stats(|v| *v * 2), 0);
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn update_stats<F>(&self, mut f: F) -> usize
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+    {
+        let mut retries = 0;
+        let result =
+            self.update_core_with_retries(|v| Ok::<_, Infallible>(Arc::new(f(v))), &mut retries);
+        if let Err(e) = result {
+            match e {}
         }
+        retries
     }
 
-    /// Stores a value into this pointer, returning the old value.
+    /// Updates the value of this pointer like `update`,
+    /// but the old value is dropped on the background reclaimer thread.
+    ///
+    /// See the [`reclaim`](reclaim/index.html) module for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::{reclaim, AtomicImmut};
+    ///
+    /// let value = AtomicImmut::new(vec![0; 1024]);
+    /// value.update_deferred(|v| v.iter().map(|x| x + 1).collect());
+    /// assert_eq!(value.load()[0], 1);
+    ///
+    /// reclaim::flush();
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn update_deferred<F>(&self, mut f: F)
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+        T: Send + Sync + 'static,
+    {
+        let result = self.update_core(|v| Ok::<_, Infallible>(Arc::new(f(v))));
+        match result {
+            Ok((old, _)) => reclaim::defer_drop(old),
+            Err(e) => match e {},
+        }
+    }
+
+    /// Tries to update the value of this pointer by calling `f` on the value to get a new value.
+    ///
+    /// If `f` returns `None`, this pointer is left untouched and `false` is returned.
+    ///
+    /// The function `f` may be called more than once when there is a conflict with other threads.
     ///
     /// # Examples
     ///
@@ -151,94 +643,1877 @@ impl<T> AtomicImmut<T> {
     /// use atomic_immut::AtomicImmut;
     ///
     /// let value = AtomicImmut::new(5);
-    /// assert_eq!(*value.load(), 5);
     ///
-    /// let old = value.swap(1);
-    /// assert_eq!(*value.load(), 1);
-    /// assert_eq!(*old, 5);
+    /// assert!(value.try_update(|v| if *v < 10 { Some(*v * 2) } else { None }));
+    /// assert_eq!(*value.load(), 10);
+    ///
+    /// assert!(!value.try_update(|v| if *v < 10 { Some(*v * 2) } else { None }));
+    /// assert_eq!(*value.load(), 10);
     /// ```
-    pub fn swap(&self, value: T) -> Arc<T> {
-        let new = to_arc_ptr(value);
-        let old = {
-            let _guard = self.rwlock.wlock();
-            self.ptr.swap(new, Ordering::SeqCst)
-        };
-        unsafe { Arc::from_raw(old) }
-    }
-}
-unsafe impl<T: Send + Sync> Send for AtomicImmut<T> {}
-unsafe impl<T: Send + Sync> Sync for AtomicImmut<T> {}
-impl<T> Drop for AtomicImmut<T> {
-    fn drop(&mut self) {
-        let ptr = mem::replace(self.ptr.get_mut(), ptr::null_mut());
-        let _ = unsafe { Arc::from_raw(ptr) };
+    pub fn try_update<F>(&self, mut f: F) -> bool
+    where
+        F: for<'a> FnMut(&'a T) -> Option<T>,
+    {
+        self.update_core(|v| f(v).map(Arc::new).ok_or(())).is_ok()
     }
-}
-impl<T: Default> Default for AtomicImmut<T> {
-    fn default() -> Self {
-        Self::new(T::default())
+
+    /// Updates the value of this pointer like `update`,
+    /// but gives up after `max_retries` retries caused by conflicts with other writers.
+    ///
+    /// This is useful for falling back to another strategy (e.g., queueing)
+    /// instead of retrying indefinitely on a heavily contended pointer.
+    /// `f` is called at most `max_retries + 1` times.
+    ///
+    /// Only real conflicts (i.e., another writer stored a value while `f` was running) are counted as retries.
+    /// Since `compare_and_swap` never fails spuriously, `max_retries` of `0` always succeeds without contention.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// assert!(value.try_update_bounded(0, |v| *v * 2).is_ok());
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn try_update_bounded<F>(&self, max_retries: usize, mut f: F) -> Result<(), UpdateContended>
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+    {
+        let mut attempts = 0;
+        self.update_core(|v| {
+            if attempts > max_retries {
+                return Err(UpdateContended::new(max_retries));
+            }
+            attempts += 1;
+            Ok(Arc::new(f(v)))
+        })
+        .map(|_| ())
     }
-}
 
-#[derive(Debug)]
-struct SpinRwLock(AtomicUsize);
-impl SpinRwLock {
-    fn new() -> Self {
-        SpinRwLock(AtomicUsize::new(0))
+    /// Tries to update the value of this pointer by calling `f` on the value to get a new value.
+    ///
+    /// If `f` returns an error, this pointer is left untouched and the error is returned.
+    ///
+    /// The function `f` may be called more than once when there is a conflict with other threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    ///
+    /// assert_eq!(value.try_update_result(|_| "10".parse::<i32>()), Ok(()));
+    /// assert_eq!(*value.load(), 10);
+    ///
+    /// assert!(value.try_update_result(|_| "foo".parse::<i32>()).is_err());
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn try_update_result<F, E>(&self, mut f: F) -> Result<(), E>
+    where
+        F: for<'a> FnMut(&'a T) -> Result<T, E>,
+    {
+        self.update_core(|v| f(v).map(Arc::new)).map(|_| ())
     }
-    fn rlock(&self) -> ReadGuard {
-        let old = self.0.fetch_add(1, Ordering::SeqCst);
-        let mut writers = old >> reader_bits();
-        while writers != 0 {
-            writers = self.0.load(Ordering::SeqCst) >> reader_bits();
+
+    /// Updates the value of this pointer by calling `f` on the value to get a new value,
+    /// returning the old value.
+    ///
+    /// The function `f` may be called more than once when there is a conflict with other threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    ///
+    /// let old = value.fetch_update(|v| *v * 2);
+    /// assert_eq!(*old, 5);
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn fetch_update<F>(&self, mut f: F) -> Arc<T>
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+    {
+        match self.update_core(|v| Ok::<_, Infallible>(Arc::new(f(v)))) {
+            Ok((old, _)) => old,
+            Err(e) => match e {},
         }
-        ReadGuard(self)
     }
-    fn runlock(&self) {
-        self.0.fetch_sub(1, Ordering::SeqCst);
-    }
-    fn wlock(&self) -> WriteGuard {
-        while self.0.fetch_add(1 << reader_bits(), Ordering::SeqCst) != 0 {
-            self.0.fetch_sub(1 << reader_bits(), Ordering::SeqCst);
-            while self.0.load(Ordering::SeqCst) != 0 {}
+
+    /// Updates the value of this pointer by calling `f` on the value to get a new value,
+    /// returning the new value.
+    ///
+    /// The function `f` may be called more than once when there is a conflict with other threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    ///
+    /// let new = value.update_and_fetch(|v| *v * 2);
+    /// assert_eq!(*new, 10);
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn update_and_fetch<F>(&self, mut f: F) -> Arc<T>
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+    {
+        match self.update_core(|v| Ok::<_, Infallible>(Arc::new(f(v)))) {
+            Ok((_, new)) => new,
+            Err(e) => match e {},
         }
-        WriteGuard(self)
     }
-    fn wunlock(&self) {
-        self.0.fetch_sub(1 << reader_bits(), Ordering::SeqCst);
-    }
-}
 
-#[derive(Debug)]
-struct ReadGuard<'a>(&'a SpinRwLock);
-impl<'a> Drop for ReadGuard<'a> {
-    fn drop(&mut self) {
-        self.0.runlock();
+    /// Updates the value of this pointer by calling `f` on the value to get a new value
+    /// along with an auxiliary result, returning the result.
+    ///
+    /// The function `f` may be called more than once when there is a conflict with other threads,
+    /// and the result of the attempt whose value has been stored is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let ids = AtomicImmut::new(vec![3, 7]);
+    ///
+    /// // Allocates an ID during the update.
+    /// let id = ids.update_returning(|v| {
+    ///     let id = v.iter().max().map_or(0, |x| x + 1);
+    ///     let mut v = v.clone();
+    ///     v.push(id);
+    ///     (v, id)
+    /// });
+    /// assert_eq!(id, 8);
+    /// assert_eq!(*ids.load(), [3, 7, 8]);
+    /// ```
+    pub fn update_returning<F, R>(&self, mut f: F) -> R
+    where
+        F: for<'a> FnMut(&'a T) -> (T, R),
+    {
+        let mut output = None;
+        let result = self.update_core(|v| {
+            let (new, r) = f(v);
+            output = Some(r);
+            Ok::<_, Infallible>(Arc::new(new))
+        });
+        match result {
+            Ok(_) => output.expect("Never fails"),
+            Err(e) => match e {},
+        }
     }
-}
 
-#[derive(Debug)]
-struct WriteGuard<'a>(&'a SpinRwLock);
-impl<'a> Drop for WriteGuard<'a> {
-    fn drop(&mut self) {
-        self.0.wunlock();
+    /// Updates the value of this pointer by calling `f` on the value to get a new value,
+    /// excluding other writers during the whole read-compute-store sequence.
+    ///
+    /// Unlike `update`, `f` is called exactly once, so it can be `FnOnce`.
+    /// This is useful when `f` is expensive (e.g., for big values).
+    /// Readers are never blocked, but other writers wait until `f` returns.
+    ///
+    /// Note that writing to the same pointer in `f` causes a deadlock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value returned by `f` is rejected by the validator of this pointer (see `with_validator`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(vec![0, 1]);
+    /// let extra = vec![2, 3];
+    /// value.update_exclusive(move |v| v.iter().cloned().chain(extra).collect());
+    /// assert_eq!(*value.load(), [0, 1, 2, 3]);
+    /// ```
+    pub fn update_exclusive<F>(&self, f: F)
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let (old, new) = {
+            let _lock = self.writer.lock();
+            // While holding the writer lock, the current slot is never retired.
+            let current = unsafe { &*(*self.ptr.load(Ordering::Acquire)).value };
+            let new = Arc::new(f(current));
+            self.assert_valid(&new);
+            let watched = self.watch(&new);
+            (self.replace_locked(new), watched)
+        };
+        unsafe { self.retire(old, new) };
     }
-}
 
-fn to_arc_ptr<T>(value: T) -> *mut T {
-    let boxed = Arc::new(value);
-    Arc::into_raw(boxed) as _
-}
-
-#[inline]
-fn reader_bits() -> usize {
-    mem::size_of::<usize>() * 8 / 2
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::sync::{Arc, Barrier};
+    /// Updates the value of this pointer by mutating it in place.
+    ///
+    /// Like `Arc::make_mut`, the value is cloned only if it is shared with other `Arc` pointers
+    /// (e.g., values returned by `load` which are still alive, values watched by hooks,
+    /// or values kept in the history).
+    /// Otherwise, `f` mutates the value directly without cloning it.
+    ///
+    /// Like `store`, the old value is recorded in the history (see `keep_history`),
+    /// and like `update`, this is counted in `stats`.
+    ///
+    /// This requires a mutable reference to ensure that
+    /// no other threads are concurrently accessing this pointer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value mutated by `f` is rejected by the validator of this pointer (see `with_validator`).
+    /// In that case, the value of this pointer is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let mut value = AtomicImmut::new(vec![0, 1]);
+    /// let old = value.load();
+    ///
+    /// value.update_in_place(|v| v.push(2)); // `old` is alive, so the value is cloned
+    /// assert_eq!(*value.load(), [0, 1, 2]);
+    /// assert_eq!(*old, [0, 1]);
+    ///
+    /// value.update_in_place(|v| v.push(3)); // The value is mutated in place
+    /// assert_eq!(*value.load(), [0, 1, 2, 3]);
+    /// ```
+    pub fn update_in_place<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut T),
+        T: Clone,
+    {
+        let slot = unsafe { &mut *primitive::with_mut(&mut self.ptr, |p| *p) };
+        let value = &mut slot.value;
+        let extras = self.extras.get();
+        let old = extras.and_then(|e| e.hooks.watch(value));
+        match extras {
+            Some(extras) if extras.validator.is_enabled() => {
+                // The value must not be changed if the result is rejected.
+                let mut candidate = T::clone(value);
+                f(&mut candidate);
+                extras.validator.assert_valid(&candidate);
+                extras.history.record(value);
+                *Arc::make_mut(value) = candidate;
+            }
+            _ => {
+                // Recorded before mutating, so a value kept in the history is cloned rather than mutated.
+                if let Some(extras) = extras {
+                    extras.history.record(value);
+                }
+                f(Arc::make_mut(value));
+            }
+        }
+        // Advanced after the value is changed, so a rejected value (or a panic of `f`) leaves the version as it is.
+        let version = self.notifier.advance();
+        slot.version = version;
+        self.stats.record(true, 0);
+        self.announce(version, Some(value));
+        if let (Some(old), Some(extras)) = (old, extras) {
+            extras.hooks.call(&old, value);
+        }
+    }
+
+    /// Stores a value into this pointer, returning the old value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is rejected by the validator of this pointer (see `with_validator`).
+    /// Use `try_store` to handle the rejection instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// assert_eq!(*value.load(), 5);
+    ///
+    /// let old = value.swap(1);
+    /// assert_eq!(*value.load(), 1);
+    /// assert_eq!(*old, 5);
+    /// ```
+    pub fn swap(&self, value: T) -> Arc<T> {
+        self.swap_arc(Arc::new(value))
+    }
+
+    /// Replaces the value of this pointer with `T::default()`, returning the old value.
+    ///
+    /// This is handy for grabbing an accumulated value and resetting the pointer in one atomic step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let batch = AtomicImmut::new(Vec::new());
+    /// batch.update(|v| [&v[..], &[1, 2]].concat());
+    ///
+    /// assert_eq!(*batch.take(), [1, 2]);
+    /// assert!(batch.load().is_empty());
+    /// ```
+    pub fn take(&self) -> Arc<T>
+    where
+        T: Default,
+    {
+        self.swap(T::default())
+    }
+
+    /// Temporarily replaces the value of this pointer,
+    /// restoring the previous value when the returned guard is dropped (even by a panic).
+    ///
+    /// Guards can be nested as long as they are dropped in the reverse order of their creation.
+    /// This is mainly intended for overriding shared settings in tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let timeout = AtomicImmut::new(30);
+    /// {
+    ///     let _guard = timeout.override_scoped(1);
+    ///     assert_eq!(*timeout.load(), 1);
+    ///     {
+    ///         let guard = timeout.override_scoped(0);
+    ///         assert_eq!(**guard.previous(), 1);
+    ///     }
+    ///     assert_eq!(*timeout.load(), 1);
+    /// }
+    /// assert_eq!(*timeout.load(), 30);
+    /// ```
+    pub fn override_scoped(&self, value: T) -> OverrideGuard<'_, T, B> {
+        OverrideGuard::new(self, self.swap(value))
+    }
+
+    /// Stores a value into this pointer only if it differs from the current value.
+    ///
+    /// If the values are equal, this pointer is left untouched
+    /// (neither allocating nor notifying subscribers) and `false` is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is rejected by the validator of this pointer (see `with_validator`).
+    /// Use `try_store` to handle the rejection instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    ///
+    /// assert!(!value.store_if_changed(5));
+    /// assert_eq!(value.version(), 0);
+    ///
+    /// assert!(value.store_if_changed(10));
+    /// assert_eq!(*value.load(), 10);
+    /// assert_eq!(value.version(), 1);
+    /// ```
+    pub fn store_if_changed(&self, value: T) -> bool
+    where
+        T: PartialEq,
+    {
+        // The `Arc` is allocated on the first attempt which finds a different value.
+        let mut value = Some(value);
+        let mut new = None;
+        self.update_core(|current| {
+            if *current == *new.as_deref().or(value.as_ref()).expect("Never fails") {
+                return Err(());
+            }
+            let new = new.get_or_insert_with(|| Arc::new(value.take().expect("Never fails")));
+            Ok(Arc::clone(new))
+        })
+        .is_ok()
+    }
+
+    /// Stores a value into this pointer if the current value is the same as `current`.
+    ///
+    /// The values are compared by pointer identity (i.e., `Arc::ptr_eq`), not by `PartialEq`.
+    ///
+    /// If the replacement succeeds, the old value (i.e., `current`) is returned as `Ok`.
+    /// Otherwise, the actual current value is returned as `Err`
+    /// and the given `new` value is dropped.
+    ///
+    /// The comparison is executed while excluding the other writers,
+    /// so this never fails spuriously (unlike `compare_exchange_weak`):
+    /// `Err` always means that the current value is not `current`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new` is rejected by the validator of this pointer (see `with_validator`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let current = value.load();
+    ///
+    /// let old = value.compare_and_swap(&current, 10).unwrap();
+    /// assert_eq!(*old, 5);
+    /// assert_eq!(*value.load(), 10);
+    ///
+    /// // `current` is no longer the latest value.
+    /// let actual = value.compare_and_swap(&current, 20).unwrap_err();
+    /// assert_eq!(*actual, 10);
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn compare_and_swap(&self, current: &Arc<T>, new: T) -> Result<Arc<T>, Arc<T>> {
+        self.compare_and_swap_arc(current, Arc::new(new))
+    }
+
+    /// Stores a value into this pointer if the version of the current value is `version`.
+    ///
+    /// Unlike `compare_and_swap`, this fails if any value has been stored since `version`,
+    /// even if the same `Arc` has been stored back (e.g., by `store_arc` or `rollback`).
+    ///
+    /// If the replacement succeeds, the old value is returned as `Ok`.
+    /// Otherwise, the actual current value and its version are returned as `Err`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new` is rejected by the validator of this pointer (see `with_validator`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let (current, version) = value.load_versioned();
+    ///
+    /// value.store_arc(Arc::clone(&current));
+    /// let (actual, actual_version) = value.compare_and_swap_versioned(version, 10).unwrap_err();
+    /// assert!(Arc::ptr_eq(&actual, &current));
+    /// assert_eq!(actual_version, 1);
+    ///
+    /// assert_eq!(*value.compare_and_swap_versioned(1, 10).unwrap(), 5);
+    /// assert_eq!(value.load_versioned(), (Arc::new(10), 2));
+    /// ```
+    pub fn compare_and_swap_versioned(
+        &self,
+        version: u64,
+        new: T,
+    ) -> Result<Arc<T>, (Arc<T>, u64)> {
+        self.compare_and_swap_versioned_arc(version, Arc::new(new))
+    }
+}
+impl<T: ?Sized> AtomicImmut<T> {
+    /// Makes a new `AtomicImmut` instance which has the given shared value.
+    ///
+    /// Unlike `new`, this method can be used for unsized values
+    /// such as `str`, slices and trait objects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value: AtomicImmut<str> = AtomicImmut::from_arc(Arc::from("foo"));
+    /// assert_eq!(&*value.load(), "foo");
+    ///
+    /// value.store_arc(Arc::from("bar"));
+    /// assert_eq!(&*value.load(), "bar");
+    /// ```
+    pub fn from_arc(value: Arc<T>) -> Self {
+        Self::from_arc_with_backend(value, DefaultBackend::new())
+    }
+
+    /// Makes a new `AtomicImmut` instance from a raw pointer returned by `load_raw`
+    /// (or `Arc::into_raw`).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `Arc::into_raw` (which `load_raw` calls),
+    /// and its strong reference must not have been released.
+    /// The reference is moved into the new instance,
+    /// so `ptr` must not be used to release it again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let raw = value.load_raw(); // e.g., passed across an FFI boundary
+    ///
+    /// let restored = unsafe { AtomicImmut::from_raw(raw) };
+    /// assert_eq!(*restored.load(), 5);
+    /// ```
+    pub unsafe fn from_raw(ptr: *const T) -> Self {
+        Self::from_arc(Arc::from_raw(ptr))
+    }
+
+    /// Returns a builder which makes an `AtomicImmut` with the per-pointer configurations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::builder().name("answer").keep_history(1).build(42);
+    /// value.store(0);
+    /// assert_eq!(*value.rollback(1).unwrap(), 42);
+    /// assert_eq!(*value.load(), 42);
+    /// ```
+    pub fn builder() -> AtomicImmutBuilder<T> {
+        AtomicImmutBuilder::new()
+    }
+}
+impl<T: ?Sized, B: Backend> AtomicImmut<T, B> {
+    /// Makes a new `AtomicImmut` instance which has the given shared value
+    /// and uses the given synchronization backend.
+    pub fn from_arc_with_backend(value: Arc<T>, backend: B) -> Self {
+        let ptr = CachePadded::new(AtomicPtr::new(to_slot(value, 0)));
+        let checks = Checks::new();
+        checks.forgotten();
+        AtomicImmut {
+            ptr,
+            backend,
+            notifier: Notifier::new(),
+            stats: StatsCounter::new(),
+            tracer: Tracer::new(),
+            extras: LazyExtras::new(),
+            checks,
+            writer: WriterLock::new(),
+        }
+    }
+
+    /// Loads the value from this pointer.
+    ///
+    /// With the default backend (see `backend::DefaultBackend`), this method is wait-free:
+    /// it never waits for writers of this pointer.
+    ///
+    /// Besides the reference count of the value, a load touches the reader counter
+    /// which the backend shares among all threads.
+    /// There is no optimistic path which skips it and validates a version afterwards:
+    /// a writer that does not know about such a reader may release the slot while it is being read.
+    /// If the shared counter becomes a bottleneck under heavy reads,
+    /// use `ShardedBackend` or `EpochBackend` instead (see `benches/lib.rs` for comparisons).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// assert_eq!(*value.load(), 5);
+    /// ```
+    pub fn load(&self) -> Arc<T> {
+        let _read = self.checks.enter();
+        let (_guard, ptr) = self.backend.protect(&self.ptr);
+        Arc::clone(unsafe { &(*ptr).value })
+    }
+
+    /// Loads the value from this pointer only if it can be done without waiting for writers.
+    ///
+    /// Returns `None` if the backend would have to wait (e.g., `StdBackend` while a writer holds the lock),
+    /// so that latency-critical readers can fall back to a previous snapshot.
+    /// With the default backend, whose readers never wait, this always returns `Some`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    /// use atomic_immut::backend::StdBackend;
+    ///
+    /// let value = AtomicImmut::with_backend(5, StdBackend::new());
+    /// let mut snapshot = value.load();
+    /// if let Some(latest) = value.try_load() {
+    ///     snapshot = latest;
+    /// }
+    /// assert_eq!(*snapshot, 5);
+    /// ```
+    pub fn try_load(&self) -> Option<Arc<T>> {
+        let _read = self.checks.enter();
+        let (_guard, ptr) = self.backend.try_protect(&self.ptr)?;
+        Some(Arc::clone(unsafe { &(*ptr).value }))
+    }
+
+    /// Loads the value from this pointer as a raw pointer.
+    ///
+    /// This is the same as `Arc::into_raw(self.load())`:
+    /// the returned pointer owns a strong reference to the value,
+    /// which keeps the value alive however this pointer is updated afterwards.
+    /// To avoid a leak, the reference must be released exactly once
+    /// by `Arc::from_raw`, `Arc::decrement_strong_count` or `AtomicImmut::from_raw`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let raw = value.load_raw();
+    /// value.store(10);
+    ///
+    /// let old = unsafe { Arc::from_raw(raw) };
+    /// assert_eq!(*old, 5);
+    /// ```
+    pub fn load_raw(&self) -> *const T {
+        Arc::into_raw(self.load())
+    }
+
+    /// Returns a raw pointer to the current value of this pointer.
+    ///
+    /// Unlike `load_raw`, the reference count is not touched (like `Arc::as_ptr`),
+    /// so the returned pointer is valid only while the value is kept alive by other means
+    /// (e.g., an `Arc` returned by `load`).
+    /// Once a writer replaces the value, the pointer may be dangling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let snapshot = value.load();
+    /// assert_eq!(value.as_raw(), Arc::as_ptr(&snapshot));
+    ///
+    /// value.store(10);
+    /// assert_ne!(value.as_raw(), Arc::as_ptr(&snapshot));
+    /// ```
+    pub fn as_raw(&self) -> *const T {
+        let _read = self.checks.enter();
+        let (_guard, ptr) = self.backend.protect(&self.ptr);
+        Arc::as_ptr(unsafe { &(*ptr).value })
+    }
+
+    /// Returns a guard which gives a reference to the current value of this pointer.
+    ///
+    /// Unlike `load`, this method does not touch the reference count of the value.
+    /// Instead, writers of this pointer cannot return until the guard is dropped
+    /// (though the values they store become visible immediately),
+    /// so it is intended to be used for short-lived accesses.
+    ///
+    /// Note that calling a writing method (e.g., `store`) of the same pointer
+    /// while holding the guard causes a deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(vec![0, 1, 2]);
+    /// assert_eq!(value.peek().len(), 3);
+    /// ```
+    pub fn peek(&self) -> Peek<'_, T, B> {
+        let read = self.checks.enter();
+        let (guard, ptr) = self.backend.protect(&self.ptr);
+        let value = unsafe { &*(*ptr).value };
+        Peek {
+            value,
+            _guard: guard,
+            _read: read,
+        }
+    }
+
+    /// Returns a clone of the current value of this pointer.
+    ///
+    /// This is the same as `(*self.load()).clone()`:
+    /// the value is cloned after the read is finished, so writers never wait for `T::clone`.
+    /// If the clone is cheap, `T::clone(&self.peek())` avoids touching the reference count instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(vec![1, 2, 3]);
+    /// let mut v = value.load_cloned();
+    /// v.push(4);
+    /// assert_eq!(*value.load(), [1, 2, 3]);
+    /// ```
+    pub fn load_cloned(&self) -> T
+    where
+        T: Clone,
+    {
+        T::clone(&self.load())
+    }
+
+    /// Returns a copy of the current value of this pointer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new((1, 2));
+    /// assert_eq!(value.load_copied(), (1, 2));
+    /// ```
+    pub fn load_copied(&self) -> T
+    where
+        T: Copy,
+    {
+        *self.peek()
+    }
+
+    /// Waits until every reader of this pointer which has started before this call finishes.
+    ///
+    /// Readers here are `load` calls in progress and guards returned by `peek`
+    /// (values obtained by `load` are kept alive by their reference counts instead).
+    /// Since writers already wait for the readers of the replaced value,
+    /// this is only needed to sequence something else after a grace period,
+    /// e.g., tearing down resources associated with the replaced value.
+    ///
+    /// This also runs the callbacks deferred by `call_rcu` before this call.
+    ///
+    /// Note that calling this method while holding a guard returned by `peek` causes a deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let routes = AtomicImmut::new(vec!["/old"]);
+    /// routes.store(vec!["/new"]);
+    /// routes.synchronize();
+    /// // No reader can observe `["/old"]` through `peek` anymore.
+    /// ```
+    pub fn synchronize(&self) {
+        let deferred = self.take_deferred();
+        self.backend.synchronize_all();
+        for f in deferred {
+            f();
+        }
+    }
+
+    /// Defers `f` until every reader of this pointer which has started before this call finishes.
+    ///
+    /// Unlike `synchronize`, this method never waits:
+    /// `f` is called by the next writer of this pointer (after the wait for its readers),
+    /// the next `synchronize` call, or the destructor of this pointer, whichever comes first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(1);
+    /// let done = Arc::new(AtomicBool::new(false));
+    /// {
+    ///     let done = Arc::clone(&done);
+    ///     value.call_rcu(move || done.store(true, Ordering::SeqCst));
+    /// }
+    /// assert!(!done.load(Ordering::SeqCst));
+    ///
+    /// value.store(2);
+    /// assert!(done.load(Ordering::SeqCst));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn call_rcu<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.extras.get_or_init().deferred.push(Box::new(f));
+    }
+
+    /// Makes a new independent `AtomicImmut` instance which shares the current value of this pointer.
+    ///
+    /// The value itself is not copied: both pointers initially refer to the same `Arc`.
+    /// After that, stores into either pointer are not visible to the other one.
+    /// Subscribers, hooks, statistics and the version are not inherited
+    /// (the new pointer starts from version `0`).
+    ///
+    /// This is the same as `clone`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(vec![0, 1, 2]);
+    /// let forked = value.fork();
+    /// assert!(Arc::ptr_eq(&value.load(), &forked.load()));
+    ///
+    /// forked.store(vec![3]);
+    /// assert_eq!(*value.load(), [0, 1, 2]);
+    /// assert_eq!(*forked.load(), [3]);
+    /// ```
+    pub fn fork(&self) -> Self
+    where
+        B: Default,
+    {
+        Self::from_arc_with_backend(self.load(), B::default())
+    }
+
+    /// Returns a lightweight handle which projects the value of this pointer to a part of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new((1, "foo".to_owned()));
+    /// let name = value.map(|v| &v.1);
+    /// assert_eq!(&*name.load(), "foo");
+    ///
+    /// value.store((2, "bar".to_owned()));
+    /// assert_eq!(&*name.load(), "bar");
+    /// ```
+    pub fn map<U: ?Sized, F>(&self, f: F) -> Map<'_, T, F, B>
+    where
+        F: Fn(&T) -> &U,
+    {
+        Map::new(self, f)
+    }
+
+    /// Returns `true` if the current value of this pointer is `other` itself.
+    ///
+    /// This compares the pointers (like `Arc::ptr_eq`) without cloning the current value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let snapshot = value.load();
+    /// assert!(value.ptr_eq(&snapshot));
+    ///
+    /// value.store(5);
+    /// assert!(!value.ptr_eq(&snapshot));
+    /// ```
+    pub fn ptr_eq(&self, other: &Arc<T>) -> bool {
+        let _read = self.checks.enter();
+        let (_guard, ptr) = self.backend.protect(&self.ptr);
+        Arc::ptr_eq(unsafe { &(*ptr).value }, other)
+    }
+
+    /// Stores an already shared value into this pointer.
+    ///
+    /// Unlike `store`, this method installs the given `Arc` as it is,
+    /// so no additional allocation is needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is rejected by the validator of this pointer (see `with_validator`).
+    /// Use `try_store_arc` to handle the rejection instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let shared = Arc::new(1);
+    ///
+    /// value.store_arc(Arc::clone(&shared));
+    /// assert!(Arc::ptr_eq(&value.load(), &shared));
+    /// ```
+    pub fn store_arc(&self, value: Arc<T>) {
+        self.swap_arc(value);
+    }
+
+    /// Stores an already shared value into this pointer if it passes the validator.
+    ///
+    /// This is the same as `try_store` except that it accepts an `Arc`.
+    #[cfg(feature = "std")]
+    pub fn try_store_arc(&self, value: Arc<T>) -> Result<(), ValidationError> {
+        if let Some(extras) = self.extras.get() {
+            extras.validator.validate(&value)?;
+        }
+        self.swap_arc_unchecked(value);
+        Ok(())
+    }
+
+    /// Stores an already shared value into this pointer only if it differs from the current value.
+    ///
+    /// This is the same as `store_if_changed` except that it accepts an `Arc`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is rejected by the validator of this pointer (see `with_validator`).
+    /// Use `try_store_arc` to handle the rejection instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value: AtomicImmut<str> = AtomicImmut::from_arc(Arc::from("foo"));
+    ///
+    /// assert!(!value.store_arc_if_changed(Arc::from("foo")));
+    /// assert!(value.store_arc_if_changed(Arc::from("bar")));
+    /// assert_eq!(&*value.load(), "bar");
+    /// ```
+    pub fn store_arc_if_changed(&self, value: Arc<T>) -> bool
+    where
+        T: PartialEq,
+    {
+        self.update_core(|current| {
+            if *current == *value {
+                Err(())
+            } else {
+                Ok(Arc::clone(&value))
+            }
+        })
+        .is_ok()
+    }
+
+    /// Stores an already shared value into this pointer, returning the old value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is rejected by the validator of this pointer (see `with_validator`).
+    /// Use `try_store_arc` to handle the rejection instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    ///
+    /// let old = value.swap_arc(Arc::new(1));
+    /// assert_eq!(*value.load(), 1);
+    /// assert_eq!(*old, 5);
+    /// ```
+    pub fn swap_arc(&self, value: Arc<T>) -> Arc<T> {
+        self.assert_valid(&value);
+        self.swap_arc_unchecked(value)
+    }
+
+    fn swap_arc_unchecked(&self, value: Arc<T>) -> Arc<T> {
+        let new = self.watch(&value);
+        let old = {
+            let _lock = self.writer.lock();
+            self.replace_locked(value)
+        };
+        unsafe { self.retire(old, new) }
+    }
+
+    /// Stores an already shared value into this pointer if the current value is the same as `current`.
+    ///
+    /// This is the same as `compare_and_swap` except that it accepts an `Arc`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new` is rejected by the validator of this pointer (see `with_validator`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let current = value.load();
+    ///
+    /// let old = value.compare_and_swap_arc(&current, Arc::new(10)).unwrap();
+    /// assert_eq!(*old, 5);
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn compare_and_swap_arc(&self, current: &Arc<T>, new: Arc<T>) -> Result<Arc<T>, Arc<T>> {
+        self.assert_valid(&new);
+        let watched = self.watch(&new);
+        let old = {
+            let _lock = self.writer.lock();
+            // While holding the writer lock, the current slot is never retired.
+            let ptr = self.ptr.load(Ordering::Acquire);
+            let actual = unsafe { &(*ptr).value };
+            if !Arc::ptr_eq(actual, current) {
+                return Err(Arc::clone(actual));
+            }
+            self.replace_locked(new)
+        };
+        Ok(unsafe { self.retire(old, watched) })
+    }
+
+    /// Stores an already shared value into this pointer if the version of the current value is `version`.
+    ///
+    /// This is the same as `compare_and_swap_versioned` except that it accepts an `Arc`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new` is rejected by the validator of this pointer (see `with_validator`).
+    pub fn compare_and_swap_versioned_arc(
+        &self,
+        version: u64,
+        new: Arc<T>,
+    ) -> Result<Arc<T>, (Arc<T>, u64)> {
+        self.assert_valid(&new);
+        let watched = self.watch(&new);
+        let old = {
+            let _lock = self.writer.lock();
+            // While holding the writer lock, the current slot is never retired.
+            let actual = unsafe { &*self.ptr.load(Ordering::Acquire) };
+            if actual.version != version {
+                return Err((Arc::clone(&actual.value), actual.version));
+            }
+            self.replace_locked(new)
+        };
+        Ok(unsafe { self.retire(old, watched) })
+    }
+
+    /// Returns a mutable reference to the value of this pointer
+    /// if there are no other `Arc` pointers to the same value.
+    ///
+    /// This is safe because the mutable reference guarantees that
+    /// no other threads are concurrently accessing this pointer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let mut value = AtomicImmut::new(5);
+    /// *value.get_mut().unwrap() = 10;
+    /// assert_eq!(*value.load(), 10);
+    ///
+    /// let loaded = value.load();
+    /// assert!(value.get_mut().is_none());
+    /// ```
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.extras.get().is_some_and(|e| e.validator.is_enabled()) {
+            return None;
+        }
+        let slot = unsafe { &mut *primitive::with_mut(&mut self.ptr, |p| *p) };
+        Arc::get_mut(&mut slot.value)
+    }
+
+    /// Consumes this pointer, returning the contained value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let inner = value.into_inner();
+    /// assert_eq!(Arc::try_unwrap(inner), Ok(5));
+    /// ```
+    pub fn into_inner(mut self) -> Arc<T> {
+        let ptr = primitive::with_mut(&mut self.ptr, |p| mem::replace(p, ptr::null_mut()));
+        self.checks.recovered();
+        unsafe { from_slot(ptr) }
+    }
+
+    /// Returns the version of the value of this pointer.
+    ///
+    /// The version starts from `0` and is incremented each time a new value is stored
+    /// (e.g., by `store`, `swap` or `update`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// assert_eq!(value.version(), 0);
+    ///
+    /// value.store(10);
+    /// value.update(|v| *v * 2);
+    /// assert_eq!(value.version(), 2);
+    /// ```
+    pub fn version(&self) -> u64 {
+        self.notifier.version()
+    }
+
+    /// Loads the value from this pointer together with its version.
+    ///
+    /// The version is replaced along with the value, so the returned pair is always consistent.
+    /// If another thread stores a value concurrently, `version()` may already return the next version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// value.store(10);
+    ///
+    /// let (v, version) = value.load_versioned();
+    /// assert_eq!(*v, 10);
+    /// assert_eq!(version, 1);
+    /// ```
+    pub fn load_versioned(&self) -> (Arc<T>, u64) {
+        let _read = self.checks.enter();
+        let (_guard, ptr) = self.backend.protect(&self.ptr);
+        let slot = unsafe { &*ptr };
+        (Arc::clone(&slot.value), slot.version)
+    }
+
+    /// Loads the value from this pointer if its version is newer than `version`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let (_, version) = value.load_versioned();
+    /// assert!(value.load_if_newer(version).is_none());
+    ///
+    /// value.store(10);
+    /// let (v, version) = value.load_if_newer(version).unwrap();
+    /// assert_eq!(*v, 10);
+    /// assert_eq!(version, 1);
+    /// ```
+    pub fn load_if_newer(&self, version: u64) -> Option<(Arc<T>, u64)> {
+        if self.version() > version {
+            Some(self.load_versioned())
+        } else {
+            None
+        }
+    }
+
+    /// Splits this pointer into a cloneable read-only handle and a unique write-only handle.
+    ///
+    /// This is useful to enforce the single-writer / multi-reader discipline by the type system.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let (reader, mut writer) = AtomicImmut::new(0).split();
+    /// {
+    ///     let reader = reader.clone();
+    ///     thread::spawn(move || while *reader.load() == 0 {});
+    /// }
+    /// writer.update(|v| v + 1);
+    /// assert_eq!(*reader.load(), 1);
+    /// ```
+    pub fn split(self) -> (ReadHandle<T, B>, WriteHandle<T, B>) {
+        handle::split(self)
+    }
+
+    /// Returns a handle which caches the value of this pointer locally.
+    ///
+    /// This is useful for hot paths, where even `load` is too expensive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(vec![0, 1, 2]);
+    /// let mut cache = value.cache();
+    /// for _ in 0..100 {
+    ///     assert_eq!(cache.get().len(), 3); // Only checks the version
+    /// }
+    /// ```
+    pub fn cache(&self) -> Cache<'_, T, B> {
+        Cache::new(self)
+    }
+
+    /// Returns `true` if no value has been stored since `version` was observed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let (_, version) = value.load_versioned();
+    /// assert!(value.is_current(version));
+    ///
+    /// value.store(10);
+    /// assert!(!value.is_current(version));
+    /// ```
+    pub fn is_current(&self, version: u64) -> bool {
+        self.version() == version
+    }
+
+    /// Returns a `Subscriber` which is used to wait for changes of the value of this pointer.
+    ///
+    /// # Examples
+    ///
+    /// Note that `Subscriber::wait` requires the `std` feature.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let v = Arc::new(AtomicImmut::new(vec![0]));
+    /// {
+    ///     let v = v.clone();
+    ///     thread::spawn(move || {
+    ///                       let mut new = (&*v.load()).clone();
+    ///                       new.push(1);
+    ///                       v.store(new);
+    ///                   });
+    /// }
+    ///
+    /// let mut subscriber = v.subscribe();
+    /// while v.load().len() == 1 {
+    ///     subscriber.wait();
+    /// }
+    /// assert_eq!(&*v.load(), &vec![0, 1]);
+    /// ```
+    pub fn subscribe(&self) -> Subscriber<'_, T, B> {
+        Subscriber::new(self)
+    }
+
+    /// Returns a stream which yields the values stored into this pointer after this call.
+    ///
+    /// `policy` decides how the values stored faster than they are consumed are handled
+    /// (see `StreamPolicy`).
+    /// This method is available when the `async` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate atomic_immut;
+    /// # extern crate futures;
+    /// use atomic_immut::{AtomicImmut, StreamPolicy};
+    /// use futures::executor::block_on;
+    /// use futures::StreamExt;
+    ///
+    /// # fn main() {
+    /// let value = AtomicImmut::new(0);
+    /// let mut stream = value.stream(StreamPolicy::Latest);
+    /// value.store(1);
+    /// value.store(2);
+    /// assert_eq!(block_on(stream.next()).map(|v| *v.unwrap()), Some(2));
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn stream(&self, policy: StreamPolicy) -> Snapshots<'_, T, B>
+    where
+        T: Send + Sync + 'static,
+    {
+        Snapshots::new(self, policy)
+    }
+
+    /// Returns a sink which stores every received value (`T` or `Arc<T>`) into this pointer.
+    ///
+    /// This method is available when the `async` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate atomic_immut;
+    /// # extern crate futures;
+    /// use atomic_immut::AtomicImmut;
+    /// use futures::executor::block_on;
+    /// use futures::SinkExt;
+    ///
+    /// # fn main() {
+    /// let value = AtomicImmut::new(0);
+    /// block_on(value.sink().send(5)).unwrap();
+    /// assert_eq!(*value.load(), 5);
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn sink(&self) -> StoreSink<'_, T, B> {
+        StoreSink::new(self)
+    }
+
+    /// Blocks the current thread until the value of this pointer satisfies `pred`,
+    /// and returns the value.
+    ///
+    /// The current thread is parked while no new value is stored,
+    /// and `pred` is called once for each value observed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let v = Arc::new(AtomicImmut::new(0));
+    /// {
+    ///     let v = v.clone();
+    ///     thread::spawn(move || {
+    ///         for i in 1..=10 {
+    ///             v.store(i);
+    ///         }
+    ///     });
+    /// }
+    /// assert_eq!(*v.wait_until(|x| *x == 10), 10);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_until<F>(&self, mut pred: F) -> Arc<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut subscriber = self.subscribe();
+        let mut value = self.load();
+        while !pred(&value) {
+            value = subscriber.wait();
+        }
+        value
+    }
+
+    /// Same as `wait_until` except that this method gives up waiting after `timeout` has elapsed.
+    ///
+    /// If the timeout expires, `None` will be returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// assert_eq!(value.wait_until_timeout(|v| *v > 5, Duration::from_millis(1)), None);
+    /// assert_eq!(
+    ///     value.wait_until_timeout(|v| *v == 5, Duration::from_millis(1)).map(|v| *v),
+    ///     Some(5)
+    /// );
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_until_timeout<F>(&self, mut pred: F, timeout: Duration) -> Option<Arc<T>>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let mut subscriber = self.subscribe();
+        let mut value = self.load();
+        while !pred(&value) {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            value = subscriber.wait_timeout(remaining)?;
+        }
+        Some(value)
+    }
+
+    /// Registers a hook which is called with the old and new values
+    /// after every successful store (e.g., `store`, `swap` and `update`).
+    ///
+    /// Hooks are called on the writer thread, in the order of the registration.
+    /// Stores which have started before the registration may not call the hook.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// {
+    ///     let log = Arc::clone(&log);
+    ///     value.register_on_store(move |old, new| log.lock().unwrap().push((**old, **new)));
+    /// }
+    ///
+    /// value.store(10);
+    /// value.update(|v| v + 1);
+    /// assert_eq!(*log.lock().unwrap(), [(5, 10), (10, 11)]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn register_on_store<F>(&self, f: F) -> HookId
+    where
+        F: Fn(&Arc<T>, &Arc<T>) + Send + Sync + 'static,
+    {
+        self.extras.get_or_init().hooks.register(Arc::new(f))
+    }
+
+    /// Unregisters the hook identified by `id`.
+    ///
+    /// Returns `false` if there is no such hook.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let count = Arc::new(AtomicUsize::new(0));
+    /// let id = {
+    ///     let count = Arc::clone(&count);
+    ///     value.register_on_store(move |_, _| {
+    ///         count.fetch_add(1, Ordering::SeqCst);
+    ///     })
+    /// };
+    ///
+    /// value.store(10);
+    /// assert!(value.unregister(id));
+    /// assert!(!value.unregister(id));
+    ///
+    /// value.store(20);
+    /// assert_eq!(count.load(Ordering::SeqCst), 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn unregister(&self, id: HookId) -> bool {
+        self.extras.get().is_some_and(|e| e.hooks.unregister(id))
+    }
+
+    /// Names this pointer.
+    ///
+    /// The name is included in diagnostics such as the `tracing` events of the stores.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(0).named("routing_table");
+    /// assert_eq!(value.name(), Some("routing_table"));
+    /// ```
+    pub fn named<N: Into<String>>(mut self, name: N) -> Self {
+        self.extras.get_mut().name = Some(name.into().into_boxed_str());
+        self
+    }
+
+    /// Returns the name of this pointer given by `named`.
+    pub fn name(&self) -> Option<&str> {
+        self.extras.get().and_then(|e| e.name.as_deref())
+    }
+
+    /// Makes this pointer track the time of the last store, which is returned by `last_updated`.
+    ///
+    /// The construction time is regarded as the first store.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(0);
+    /// assert_eq!(value.last_updated(), None);
+    ///
+    /// let value = AtomicImmut::new(0).track_last_updated();
+    /// let created = value.last_updated().unwrap();
+    /// value.store(1);
+    /// assert!(value.last_updated().unwrap() >= created);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn track_last_updated(mut self) -> Self {
+        self.extras.get_mut().updated.enable();
+        self
+    }
+
+    /// Returns the time of the last successful store.
+    ///
+    /// Returns `None` unless the tracking is enabled by `track_last_updated`.
+    #[cfg(feature = "std")]
+    pub fn last_updated(&self) -> Option<SystemTime> {
+        self.extras.get().and_then(|e| e.updated.get())
+    }
+
+    /// Returns the time elapsed since the last successful store.
+    ///
+    /// If the system clock has gone back, zero is returned.
+    /// Returns `None` unless the tracking is enabled by `track_last_updated`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let config = AtomicImmut::new("config").track_last_updated();
+    /// let stale = config.elapsed_since_update().unwrap() > Duration::from_secs(3600);
+    /// assert!(!stale);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn elapsed_since_update(&self) -> Option<Duration> {
+        self.last_updated()
+            .map(|t| t.elapsed().unwrap_or(Duration::from_secs(0)))
+    }
+
+    /// Makes the `tracing` events of the stores include the `Debug` rendering of the new values
+    /// (truncated to 256 bytes).
+    ///
+    /// Every store into this pointer emits a `DEBUG` event (available when the `tracing` feature is enabled)
+    /// with the name and the version of this pointer.
+    /// The values are not rendered by default since it may be costly.
+    #[cfg(feature = "tracing")]
+    pub fn trace_values(mut self) -> Self
+    where
+        T: fmt::Debug,
+    {
+        self.tracer.render_values();
+        self
+    }
+
+    /// Makes this pointer keep the last `n` replaced values, which can be restored by `rollback`.
+    ///
+    /// The history is disabled (i.e., `n` is `0`) by default.
+    /// Note that the values in the history are not released until they are evicted from it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(0).keep_history(2);
+    /// for i in 1..4 {
+    ///     value.store(i);
+    /// }
+    /// let history = value.history().iter().map(|v| **v).collect::<Vec<_>>();
+    /// assert_eq!(history, [2, 1]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn keep_history(mut self, n: usize) -> Self {
+        self.extras.get_mut().history = History::with_capacity(n);
+        self
+    }
+
+    /// Returns the values kept in the history, the most recently replaced one first.
+    ///
+    /// See `keep_history`.
+    #[cfg(feature = "std")]
+    pub fn history(&self) -> Vec<Arc<T>> {
+        self.extras
+            .get()
+            .map_or_else(Vec::new, |e| e.history.snapshot())
+    }
+
+    /// Restores the value which was replaced `steps` stores ago, and returns it.
+    ///
+    /// The restored value and the values newer than it are removed from the history,
+    /// and the current value is discarded (not recorded in the history),
+    /// so that successive rollbacks go further back.
+    /// Hooks and subscribers are notified as with other stores.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RollbackError` if `steps` is `0` or the history has less than `steps` values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let config = AtomicImmut::new("good").keep_history(8);
+    /// config.store("bad");
+    /// config.store("worse");
+    ///
+    /// assert_eq!(*config.rollback(2).unwrap(), "good");
+    /// assert_eq!(*config.load(), "good");
+    ///
+    /// let e = config.rollback(1).unwrap_err();
+    /// assert_eq!(e.available(), 0);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn rollback(&self, steps: usize) -> Result<Arc<T>, RollbackError> {
+        let (old, restored) = {
+            let _lock = self.writer.lock();
+            let restored = self
+                .extras
+                .get()
+                .map_or(Err(0), |e| e.history.rewind(steps))
+                .map_err(|available| RollbackError::new(steps, available))?;
+            (self.install_locked(Arc::clone(&restored)), restored)
+        };
+        let watched = self.watch(&restored);
+        unsafe { self.retire(old, watched) };
+        Ok(restored)
+    }
+
+    /// Returns the contention statistics of the updates of this pointer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// value.update(|v| v + 1);
+    /// value.store(10); // `store` is not an update
+    ///
+    /// let stats = value.stats();
+    /// assert_eq!(stats.updates, 1);
+    /// assert_eq!(stats.retries, 0);
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// Returns a clone of `new` if hooks or tracing events need it after the store.
+    fn watch(&self, new: &Arc<T>) -> Option<Arc<T>> {
+        let watched = self.extras.get().and_then(|e| e.hooks.watch(new));
+        if watched.is_none() && self.tracer.needs_value() {
+            Some(Arc::clone(new))
+        } else {
+            watched
+        }
+    }
+
+    /// Replaces the current value with `new`, recording the old value in the history.
+    ///
+    /// Must be called while holding the writer lock, and the returned slot must be retired.
+    fn replace_locked(&self, new: Arc<T>) -> (*mut Slot<T>, u64) {
+        let (old, version) = self.install_locked(new);
+        if let Some(extras) = self.extras.get() {
+            // While holding the writer lock, the old slot is never retired by other writers.
+            extras.history.record(unsafe { &(*old).value });
+        }
+        (old, version)
+    }
+
+    /// Replaces the current value with `new`, returning the old slot and the new version.
+    ///
+    /// Must be called while holding the writer lock, and the returned slot must be retired.
+    fn install_locked(&self, new: Arc<T>) -> (*mut Slot<T>, u64) {
+        self.writer.assert_locked();
+        // The version is advanced before the slot is replaced,
+        // so a loaded version is never newer than `version()`.
+        let version = self.notifier.advance();
+        let old = self.ptr.swap(to_slot(new, version), Ordering::AcqRel);
+        self.checks.forgotten();
+        (old, version)
+    }
+
+    /// Completes a store which has replaced `old` with `new` (if `new` is watched by `watch`).
+    ///
+    /// # Safety
+    ///
+    /// `old` must be a slot which has just been removed from `self.ptr`
+    /// (along with the new version) by `install_locked`.
+    unsafe fn retire(&self, (old, version): (*mut Slot<T>, u64), new: Option<Arc<T>>) -> Arc<T> {
+        self.announce(version, new.as_deref());
+        let deferred = self.take_deferred();
+        self.backend.synchronize(old);
+        if !deferred.is_empty() {
+            self.backend.synchronize_all();
+        }
+        self.checks.recovered();
+        let old = from_slot(old);
+        for f in deferred {
+            f();
+        }
+        if let (Some(new), Some(extras)) = (new, self.extras.get()) {
+            extras.hooks.call(&old, &new);
+        }
+        old
+    }
+
+    /// Panics if `value` is rejected by the validator (see `with_validator`).
+    fn assert_valid(&self, value: &T) {
+        if let Some(extras) = self.extras.get() {
+            extras.validator.assert_valid(value);
+        }
+    }
+
+    /// Notifies the subscribers and the tracer that the value of `version` has been stored.
+    fn announce(&self, version: u64, new: Option<&T>) {
+        if let Some(extras) = self.extras.get() {
+            #[cfg(feature = "std")]
+            extras.waiters.notify_all();
+            extras.updated.touch();
+        }
+        self.tracer.stored(self.name(), version, new);
+    }
+
+    fn take_deferred(&self) -> Vec<Callback> {
+        self.extras
+            .get()
+            .map_or_else(Vec::new, |e| e.deferred.take())
+    }
+
+    fn update_core<F, E>(&self, f: F) -> Result<(Arc<T>, Arc<T>), E>
+    where
+        F: FnMut(&T) -> Result<Arc<T>, E>,
+    {
+        self.update_core_with_retries(f, &mut 0)
+    }
+
+    fn update_core_with_retries<F, E>(
+        &self,
+        mut f: F,
+        retries: &mut usize,
+    ) -> Result<(Arc<T>, Arc<T>), E>
+    where
+        F: FnMut(&T) -> Result<Arc<T>, E>,
+    {
+        let result = loop {
+            let old = self.load();
+            let new = match f(&old) {
+                Ok(new) => new,
+                Err(e) => break Err(e),
+            };
+            if let Ok(old) = self.compare_and_swap_arc(&old, Arc::clone(&new)) {
+                break Ok((old, new));
+            }
+            *retries += 1;
+        };
+        self.stats.record(result.is_ok(), *retries);
+        result
+    }
+}
+unsafe impl<T: ?Sized + Send + Sync, B: Send + Sync> Send for AtomicImmut<T, B> {}
+unsafe impl<T: ?Sized + Send + Sync, B: Send + Sync> Sync for AtomicImmut<T, B> {}
+impl<T: ?Sized, B> Drop for AtomicImmut<T, B> {
+    fn drop(&mut self) {
+        // No reader is left, so the grace period has already elapsed.
+        if let Some(extras) = self.extras.get() {
+            for f in extras.deferred.take() {
+                f();
+            }
+        }
+        let ptr = primitive::with_mut(&mut self.ptr, |p| mem::replace(p, ptr::null_mut()));
+        if !ptr.is_null() {
+            self.checks.recovered();
+            let _ = unsafe { from_slot(ptr) };
+        }
+        self.checks.assert_balanced(0);
+    }
+}
+impl<T: ?Sized + fmt::Debug, B: Backend> fmt::Debug for AtomicImmut<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicImmut")
+            .field("value", &&*self.load())
+            .finish()
+    }
+}
+impl<T: ?Sized + fmt::Display, B: Backend> fmt::Display for AtomicImmut<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (*self.load()).fmt(f)
+    }
+}
+/// Compares the current values.
+impl<T: ?Sized + PartialEq, B: Backend> PartialEq for AtomicImmut<T, B> {
+    fn eq(&self, other: &Self) -> bool {
+        *self.load() == *other.load()
+    }
+}
+impl<T: ?Sized + Eq, B: Backend> Eq for AtomicImmut<T, B> {}
+/// Hashes the current value.
+impl<T: ?Sized + Hash, B: Backend> Hash for AtomicImmut<T, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (*self.load()).hash(state);
+    }
+}
+/// Makes a new independent `AtomicImmut` instance which shares the current value (see `fork`).
+impl<T: ?Sized, B: Backend + Default> Clone for AtomicImmut<T, B> {
+    fn clone(&self) -> Self {
+        self.fork()
+    }
+}
+impl<T: Default, B: Backend + Default> Default for AtomicImmut<T, B> {
+    fn default() -> Self {
+        Self::with_backend(T::default(), B::default())
+    }
+}
+impl<T: ?Sized, B: Backend + Default> From<Arc<T>> for AtomicImmut<T, B> {
+    fn from(value: Arc<T>) -> Self {
+        Self::from_arc_with_backend(value, B::default())
+    }
+}
+impl<T, B: Backend> Immut<T> for AtomicImmut<T, B> {
+    type Ptr = Arc<T>;
+
+    fn load(&self) -> Arc<T> {
+        AtomicImmut::load(self)
+    }
+    fn store(&self, value: T) {
+        AtomicImmut::store(self, value);
+    }
+    fn swap(&self, value: T) -> Arc<T> {
+        AtomicImmut::swap(self, value)
+    }
+    fn update<F>(&self, f: F)
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+    {
+        AtomicImmut::update(self, f);
+    }
+}
+
+/// The common interface of `AtomicImmut` and `LocalImmut`.
+///
+/// This trait is useful for writing code which is generic over thread-safety.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::{AtomicImmut, Immut, LocalImmut};
+///
+/// fn push<C: Immut<Vec<u8>>>(cell: &C, x: u8) {
+///     cell.update(|v| {
+///         let mut v = v.clone();
+///         v.push(x);
+///         v
+///     });
+/// }
+///
+/// let atomic = AtomicImmut::new(vec![]);
+/// push(&atomic, 1);
+/// assert_eq!(&*atomic.load(), &vec![1]);
+///
+/// let local = LocalImmut::new(vec![]);
+/// push(&local, 1);
+/// assert_eq!(&*local.load(), &vec![1]);
+/// ```
+pub trait Immut<T> {
+    /// The type of the shared pointers to the values (i.e., `Arc<T>` or `Rc<T>`).
+    type Ptr: Deref<Target = T> + Clone;
+
+    /// Loads the value from this cell.
+    fn load(&self) -> Self::Ptr;
+
+    /// Stores a value into this cell.
+    fn store(&self, value: T);
+
+    /// Stores a value into this cell, returning the old value.
+    fn swap(&self, value: T) -> Self::Ptr;
+
+    /// Updates the value of this cell by calling `f` on the value to get a new value.
+    fn update<F>(&self, f: F)
+    where
+        F: for<'a> FnMut(&'a T) -> T;
+}
+
+/// A guard which gives a reference to the value of an `AtomicImmut`.
+///
+/// This is created by the `AtomicImmut::peek` method.
+pub struct Peek<'a, T: ?Sized + 'a, B: Backend + 'a = DefaultBackend> {
+    value: &'a T,
+    _guard: B::Guard<'a>,
+    _read: ReadToken<'a>,
+}
+impl<'a, T: ?Sized, B: Backend> Deref for Peek<'a, T, B> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+impl<'a, T: ?Sized + fmt::Debug, B: Backend> fmt::Debug for Peek<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Peek").field(&self.value).finish()
+    }
+}
+
+fn to_arc_ptr<T>(value: T) -> *mut T {
+    Arc::into_raw(Arc::new(value)).cast_mut()
+}
+
+// Because `AtomicPtr` cannot hold a pointer to an unsized value,
+// `AtomicImmut` holds a pointer to a boxed `Arc` instead.
+// The box also carries the version of the value, so that both are replaced at once.
+struct Slot<T: ?Sized> {
+    value: Arc<T>,
+    version: u64,
+}
+
+fn to_slot<T: ?Sized>(value: Arc<T>, version: u64) -> *mut Slot<T> {
+    Box::into_raw(Box::new(Slot { value, version }))
+}
+
+unsafe fn from_slot<T: ?Sized>(ptr: *mut Slot<T>) -> Arc<T> {
+    Box::from_raw(ptr).value
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+    use std::sync::{Arc, Barrier, Mutex};
     use std::thread;
     use std::time::Duration;
 
@@ -279,6 +2554,25 @@ mod test {
         assert_eq!(Arc::strong_count(&v.load()), 2);
     }
 
+    #[test]
+    fn pending_writer_does_not_block_readers() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let peek = v.peek();
+
+        let handle = {
+            let v = v.clone();
+            thread::spawn(move || v.store(1))
+        };
+        while *v.load() != 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*peek, 0);
+
+        drop(peek);
+        handle.join().unwrap();
+        assert_eq!(Arc::strong_count(&v.load()), 2);
+    }
+
     #[test]
     fn update_works() {
         let v = AtomicImmut::new(vec![0, 1, 2]);
@@ -289,4 +2583,658 @@ mod test {
         assert_eq!(&*v.load(), &vec![0]);
         assert_eq!(Arc::strong_count(&v.load()), 2);
     }
+
+    #[test]
+    fn peek_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+        {
+            let peek = v.peek();
+            assert_eq!(&*peek, &vec![0, 1, 2]);
+            assert_eq!(Arc::strong_count(&v.load()), 2);
+        }
+
+        v.store(vec![0]);
+        assert_eq!(&*v.peek(), &vec![0]);
+    }
+
+    #[test]
+    fn try_update_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+        let old = v.load();
+
+        assert!(!v.try_update(|_| None));
+        assert!(Arc::ptr_eq(&v.load(), &old));
+
+        assert!(v.try_update(|_| Some(vec![0])));
+        assert_eq!(&*v.load(), &vec![0]);
+        assert_eq!(Arc::strong_count(&v.load()), 2);
+        assert_eq!(Arc::strong_count(&old), 1);
+    }
+
+    #[test]
+    fn try_update_result_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+        let old = v.load();
+
+        assert_eq!(v.try_update_result(|_| Err("error")), Err("error"));
+        assert!(Arc::ptr_eq(&v.load(), &old));
+
+        assert_eq!(v.try_update_result(|_| Ok::<_, ()>(vec![0])), Ok(()));
+        assert_eq!(&*v.load(), &vec![0]);
+        assert_eq!(Arc::strong_count(&old), 1);
+    }
+
+    #[test]
+    fn fetch_update_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+
+        let old = v.fetch_update(|_| vec![0]);
+        assert_eq!(&*old, &vec![0, 1, 2]);
+        assert_eq!(Arc::strong_count(&old), 1);
+
+        let new = v.update_and_fetch(|v| v.iter().map(|x| x + 1).collect());
+        assert_eq!(&*new, &vec![1]);
+        assert!(Arc::ptr_eq(&v.load(), &new));
+        assert_eq!(Arc::strong_count(&new), 2);
+    }
+
+    #[test]
+    fn store_if_changed_compares_once() {
+        static EQ_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug)]
+        struct Counted(u32);
+        impl PartialEq for Counted {
+            fn eq(&self, other: &Self) -> bool {
+                EQ_CALLS.fetch_add(1, Ordering::SeqCst);
+                self.0 == other.0
+            }
+        }
+
+        let v = AtomicImmut::new(Counted(0));
+        assert!(!v.store_if_changed(Counted(0)));
+        assert_eq!(EQ_CALLS.load(Ordering::SeqCst), 1);
+        assert!(v.store_if_changed(Counted(1)));
+        assert_eq!(EQ_CALLS.load(Ordering::SeqCst), 2);
+        assert_eq!(v.version(), 1);
+    }
+
+    #[test]
+    fn compare_and_swap_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+        let current = v.load();
+
+        let old = v.compare_and_swap(&current, vec![0]).unwrap();
+        assert!(Arc::ptr_eq(&old, &current));
+        assert_eq!(&*v.load(), &vec![0]);
+        assert_eq!(Arc::strong_count(&v.load()), 2);
+
+        let actual = v.compare_and_swap(&current, vec![1]).unwrap_err();
+        assert_eq!(&*actual, &vec![0]);
+        assert_eq!(&*v.load(), &vec![0]);
+        assert_eq!(Arc::strong_count(&actual), 2);
+
+        drop(old);
+        assert_eq!(Arc::strong_count(&current), 1);
+    }
+
+    #[test]
+    fn exclusive_access_works() {
+        let mut v = AtomicImmut::new(vec![0, 1, 2]);
+        v.get_mut().unwrap().push(3);
+        assert_eq!(&*v.load(), &vec![0, 1, 2, 3]);
+
+        let loaded = v.load();
+        assert!(v.get_mut().is_none());
+        drop(loaded);
+
+        let inner = v.into_inner();
+        assert_eq!(Arc::strong_count(&inner), 1);
+        assert_eq!(&*inner, &vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn version_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+        assert_eq!(v.version(), 0);
+
+        v.store(vec![0]);
+        v.swap_arc(Arc::new(vec![1]));
+        assert_eq!(v.version(), 2);
+
+        assert!(!v.try_update(|_| None));
+        assert!(v.compare_and_swap(&Arc::new(vec![]), vec![2]).is_err());
+        assert_eq!(v.version(), 2);
+
+        assert!(v.load_if_newer(2).is_none());
+        let (value, version) = v.load_if_newer(1).unwrap();
+        assert_eq!(&*value, &vec![1]);
+        assert_eq!(version, 2);
+    }
+
+    #[test]
+    fn versioned_compare_and_swap_works() {
+        let v = AtomicImmut::builder().keep_history(1).build(0);
+        let (zero, version) = v.load_versioned();
+        v.store(1);
+        v.rollback(1).unwrap();
+        assert!(Arc::ptr_eq(&v.load(), &zero));
+
+        // The same value has been stored back, but the version tells the difference.
+        assert!(v.compare_and_swap_versioned(version, 3).is_err());
+        let (_, version) = v.load_versioned();
+        assert_eq!(version, 2);
+        assert_eq!(*v.compare_and_swap_versioned(version, 3).unwrap(), 0);
+        assert_eq!(v.load_versioned(), (Arc::new(3), 3));
+
+        let mut v = v;
+        v.update_in_place(|x| *x += 1);
+        assert_eq!(v.load_versioned(), (Arc::new(4), 4));
+        assert_eq!(v.version(), 4);
+    }
+
+    #[test]
+    fn unsized_works() {
+        let v: AtomicImmut<[u8]> = AtomicImmut::from_arc(Arc::from(vec![0, 1, 2]));
+        assert_eq!(&*v.load(), &[0, 1, 2]);
+        assert_eq!(&*v.peek(), &[0, 1, 2]);
+
+        let old = v.swap_arc(Arc::from(vec![3]));
+        assert_eq!(&*old, &[0, 1, 2]);
+        assert_eq!(Arc::strong_count(&old), 1);
+        assert_eq!(&*v.load(), &[3]);
+
+        let v: AtomicImmut<dyn Fn(i32) -> i32> = AtomicImmut::from_arc(Arc::new(|x| x + 1));
+        assert_eq!((v.load())(1), 2);
+        let current = v.load();
+        assert!(v
+            .compare_and_swap_arc(&current, Arc::new(|x| x * 3))
+            .is_ok());
+        assert_eq!((v.load())(2), 6);
+    }
+
+    #[test]
+    fn swap_arc_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+        let shared = Arc::new(vec![0]);
+
+        let old = v.swap_arc(Arc::clone(&shared));
+        assert!(Arc::ptr_eq(&v.load(), &shared));
+        assert_eq!(Arc::strong_count(&shared), 2);
+        assert_eq!(&*old, &vec![0, 1, 2]);
+        assert_eq!(Arc::strong_count(&old), 1);
+
+        drop(v);
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn deferred_drop_works() {
+        struct Foo(Arc<Mutex<Option<String>>>);
+        impl Drop for Foo {
+            fn drop(&mut self) {
+                let name = thread::current().name().map(|s| s.to_owned());
+                *self.0.lock().unwrap() = name;
+            }
+        }
+
+        let dropped_by = Arc::new(Mutex::new(None));
+        let v = AtomicImmut::new(Foo(Arc::clone(&dropped_by)));
+        v.store_deferred(Foo(Arc::new(Mutex::new(None))));
+        ::reclaim::flush();
+        assert_eq!(
+            dropped_by.lock().unwrap().as_ref().map(|s| s.as_str()),
+            Some("atomic_immut-reclaimer")
+        );
+
+        let dropped_by = Arc::new(Mutex::new(None));
+        v.store(Foo(Arc::clone(&dropped_by)));
+        v.update_deferred(|_| Foo(Arc::new(Mutex::new(None))));
+        ::reclaim::flush();
+        assert_eq!(
+            dropped_by.lock().unwrap().as_ref().map(|s| s.as_str()),
+            Some("atomic_immut-reclaimer")
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn on_store_hooks_work() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let id = Arc::new(Mutex::new(None));
+        {
+            let calls = Arc::clone(&calls);
+            let id_for_hook = Arc::clone(&id);
+            let v_for_hook = Arc::downgrade(&v);
+            *id.lock().unwrap() = Some(v.register_on_store(move |old, new| {
+                calls.lock().unwrap().push((**old, **new));
+                if **new == 3 {
+                    // Hooks can unregister themselves.
+                    let id = id_for_hook.lock().unwrap().take().unwrap();
+                    assert!(v_for_hook.upgrade().unwrap().unregister(id));
+                }
+            }));
+        }
+
+        v.store(1);
+        v.swap(2);
+        let current = v.load();
+        assert!(v.compare_and_swap(&current, 3).is_ok());
+        v.update(|x| x + 1);
+        assert_eq!(*calls.lock().unwrap(), [(0, 1), (1, 2), (2, 3)]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wait_until_works() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let handle = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || *v.wait_until(|x| *x >= 3))
+        };
+        for i in 1..5 {
+            thread::sleep(Duration::from_millis(1));
+            v.store(i);
+        }
+        assert!(handle.join().unwrap() >= 3);
+
+        assert_eq!(
+            v.wait_until_timeout(|x| *x == 0, Duration::from_millis(10)),
+            None
+        );
+    }
+
+    #[test]
+    fn update_in_place_works() {
+        let mut v = AtomicImmut::new(vec![0]);
+        let ptr = v.load().as_ptr();
+        v.update_in_place(|x| x.push(1));
+        assert_eq!(v.load().as_ptr(), ptr);
+        assert_eq!(v.version(), 1);
+
+        let old = v.load();
+        v.update_in_place(|x| x.push(2));
+        assert_ne!(v.load().as_ptr(), ptr);
+        assert_eq!(*old, [0, 1]);
+        assert_eq!(*v.load(), [0, 1, 2]);
+    }
+
+    #[cfg(all(
+        target_pointer_width = "64",
+        not(any(feature = "padding", feature = "stats", feature = "tracing"))
+    ))]
+    #[test]
+    fn optional_state_is_allocated_lazily() {
+        // The pointer, the version, the pointer to the extras, and the writer lock.
+        let words = mem::size_of::<AtomicImmut<u8>>() - mem::size_of::<DefaultBackend>();
+        assert_eq!(words / mem::size_of::<usize>(), 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn update_in_place_records_history() {
+        let mut v = AtomicImmut::new(0).keep_history(4);
+        v.store(1);
+        v.update_in_place(|x| *x = 2);
+        assert_eq!(*v.load(), 2);
+        assert_eq!(v.history().iter().map(|x| **x).collect::<Vec<_>>(), [1, 0]);
+        #[cfg(feature = "stats")]
+        assert_eq!(v.stats().updates, 1);
+
+        assert_eq!(*v.rollback(1).unwrap(), 1);
+        assert_eq!(*v.load(), 1);
+        assert_eq!(*v.rollback(1).unwrap(), 0);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_works() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let thread_count = 4;
+        let barrier = Arc::new(Barrier::new(thread_count));
+        let handles = (0..thread_count)
+            .map(|_| {
+                let v = Arc::clone(&v);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    (0..100).map(|_| v.update_stats(|x| x + 1)).sum::<usize>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let retries = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>();
+        assert_eq!(*v.load(), thread_count * 100);
+        assert_eq!(
+            v.stats(),
+            Stats {
+                updates: thread_count * 100,
+                retries,
+            }
+        );
+    }
+
+    #[test]
+    fn value_aware_traits_work() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let v = AtomicImmut::new(5);
+        assert_eq!(format!("{:?}", v), "AtomicImmut { value: 5 }");
+        assert_eq!(v.to_string(), "5");
+
+        let s: AtomicImmut<str> = AtomicImmut::from_arc(Arc::from("foo"));
+        assert_eq!(format!("{:?}", s), r#"AtomicImmut { value: "foo" }"#);
+
+        assert_eq!(v, AtomicImmut::new(5));
+        assert_ne!(v, AtomicImmut::new(6));
+
+        let hash = |x: &dyn Fn(&mut DefaultHasher)| {
+            let mut hasher = DefaultHasher::new();
+            x(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&|h| v.hash(h)), hash(&|h| 5.hash(h)));
+    }
+
+    #[test]
+    fn clone_works() {
+        let v = AtomicImmut::new(vec![0]);
+        v.store(vec![1]);
+        let cloned = v.clone();
+        assert!(Arc::ptr_eq(&v.load(), &cloned.load()));
+        assert_eq!(cloned.version(), 0);
+
+        v.store(vec![2]);
+        assert_eq!(*cloned.load(), [1]);
+        assert_eq!(Arc::strong_count(&cloned.load()), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn derive_from_works() {
+        let source = Arc::new(AtomicImmut::new(0));
+        let derived = AtomicImmut::derive_from(&source, |v: &usize| v * 2);
+        let thread_count = 4;
+        let handles = (0..thread_count)
+            .map(|_| {
+                let source = Arc::clone(&source);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        source.update(|v| v + 1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*derived.load(), thread_count * 100 * 2);
+
+        // The derived value is not kept alive by the source
+        let weak = Arc::downgrade(&derived);
+        drop(derived);
+        assert!(weak.upgrade().is_none());
+        source.store(0);
+    }
+
+    #[test]
+    fn try_update_bounded_works() {
+        let v = AtomicImmut::new(0);
+        let mut calls = 0;
+        let result = v.try_update_bounded(2, |x| {
+            calls += 1;
+            v.store(x + 10); // Always conflicts
+            x + 1
+        });
+        assert_eq!(result, Err(UpdateContended::new(2)));
+        assert_eq!(calls, 3);
+        assert_eq!(*v.load(), 30);
+
+        // No spurious failures without contention.
+        for i in 0..1000 {
+            assert_eq!(v.try_update_bounded(0, |x| x + 1), Ok(()));
+            assert_eq!(*v.load(), 31 + i);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn history_works() {
+        let v = AtomicImmut::new(0).keep_history(3);
+        let rollbacks = Arc::new(AtomicUsize::new(0));
+        {
+            let rollbacks = Arc::clone(&rollbacks);
+            v.register_on_store(move |old, new| {
+                if old > new {
+                    rollbacks.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        }
+        v.store(1);
+        v.update(|x| x + 1);
+        assert!(v.compare_and_swap(&v.load(), 3).is_ok());
+        v.swap(4);
+        let history = v.history().iter().map(|x| **x).collect::<Vec<_>>();
+        assert_eq!(history, [3, 2, 1]);
+
+        assert_eq!(v.rollback(0), Err(RollbackError::new(0, 3)));
+        assert_eq!(v.rollback(4), Err(RollbackError::new(4, 3)));
+        assert_eq!(*v.rollback(1).unwrap(), 3);
+        assert_eq!(*v.rollback(2).unwrap(), 1);
+        assert_eq!(*v.load(), 1);
+        assert!(v.history().is_empty());
+        assert_eq!(rollbacks.load(Ordering::SeqCst), 2);
+        assert_eq!(Arc::strong_count(&v.load()), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn validator_works() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let validator = |v: &Vec<u8>| if v.len() < 3 { Ok(()) } else { Err("too long") };
+        assert!(AtomicImmut::with_validator(vec![0; 3], validator).is_err());
+
+        let mut v = AtomicImmut::with_validator(vec![0], validator).unwrap();
+        assert!(v.try_store(vec![0, 1]).is_ok());
+        assert!(v.try_store_arc(Arc::new(vec![0, 1, 2])).is_err());
+        assert!(v.get_mut().is_none());
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| v.update(|x| x.repeat(2))));
+        assert!(result.is_err());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| v.update_exclusive(|x| x.repeat(2))));
+        assert!(result.is_err());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| v.update_in_place(|x| x.push(2))));
+        assert!(result.is_err());
+        assert_eq!(*v.load(), [0, 1]);
+        assert_eq!(v.version(), 1);
+
+        v.update_in_place(|x| x[0] = 1);
+        v.update(|x| x[..1].to_vec());
+        assert_eq!(*v.load(), [1]);
+    }
+
+    fn synchronize_waits_for_readers<B>(v: AtomicImmut<usize, B>)
+    where
+        B: Backend + Send + Sync + 'static,
+    {
+        let v = Arc::new(v);
+        let released = Arc::new(AtomicBool::new(false));
+        let barrier = Arc::new(Barrier::new(2));
+        let reader = {
+            let v = Arc::clone(&v);
+            let released = Arc::clone(&released);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                let _peek = v.peek();
+                barrier.wait();
+                thread::sleep(Duration::from_millis(50));
+                released.store(true, Ordering::SeqCst);
+            })
+        };
+        barrier.wait();
+        v.synchronize();
+        assert!(released.load(Ordering::SeqCst));
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn synchronize_works() {
+        synchronize_waits_for_readers(AtomicImmut::new(0));
+        synchronize_waits_for_readers(AtomicImmut::with_backend(0, backend::StdBackend::new()));
+        #[cfg(feature = "hazard")]
+        synchronize_waits_for_readers(AtomicImmut::with_backend(0, backend::HazardBackend::new()));
+        #[cfg(feature = "sharded")]
+        synchronize_waits_for_readers(AtomicImmut::with_backend(0, backend::ShardedBackend::new()));
+    }
+
+    #[test]
+    fn call_rcu_works() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let defer = |v: &AtomicImmut<usize>| {
+            let calls = Arc::clone(&calls);
+            v.call_rcu(move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+            });
+        };
+
+        let v = AtomicImmut::new(0);
+        defer(&v);
+        defer(&v);
+        v.synchronize();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        defer(&v);
+        v.update(|x| x + 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        defer(&v);
+        drop(v);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn updates_are_panic_safe() {
+        let v = AtomicImmut::new(Arc::new(0));
+        let inner = Arc::clone(&*v.load());
+        let panicked = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            v.update(|_| panic!());
+        }));
+        assert!(panicked.is_err());
+        let panicked = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            v.update_exclusive(|_| panic!());
+        }));
+        assert!(panicked.is_err());
+        assert!(v.update_catch(|_| panic!()).is_err());
+
+        // Nothing has leaked, and the writer lock has been released.
+        assert_eq!(Arc::strong_count(&inner), 2);
+        v.update(|x| Arc::new(**x + 1));
+        assert_eq!(**v.load(), 1);
+        assert_eq!(Arc::strong_count(&inner), 1);
+    }
+
+    #[test]
+    fn try_load_works() {
+        let v = AtomicImmut::new(5);
+        let _peek = v.peek();
+        assert_eq!(v.try_load().map(|x| *x), Some(5));
+
+        let v = AtomicImmut::with_backend(5, backend::StdBackend::new());
+        assert_eq!(v.try_load().map(|x| *x), Some(5));
+    }
+
+    #[test]
+    #[cfg(feature = "debug-checks")]
+    #[should_panic(expected = "read guards are leaked")]
+    fn leaked_peek_is_detected() {
+        let v = AtomicImmut::new(5);
+        mem::forget(v.peek());
+    }
+
+    #[test]
+    fn override_scoped_restores_on_panic() {
+        let v = AtomicImmut::new(vec![0]);
+        let original = v.load();
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            let _outer = v.override_scoped(vec![1]);
+            let _inner = v.override_scoped(vec![2]);
+            assert_eq!(*v.load(), [2]);
+            panic!();
+        }));
+        assert!(result.is_err());
+        assert!(v.ptr_eq(&original));
+    }
+
+    #[test]
+    fn take_works() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let handles = (0..4)
+            .map(|_| {
+                let v = Arc::clone(&v);
+                thread::spawn(move || {
+                    let mut taken = 0;
+                    for _ in 0..100 {
+                        v.update(|x| x + 1);
+                        taken += *v.take();
+                    }
+                    taken
+                })
+            })
+            .collect::<Vec<_>>();
+        let taken = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>();
+        assert_eq!(taken + *v.take(), 400);
+        assert_eq!(*v.load(), 0);
+    }
+
+    #[test]
+    fn update_exclusive_works() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let thread_count = 4;
+        let handles = (0..thread_count)
+            .map(|i| {
+                let v = Arc::clone(&v);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        if i % 2 == 0 {
+                            let mut called = false;
+                            v.update_exclusive(|x| {
+                                assert!(!called);
+                                called = true;
+                                x + 1
+                            });
+                        } else {
+                            v.update(|x| x + 1);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*v.load(), thread_count * 100);
+    }
+
+    #[test]
+    fn update_accepts_fn_mut() {
+        let v = AtomicImmut::new(1);
+        let mut attempts = 0;
+        v.update(|x| {
+            attempts += 1;
+            if attempts == 1 {
+                v.store(10); // Conflicts
+            }
+            x + 1
+        });
+        assert_eq!(attempts, 2);
+        assert_eq!(*v.load(), 11);
+    }
 }
@@ -0,0 +1,173 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use Immut;
+
+/// A non thread-safe pointer for immutable value.
+///
+/// This has the same interface as `AtomicImmut` but is built on `Rc`,
+/// so it has no synchronization overhead.
+/// It is useful for single-threaded programs.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::LocalImmut;
+///
+/// let value = LocalImmut::new(vec![0]);
+/// let old = value.load();
+///
+/// value.update(|v| {
+///     let mut v = v.clone();
+///     v.push(1);
+///     v
+/// });
+/// assert_eq!(&*value.load(), &vec![0, 1]);
+/// assert_eq!(&*old, &vec![0]);
+/// ```
+#[derive(Debug)]
+pub struct LocalImmut<T: ?Sized> {
+    ptr: RefCell<Rc<T>>,
+}
+impl<T> LocalImmut<T> {
+    /// Makes a new `LocalImmut` instance.
+    pub fn new(value: T) -> Self {
+        Self::from_rc(Rc::new(value))
+    }
+
+    /// Stores a value into this pointer.
+    pub fn store(&self, value: T) {
+        self.swap(value);
+    }
+
+    /// Stores a value into this pointer, returning the old value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::LocalImmut;
+    ///
+    /// let value = LocalImmut::new(5);
+    /// assert_eq!(*value.swap(1), 5);
+    /// assert_eq!(*value.load(), 1);
+    /// ```
+    pub fn swap(&self, value: T) -> Rc<T> {
+        self.swap_rc(Rc::new(value))
+    }
+
+    /// Updates the value of this pointer by calling `f` on the value to get a new value.
+    ///
+    /// Unlike `AtomicImmut::update`, the function `f` is called exactly once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::LocalImmut;
+    ///
+    /// let value = LocalImmut::new(5);
+    /// value.update(|v| *v * 2);
+    /// assert_eq!(*value.load(), 10);
+    /// ```
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let old = self.load();
+        let new = f(&old);
+        self.store(new);
+    }
+}
+impl<T: ?Sized> LocalImmut<T> {
+    /// Makes a new `LocalImmut` instance which has the given shared value.
+    pub fn from_rc(value: Rc<T>) -> Self {
+        LocalImmut {
+            ptr: RefCell::new(value),
+        }
+    }
+
+    /// Loads the value from this pointer.
+    pub fn load(&self) -> Rc<T> {
+        Rc::clone(&self.ptr.borrow())
+    }
+
+    /// Stores an already shared value into this pointer.
+    pub fn store_rc(&self, value: Rc<T>) {
+        self.swap_rc(value);
+    }
+
+    /// Stores an already shared value into this pointer, returning the old value.
+    pub fn swap_rc(&self, value: Rc<T>) -> Rc<T> {
+        self.ptr.replace(value)
+    }
+
+    /// Consumes this pointer, returning the contained value.
+    pub fn into_inner(self) -> Rc<T> {
+        self.ptr.into_inner()
+    }
+}
+impl<T: Default> Default for LocalImmut<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<T: ?Sized> From<Rc<T>> for LocalImmut<T> {
+    fn from(value: Rc<T>) -> Self {
+        Self::from_rc(value)
+    }
+}
+impl<T> Immut<T> for LocalImmut<T> {
+    type Ptr = Rc<T>;
+
+    fn load(&self) -> Rc<T> {
+        LocalImmut::load(self)
+    }
+    fn store(&self, value: T) {
+        LocalImmut::store(self, value);
+    }
+    fn swap(&self, value: T) -> Rc<T> {
+        LocalImmut::swap(self, value)
+    }
+    fn update<F>(&self, f: F)
+    where
+        F: for<'a> FnMut(&'a T) -> T,
+    {
+        LocalImmut::update(self, f);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+    use {AtomicImmut, Immut};
+
+    fn increment<C: Immut<usize>>(cell: &C) -> usize {
+        cell.update(|v| v + 1);
+        *cell.load()
+    }
+
+    #[test]
+    fn it_works() {
+        let v = LocalImmut::new(vec![0, 1, 2]);
+        assert_eq!(Rc::strong_count(&v.load()), 2);
+
+        let old = v.swap(vec![0]);
+        assert_eq!(&*old, &vec![0, 1, 2]);
+        assert_eq!(Rc::strong_count(&old), 1);
+        assert_eq!(&*v.load(), &vec![0]);
+
+        // A value can be stored from the inside of `update`.
+        v.update(|x| {
+            v.store(vec![]);
+            x.iter().map(|x| x + 1).collect()
+        });
+        assert_eq!(&*v.load(), &vec![1]);
+    }
+
+    #[test]
+    fn generic_over_cells_works() {
+        assert_eq!(increment(&LocalImmut::new(0)), 1);
+        assert_eq!(increment(&AtomicImmut::new(0)), 1);
+    }
+}
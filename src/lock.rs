@@ -0,0 +1,55 @@
+use core::sync::atomic::Ordering;
+
+use backend::Backoff;
+use padded::CachePadded;
+use primitive::AtomicBool;
+
+/// A spin lock which serializes the writers of an `AtomicImmut` (or the grace periods of a backend).
+///
+/// Readers never acquire this lock.
+#[derive(Debug, Default)]
+pub(crate) struct WriterLock {
+    locked: CachePadded<AtomicBool>,
+}
+impl WriterLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lock(&self) -> WriterGuard<'_> {
+        let mut snooze = Backoff::new().start();
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                snooze.snooze();
+            }
+        }
+        WriterGuard(self)
+    }
+
+    /// Panics if this lock is not held (checked only if the `debug-checks` feature is enabled).
+    pub fn assert_locked(&self) {
+        if cfg!(feature = "debug-checks") {
+            assert!(
+                self.locked.load(Ordering::Relaxed),
+                "debug-checks: the writer lock is not held"
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct WriterGuard<'a>(&'a WriterLock);
+impl<'a> Drop for WriterGuard<'a> {
+    fn drop(&mut self) {
+        if cfg!(feature = "debug-checks") {
+            let locked = self.0.locked.swap(false, Ordering::Release);
+            assert!(locked, "debug-checks: the writer lock is released twice");
+        } else {
+            self.0.locked.store(false, Ordering::Release);
+        }
+    }
+}
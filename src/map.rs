@@ -0,0 +1,126 @@
+use core::fmt;
+use core::ops::Deref;
+use primitive::Arc;
+
+use backend::{Backend, DefaultBackend};
+use AtomicImmut;
+
+/// A lightweight handle which projects the value of an `AtomicImmut` to a part of it.
+///
+/// This is created by the `AtomicImmut::map` method.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+///
+/// struct Config {
+///     name: String,
+///     timeouts: Vec<u32>,
+/// }
+///
+/// let config = AtomicImmut::new(Config { name: "foo".to_owned(), timeouts: vec![10] });
+/// let timeouts = config.map(|c| &c.timeouts);
+/// assert_eq!(*timeouts.load(), [10]);
+///
+/// config.store(Config { name: "bar".to_owned(), timeouts: vec![20, 30] });
+/// assert_eq!(*timeouts.load(), [20, 30]);
+/// ```
+pub struct Map<'a, T: ?Sized + 'a, F, B: 'a = DefaultBackend> {
+    cell: &'a AtomicImmut<T, B>,
+    f: F,
+}
+impl<'a, T: ?Sized, F, B: Backend> Map<'a, T, F, B> {
+    pub(crate) fn new(cell: &'a AtomicImmut<T, B>, f: F) -> Self {
+        Map { cell, f }
+    }
+
+    /// Loads the current value of the underlying `AtomicImmut`,
+    /// and returns a guard which dereferences to the projected part of it.
+    ///
+    /// Like `AtomicImmut::load`, the guard keeps the whole value alive
+    /// without blocking writers of the underlying `AtomicImmut`.
+    pub fn load<U: ?Sized>(&self) -> MapGuard<'_, T, F>
+    where
+        F: Fn(&T) -> &U,
+    {
+        MapGuard {
+            value: self.cell.load(),
+            f: &self.f,
+        }
+    }
+
+    /// Returns the underlying `AtomicImmut`.
+    pub fn cell(&self) -> &'a AtomicImmut<T, B> {
+        self.cell
+    }
+}
+impl<'a, T: ?Sized, F: Clone, B> Clone for Map<'a, T, F, B> {
+    fn clone(&self) -> Self {
+        Map {
+            cell: self.cell,
+            f: self.f.clone(),
+        }
+    }
+}
+impl<'a, T: ?Sized, F, B> fmt::Debug for Map<'a, T, F, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Map").finish()
+    }
+}
+
+/// A guard which dereferences to the projected part of a loaded value.
+///
+/// This is created by the `Map::load` method.
+pub struct MapGuard<'a, T: ?Sized + 'a, F: 'a> {
+    value: Arc<T>,
+    f: &'a F,
+}
+impl<'a, T: ?Sized, F> MapGuard<'a, T, F> {
+    /// Returns the whole value which contains the projected part.
+    pub fn snapshot(&self) -> &Arc<T> {
+        &self.value
+    }
+}
+impl<'a, T: ?Sized, U: ?Sized, F> Deref for MapGuard<'a, T, F>
+where
+    F: Fn(&T) -> &U,
+{
+    type Target = U;
+    fn deref(&self) -> &U {
+        (self.f)(&self.value)
+    }
+}
+impl<'a, T: ?Sized, U: ?Sized + fmt::Debug, F> fmt::Debug for MapGuard<'a, T, F>
+where
+    F: Fn(&T) -> &U,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MapGuard").field(&&**self).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use AtomicImmut;
+
+    #[test]
+    fn map_works() {
+        let v = AtomicImmut::new(vec![0, 1, 2]);
+        let first = v.map(|v| &v[0]);
+        let tail = v.map(|v| &v[1..]);
+
+        let guard = tail.load();
+        v.store(vec![3, 4]);
+        assert_eq!(*guard, [1, 2]);
+        assert_eq!(**guard.snapshot(), [0, 1, 2]);
+        assert_eq!(*tail.load(), [4]);
+        assert_eq!(*first.load(), 3);
+        assert_eq!(format!("{:?}", first.load()), "MapGuard(3)");
+
+        drop(guard);
+        assert_eq!(Arc::strong_count(&first.cell().load()), 2);
+    }
+}
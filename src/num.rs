@@ -0,0 +1,122 @@
+//! Numeric convenience methods of `AtomicImmut`.
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::AtomicImmut;
+//! use atomic_immut::num::AtomicImmutNum;
+//!
+//! let total = AtomicImmut::new(10u64);
+//! assert_eq!(*total.add(5), 15);
+//! assert_eq!(*total.sub(3), 12);
+//! assert_eq!(*total.max(20), 20);
+//! assert_eq!(*total.min(7), 7);
+//!
+//! let average = AtomicImmut::new(1.5f64);
+//! assert_eq!(*average.add(0.5), 2.0);
+//! ```
+use core::ops::{Add, Sub};
+use primitive::Arc;
+
+use backend::Backend;
+use AtomicImmut;
+
+/// Numeric update methods of `AtomicImmut`.
+///
+/// Each method updates the value with a compare-and-swap loop and returns the new value.
+/// The arithmetic follows the operators of `N`
+/// (e.g., integer overflows panic in debug builds).
+pub trait AtomicImmutNum<N> {
+    /// Adds `n` to the value.
+    fn add(&self, n: N) -> Arc<N>;
+
+    /// Subtracts `n` from the value.
+    fn sub(&self, n: N) -> Arc<N>;
+
+    /// Replaces the value with `n` if `n` is greater than it.
+    ///
+    /// If not, the value is left untouched (and no store happens).
+    fn max(&self, n: N) -> Arc<N>;
+
+    /// Replaces the value with `n` if `n` is less than it.
+    ///
+    /// If not, the value is left untouched (and no store happens).
+    fn min(&self, n: N) -> Arc<N>;
+}
+impl<N, B> AtomicImmutNum<N> for AtomicImmut<N, B>
+where
+    N: Copy + Add<Output = N> + Sub<Output = N> + PartialOrd,
+    B: Backend,
+{
+    fn add(&self, n: N) -> Arc<N> {
+        self.update_and_fetch(|&v| v + n)
+    }
+
+    fn sub(&self, n: N) -> Arc<N> {
+        self.update_and_fetch(|&v| v - n)
+    }
+
+    fn max(&self, n: N) -> Arc<N> {
+        replace_if(self, n, |current| n > current)
+    }
+
+    fn min(&self, n: N) -> Arc<N> {
+        replace_if(self, n, |current| n < current)
+    }
+}
+
+fn replace_if<N, B, F>(cell: &AtomicImmut<N, B>, n: N, f: F) -> Arc<N>
+where
+    N: Copy,
+    B: Backend,
+    F: Fn(N) -> bool,
+{
+    let mut current = cell.load();
+    loop {
+        if !f(*current) {
+            return current;
+        }
+        let new = Arc::new(n);
+        match cell.compare_and_swap_arc(&current, Arc::clone(&new)) {
+            Ok(_) => return new,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn num_works() {
+        let sum = Arc::new(AtomicImmut::new(0u64));
+        let max = Arc::new(AtomicImmut::new(0u64));
+        let handles = (0..4)
+            .map(|i| {
+                let sum = Arc::clone(&sum);
+                let max = Arc::clone(&max);
+                thread::spawn(move || {
+                    for j in 0..100 {
+                        sum.add(2);
+                        sum.sub(1);
+                        max.max(i * 100 + j);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*sum.load(), 400);
+        assert_eq!(*max.load(), 399);
+
+        let min = AtomicImmut::new(f64::NAN);
+        assert!(min.min(1.0).is_nan()); // Not comparable
+        let min = AtomicImmut::new(2.0);
+        assert_eq!(*min.min(1.0), 1.0);
+        assert_eq!(*min.min(3.0), 1.0);
+    }
+}
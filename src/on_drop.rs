@@ -0,0 +1,148 @@
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::ptr;
+use primitive::Arc;
+
+/// The hook of `OnDrop<T>`.
+pub type DropHook<T> = Arc<dyn Fn(&T) + Send + Sync>;
+
+/// A value which calls a hook when it is finally destroyed.
+///
+/// An `AtomicImmut` cannot tell when the last reader of a replaced value lets it go,
+/// since readers share the value through `Arc`s.
+/// Holding `OnDrop<T>` instead of `T` makes the value itself report it,
+/// so that cleanup of external resources can be sequenced after the value becomes unreachable.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use atomic_immut::{AtomicImmut, OnDrop};
+///
+/// let dropped = Arc::new(Mutex::new(Vec::new()));
+/// let first = {
+///     let dropped = Arc::clone(&dropped);
+///     OnDrop::new("region-1", move |name: &&str| dropped.lock().unwrap().push(*name))
+/// };
+///
+/// let region = AtomicImmut::new(first);
+/// let reader = region.load();
+/// region.store(reader.with_same_hook("region-2"));
+///
+/// // The reader still holds `region-1`.
+/// assert!(dropped.lock().unwrap().is_empty());
+/// assert_eq!(**reader, "region-1");
+///
+/// drop(reader);
+/// assert_eq!(*dropped.lock().unwrap(), ["region-1"]);
+/// ```
+pub struct OnDrop<T> {
+    value: ManuallyDrop<T>,
+    hook: ManuallyDrop<DropHook<T>>,
+}
+impl<T> OnDrop<T> {
+    /// Makes a new `OnDrop` instance which calls `hook` with `value` when it is destroyed.
+    pub fn new<F>(value: T, hook: F) -> Self
+    where
+        F: Fn(&T) + Send + Sync + 'static,
+    {
+        Self::with_hook(value, dyn_arc!(hook))
+    }
+
+    /// Makes a new `OnDrop` instance which calls the shared `hook` with `value` when it is destroyed.
+    pub fn with_hook(value: T, hook: DropHook<T>) -> Self {
+        OnDrop {
+            value: ManuallyDrop::new(value),
+            hook: ManuallyDrop::new(hook),
+        }
+    }
+
+    /// Makes a new `OnDrop` instance which holds `value` and shares the hook of this instance.
+    ///
+    /// This is handy for storing a new value into an `AtomicImmut<OnDrop<T>>`.
+    pub fn with_same_hook(&self, value: T) -> Self {
+        Self::with_hook(value, self.hook().clone())
+    }
+
+    /// Returns the hook.
+    pub fn hook(&self) -> &DropHook<T> {
+        &self.hook
+    }
+
+    /// Takes the value out without calling the hook.
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        unsafe {
+            ManuallyDrop::drop(&mut this.hook);
+            ptr::read(&*this.value)
+        }
+    }
+}
+impl<T> Deref for OnDrop<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+impl<T> Drop for OnDrop<T> {
+    fn drop(&mut self) {
+        // The value is dropped even if the hook panics.
+        struct DropValue<'a, T: 'a>(&'a mut ManuallyDrop<T>);
+        impl<'a, T> Drop for DropValue<'a, T> {
+            fn drop(&mut self) {
+                unsafe { ManuallyDrop::drop(self.0) }
+            }
+        }
+
+        let value = DropValue(&mut self.value);
+        (self.hook)(value.0);
+        drop(value);
+        unsafe { ManuallyDrop::drop(&mut self.hook) }
+    }
+}
+impl<T: fmt::Debug> fmt::Debug for OnDrop<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OnDrop").field(&*self.value).finish()
+    }
+}
+impl<T: PartialEq> PartialEq for OnDrop<T> {
+    fn eq(&self, other: &Self) -> bool {
+        *self.value == *other.value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use AtomicImmut;
+
+    #[test]
+    fn hook_is_called_once_per_value() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let hook: DropHook<Vec<u8>> = {
+            let dropped = Arc::clone(&dropped);
+            Arc::new(move |_| {
+                dropped.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+
+        let v = AtomicImmut::new(OnDrop::with_hook(vec![0], Arc::clone(&hook)));
+        for i in 1..10 {
+            v.update(|old| old.with_same_hook(vec![i]));
+        }
+        assert_eq!(dropped.load(Ordering::SeqCst), 9);
+
+        let value = OnDrop::with_hook(vec![1], Arc::clone(&hook)).into_inner();
+        assert_eq!(value, [1]);
+        assert_eq!(dropped.load(Ordering::SeqCst), 9);
+
+        drop(v);
+        assert_eq!(dropped.load(Ordering::SeqCst), 10);
+        assert_eq!(Arc::strong_count(&hook), 1);
+    }
+}
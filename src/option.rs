@@ -0,0 +1,277 @@
+use core::mem;
+use core::ptr;
+use core::sync::atomic::Ordering;
+use primitive::Arc;
+
+use backend::{Backend, SpinBackend};
+use checks::Checks;
+use lock::WriterLock;
+use primitive::{self, AtomicPtr};
+use to_arc_ptr;
+
+/// A thread-safe pointer for optional immutable value.
+///
+/// This is the same as `AtomicImmut` except that it may contain no value.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutOption;
+///
+/// let value = AtomicImmutOption::none();
+/// assert!(value.is_none());
+///
+/// assert!(value.set_if_none(5));
+/// assert!(!value.set_if_none(10));
+/// assert_eq!(value.load().map(|v| *v), Some(5));
+///
+/// assert_eq!(value.take().map(|v| *v), Some(5));
+/// assert!(value.is_none());
+/// ```
+#[derive(Debug)]
+pub struct AtomicImmutOption<T> {
+    ptr: AtomicPtr<T>,
+    backend: SpinBackend,
+    writer: WriterLock,
+    checks: Checks,
+}
+impl<T> AtomicImmutOption<T> {
+    /// Makes a new `AtomicImmutOption` instance.
+    pub fn new(value: Option<T>) -> Self {
+        let checks = Checks::new();
+        if value.is_some() {
+            checks.forgotten();
+        }
+        let ptr = AtomicPtr::new(value.map_or_else(ptr::null_mut, to_arc_ptr));
+        let backend = SpinBackend::new();
+        AtomicImmutOption {
+            ptr,
+            backend,
+            writer: WriterLock::new(),
+            checks,
+        }
+    }
+
+    /// Makes a new `AtomicImmutOption` instance which has no value.
+    pub fn none() -> Self {
+        Self::new(None)
+    }
+
+    /// Returns `true` if this pointer has no value.
+    pub fn is_none(&self) -> bool {
+        self.ptr.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns `true` if this pointer has a value.
+    pub fn is_some(&self) -> bool {
+        !self.is_none()
+    }
+
+    /// Loads the value from this pointer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutOption;
+    ///
+    /// let value = AtomicImmutOption::new(Some(5));
+    /// assert_eq!(value.load().map(|v| *v), Some(5));
+    /// ```
+    pub fn load(&self) -> Option<Arc<T>> {
+        let _read = self.checks.enter();
+        let (_guard, ptr) = self.backend.protect(&self.ptr);
+        if ptr.is_null() {
+            return None;
+        }
+        let value = unsafe { Arc::from_raw(ptr) };
+        mem::forget(Arc::clone(&value));
+        Some(value)
+    }
+
+    /// Stores a value into this pointer.
+    pub fn store(&self, value: T) {
+        self.swap(value);
+    }
+
+    /// Stores a value into this pointer, returning the old value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutOption;
+    ///
+    /// let value = AtomicImmutOption::none();
+    /// assert_eq!(value.swap(5), None);
+    /// assert_eq!(value.swap(10).map(|v| *v), Some(5));
+    /// ```
+    pub fn swap(&self, value: T) -> Option<Arc<T>> {
+        self.replace(to_arc_ptr(value))
+    }
+
+    /// Takes the value out of this pointer, leaving no value in its place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutOption;
+    ///
+    /// let value = AtomicImmutOption::new(Some(5));
+    /// assert_eq!(value.take().map(|v| *v), Some(5));
+    /// assert_eq!(value.take(), None);
+    /// ```
+    pub fn take(&self) -> Option<Arc<T>> {
+        self.replace(ptr::null_mut())
+    }
+
+    /// Stores a value into this pointer only if it has no value.
+    ///
+    /// Returns `true` if the value is stored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutOption;
+    ///
+    /// let value = AtomicImmutOption::none();
+    /// assert!(value.set_if_none(5));
+    /// assert!(!value.set_if_none(10));
+    /// assert_eq!(value.load().map(|v| *v), Some(5));
+    /// ```
+    pub fn set_if_none(&self, value: T) -> bool {
+        self.set_arc_if_none(&Arc::new(value))
+    }
+
+    /// Loads the value from this pointer, initializing it with the result of `f` if it has no value.
+    ///
+    /// If several threads call this method concurrently,
+    /// `f` may be called by more than one of them but only one of the results is stored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutOption;
+    ///
+    /// let value = AtomicImmutOption::none();
+    /// assert_eq!(*value.get_or_init(|| 5), 5);
+    /// assert_eq!(*value.get_or_init(|| 10), 5);
+    /// ```
+    pub fn get_or_init<F>(&self, f: F) -> Arc<T>
+    where
+        F: FnOnce() -> T,
+    {
+        if let Some(value) = self.load() {
+            return value;
+        }
+
+        let new = Arc::new(f());
+        loop {
+            if self.set_arc_if_none(&new) {
+                return new;
+            }
+            if let Some(value) = self.load() {
+                return value;
+            }
+        }
+    }
+
+    fn set_arc_if_none(&self, value: &Arc<T>) -> bool {
+        let new = Arc::into_raw(Arc::clone(value)).cast_mut();
+        // Not `compare_exchange_weak`: `set_if_none` must not fail spuriously.
+        match self
+            .ptr
+            .compare_exchange(ptr::null_mut(), new, Ordering::Release, Ordering::Relaxed)
+        {
+            Ok(_) => {
+                self.checks.forgotten();
+                true
+            }
+            Err(_) => {
+                unsafe { Arc::from_raw(new) };
+                false
+            }
+        }
+    }
+
+    fn replace(&self, new: *mut T) -> Option<Arc<T>> {
+        if !new.is_null() {
+            self.checks.forgotten();
+        }
+        // Writers are serialized until the old value is released,
+        // like `AtomicImmut` whose writers hold the lock until the new value is installed.
+        let _lock = self.writer.lock();
+        // `Acquire` makes the old value, stored by another thread, safe to release.
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        if old.is_null() {
+            return None;
+        }
+        self.backend.synchronize(old);
+        self.checks.recovered();
+        Some(unsafe { Arc::from_raw(old) })
+    }
+}
+unsafe impl<T: Send + Sync> Send for AtomicImmutOption<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicImmutOption<T> {}
+impl<T> Drop for AtomicImmutOption<T> {
+    fn drop(&mut self) {
+        let ptr = primitive::with_mut(&mut self.ptr, |p| mem::replace(p, ptr::null_mut()));
+        if !ptr.is_null() {
+            self.checks.recovered();
+            let _ = unsafe { Arc::from_raw(ptr) };
+        }
+        self.checks.assert_balanced(0);
+    }
+}
+impl<T> Default for AtomicImmutOption<T> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let v = AtomicImmutOption::new(Some(vec![0, 1, 2]));
+        assert!(v.is_some());
+        assert_eq!(Arc::strong_count(&v.load().unwrap()), 2);
+
+        let old = v.take().unwrap();
+        assert_eq!(&*old, &vec![0, 1, 2]);
+        assert_eq!(Arc::strong_count(&old), 1);
+        assert!(v.is_none());
+        assert_eq!(v.load(), None);
+
+        assert!(v.set_if_none(vec![0]));
+        assert!(!v.set_if_none(vec![1]));
+        assert_eq!(&*v.load().unwrap(), &vec![0]);
+        assert_eq!(Arc::strong_count(&v.load().unwrap()), 2);
+    }
+
+    #[test]
+    fn get_or_init_works() {
+        let v = Arc::new(AtomicImmutOption::none());
+        let thread_count = 8;
+        let barrier = Arc::new(Barrier::new(thread_count));
+        let handles = (0..thread_count)
+            .map(|i| {
+                let v = v.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    v.get_or_init(|| i)
+                })
+            })
+            .collect::<Vec<_>>();
+        let values = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>();
+        let stored = v.load().unwrap();
+        assert!(values.iter().all(|x| Arc::ptr_eq(x, &stored)));
+    }
+}
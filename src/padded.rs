@@ -0,0 +1,63 @@
+use core::ops::{Deref, DerefMut};
+
+/// Pads and aligns a value to the size of a cache line
+/// if the `padding` feature is enabled (otherwise, it is just a transparent wrapper).
+///
+/// Hot atomics written by writers (e.g., lock words and reader counters) and
+/// the pointer loaded by every reader are padded,
+/// so that updating one of them does not evict the others from the caches of other cores.
+///
+/// Like `crossbeam_utils::CachePadded`, 128 bytes are used on x86-64 and AArch64
+/// because their prefetchers pull pairs of 64-byte lines.
+#[derive(Debug, Default)]
+#[cfg_attr(
+    all(
+        feature = "padding",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ),
+    repr(align(128))
+)]
+#[cfg_attr(
+    all(
+        feature = "padding",
+        not(any(target_arch = "x86_64", target_arch = "aarch64"))
+    ),
+    repr(align(64))
+)]
+pub(crate) struct CachePadded<T>(T);
+impl<T> CachePadded<T> {
+    pub const fn new(value: T) -> Self {
+        CachePadded(value)
+    }
+}
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::mem;
+    use core::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn padding_works() {
+        let size = mem::size_of::<CachePadded<AtomicUsize>>();
+        if cfg!(feature = "padding") {
+            assert!(size >= 64);
+            assert_eq!(size, mem::align_of::<CachePadded<AtomicUsize>>());
+        } else {
+            assert_eq!(size, mem::size_of::<AtomicUsize>());
+        }
+    }
+}
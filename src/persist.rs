@@ -0,0 +1,344 @@
+//! Persistence of values to snapshot files.
+//!
+//! This module is available when the `persist` feature is enabled.
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::codec::CodecError;
+//! use atomic_immut::persist::PersistentAtomicImmut;
+//!
+//! let path = std::env::temp_dir()
+//!     .join(format!("atomic_immut_persist_doc_{}.snapshot", std::process::id()));
+//! let codec = (
+//!     |v: &String| -> Result<Vec<u8>, CodecError> { Ok(v.clone().into_bytes()) },
+//!     |b: &[u8]| -> Result<String, CodecError> { Ok(String::from_utf8(b.to_vec())?) },
+//! );
+//!
+//! let state = PersistentAtomicImmut::open_or_else(&path, codec, || "initial".to_owned()).unwrap();
+//! state.store("updated".to_owned()).unwrap();
+//! drop(state);
+//!
+//! // After a restart
+//! let state = PersistentAtomicImmut::open(&path, codec).unwrap();
+//! assert_eq!(*state.load(), "updated");
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+use std::convert::TryInto;
+use std::error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use codec::{Codec, CodecError};
+use AtomicImmut;
+
+/// The magic number at the head of snapshot files.
+const MAGIC: &[u8; 8] = b"AIMSNAP1";
+
+/// The size of the header (the magic number, the length and the checksum of the payload).
+const HEADER_LEN: usize = 24;
+
+/// Possible errors of persistence.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read or write the snapshot file.
+    Io(io::Error),
+
+    /// Failed to encode or decode the value.
+    Codec(CodecError),
+
+    /// The snapshot file is corrupted (e.g., truncated by a crash of a non-atomic writer).
+    Corrupted(&'static str),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Codec(ref e) => write!(f, "Codec error: {}", e),
+            Error::Corrupted(reason) => write!(f, "Corrupted snapshot: {}", reason),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Codec(ref e) => Some(&**e),
+            Error::Corrupted(_) => None,
+        }
+    }
+}
+impl From<io::Error> for Error {
+    fn from(f: io::Error) -> Self {
+        Error::Io(f)
+    }
+}
+
+/// An `AtomicImmut` whose values are written through to a snapshot file.
+///
+/// Every store writes the new value to a temporary file and renames it to the snapshot file,
+/// so the snapshot file always holds a complete value even if the process crashes.
+/// The value becomes visible to readers only after it has been persisted.
+///
+/// Stores are serialized by a lock (readers are never blocked by it).
+pub struct PersistentAtomicImmut<T, C> {
+    cell: AtomicImmut<T>,
+    path: PathBuf,
+    codec: C,
+    writer: Mutex<()>,
+}
+impl<T, C: Codec<T>> PersistentAtomicImmut<T, C> {
+    /// Opens the snapshot file at `path`, loading the last persisted value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file does not exist, is corrupted, or cannot be decoded.
+    pub fn open<P: AsRef<Path>>(path: P, codec: C) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let value = read_snapshot(&path, &codec)?;
+        Ok(Self::with_value(path, codec, value))
+    }
+
+    /// Same as `open` except that if the file does not exist,
+    /// it is created with the value returned by `f`.
+    pub fn open_or_else<P, F>(path: P, codec: C, f: F) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        F: FnOnce() -> T,
+    {
+        let path = path.as_ref().to_path_buf();
+        match read_snapshot(&path, &codec) {
+            Ok(value) => Ok(Self::with_value(path, codec, value)),
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => {
+                let value = f();
+                write_snapshot(&path, &codec.encode(&value).map_err(Error::Codec)?)?;
+                Ok(Self::with_value(path, codec, value))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn with_value(path: PathBuf, codec: C, value: T) -> Self {
+        PersistentAtomicImmut {
+            cell: AtomicImmut::new(value),
+            path,
+            codec,
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Loads the current value.
+    pub fn load(&self) -> Arc<T> {
+        self.cell.load()
+    }
+
+    /// Persists `value` and then stores it.
+    ///
+    /// # Errors
+    ///
+    /// If persisting fails, the value is not stored and an error is returned.
+    pub fn store(&self, value: T) -> Result<(), Error> {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        self.persist_and_store(value)
+    }
+
+    /// Updates the value by applying `f` to the current one, persisting the result.
+    ///
+    /// Unlike `AtomicImmut::update`, `f` is called exactly once since the stores are serialized.
+    ///
+    /// # Errors
+    ///
+    /// If persisting fails, the value is not stored and an error is returned.
+    pub fn update<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let value = f(&self.cell.load());
+        self.persist_and_store(value)
+    }
+
+    fn persist_and_store(&self, value: T) -> Result<(), Error> {
+        let payload = self.codec.encode(&value).map_err(Error::Codec)?;
+        write_snapshot(&self.path, &payload)?;
+        self.cell.store(value);
+        Ok(())
+    }
+}
+impl<T, C> PersistentAtomicImmut<T, C> {
+    /// Returns the path of the snapshot file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the underlying `AtomicImmut` (e.g., to subscribe to the changes).
+    ///
+    /// Note that the values stored directly into the returned `AtomicImmut` are not persisted.
+    pub fn as_immut(&self) -> &AtomicImmut<T> {
+        &self.cell
+    }
+}
+impl<T: fmt::Debug, C> fmt::Debug for PersistentAtomicImmut<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PersistentAtomicImmut")
+            .field("value", &self.cell.load())
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+fn read_snapshot<T, C: Codec<T>>(path: &Path, codec: &C) -> Result<T, Error> {
+    let bytes = fs::read(path)?;
+    if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+        return Err(Error::Corrupted("Not a snapshot file"));
+    }
+    let len = u64::from_le_bytes(bytes[8..16].try_into().expect("Never fails"));
+    let checksum = u64::from_le_bytes(bytes[16..24].try_into().expect("Never fails"));
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() as u64 != len {
+        return Err(Error::Corrupted("Length mismatch"));
+    }
+    if fnv1a(payload) != checksum {
+        return Err(Error::Corrupted("Checksum mismatch"));
+    }
+    codec.decode(payload).map_err(Error::Codec)
+}
+
+fn write_snapshot(path: &Path, payload: &[u8]) -> io::Result<()> {
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&(payload.len() as u64).to_le_bytes())?;
+        file.write_all(&fnv1a(payload).to_le_bytes())?;
+        file.write_all(payload)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+/// The 64-bit FNV-1a hash (used as a checksum, not against malicious modifications).
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    type TestCodec = (
+        fn(&u32) -> Result<Vec<u8>, CodecError>,
+        fn(&[u8]) -> Result<u32, CodecError>,
+    );
+
+    fn codec() -> TestCodec {
+        (
+            |v| Ok(v.to_string().into_bytes()),
+            |b| Ok(::std::str::from_utf8(b)?.parse()?),
+        )
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, Ordering::SeqCst);
+        ::std::env::temp_dir().join(format!(
+            "atomic_immut_persist_{}_{}_{}",
+            name,
+            ::std::process::id(),
+            n
+        ))
+    }
+
+    #[test]
+    fn persistence_works() {
+        let path = temp_path("works");
+        assert!(matches!(
+            PersistentAtomicImmut::open(&path, codec()),
+            Err(Error::Io(_))
+        ));
+
+        let cell = PersistentAtomicImmut::open_or_else(&path, codec(), || 1).unwrap();
+        cell.update(|v| v + 1).unwrap();
+        assert_eq!(*cell.load(), 2);
+
+        let cell = PersistentAtomicImmut::open_or_else(&path, codec(), || 100).unwrap();
+        assert_eq!(*cell.load(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_codec_works() {
+        use codec::JsonCodec;
+
+        let path = temp_path("json");
+        let cell = PersistentAtomicImmut::open_or_else(&path, JsonCodec, Vec::new).unwrap();
+        cell.update(|v| [&v[..], &["foo".to_owned()]].concat())
+            .unwrap();
+
+        let cell: PersistentAtomicImmut<Vec<String>, _> =
+            PersistentAtomicImmut::open(&path, JsonCodec).unwrap();
+        assert_eq!(*cell.load(), ["foo"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_codec_works() {
+        use codec::BincodeCodec;
+
+        let path = temp_path("bincode");
+        let cell = PersistentAtomicImmut::open_or_else(&path, BincodeCodec, Vec::new).unwrap();
+        cell.update(|v| [&v[..], &[(1u8, "foo".to_owned())]].concat())
+            .unwrap();
+
+        let cell: PersistentAtomicImmut<Vec<(u8, String)>, _> =
+            PersistentAtomicImmut::open(&path, BincodeCodec).unwrap();
+        assert_eq!(*cell.load(), [(1, "foo".to_owned())]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn corruption_is_detected() {
+        let path = temp_path("corruption");
+        PersistentAtomicImmut::open_or_else(&path, codec(), || 12345).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(
+            PersistentAtomicImmut::open(&path, codec()),
+            Err(Error::Corrupted(_))
+        ));
+
+        let last = bytes.len() - 1;
+        bytes[last] = b'0';
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            PersistentAtomicImmut::open(&path, codec()),
+            Err(Error::Corrupted(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_persistence_does_not_store() {
+        let path = temp_path("failure");
+        let cell = PersistentAtomicImmut::open_or_else(&path, codec(), || 1).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+
+        assert!(matches!(cell.store(2), Err(Error::Io(_))));
+        assert_eq!(*cell.load(), 1);
+        fs::remove_dir(&path).unwrap();
+    }
+}
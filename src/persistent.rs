@@ -0,0 +1,356 @@
+use core::fmt;
+use im;
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+use AtomicImmut;
+
+/// A thread-safe pointer for `im::HashMap` (available when the `im` feature is enabled).
+///
+/// `im::HashMap` is a persistent map: cloning it is O(1)
+/// and the modified clone shares all but O(log n) nodes with the original.
+/// So the update methods of this type take O(log n) time,
+/// while the ones of `AtomicImmut<std::collections::HashMap<K, V>>` copy the whole map.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutHashMap;
+///
+/// let routes = AtomicImmutHashMap::new();
+/// assert!(routes.insert("/", 0));
+/// let snapshot = routes.load();
+///
+/// assert!(routes.insert("/users", 1));
+/// assert!(routes.remove("/"));
+/// assert_eq!(routes.get("/users"), Some(1));
+/// assert_eq!(snapshot.get("/"), Some(&0));
+/// assert_eq!(snapshot.len(), 1);
+/// ```
+pub struct AtomicImmutHashMap<K, V> {
+    inner: AtomicImmut<im::HashMap<K, V>>,
+}
+impl<K, V> AtomicImmutHashMap<K, V>
+where
+    K: Clone + Hash + Eq,
+    V: Clone,
+{
+    /// Makes a new `AtomicImmutHashMap` instance which has an empty map.
+    pub fn new() -> Self {
+        Self::from(im::HashMap::new())
+    }
+
+    /// Loads the map from this pointer.
+    ///
+    /// This is O(1) since the returned map shares the nodes with the stored one.
+    pub fn load(&self) -> im::HashMap<K, V> {
+        im::HashMap::clone(&self.inner.peek())
+    }
+
+    /// Returns a clone of the value corresponding to `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.peek().get(key).cloned()
+    }
+
+    /// Returns `true` if the map contains `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.peek().contains_key(key)
+    }
+
+    /// Returns the number of the entries in the map.
+    pub fn len(&self) -> usize {
+        self.inner.peek().len()
+    }
+
+    /// Returns `true` if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts an entry into the map.
+    ///
+    /// Returns `false` if the map already has the same entry.
+    pub fn insert(&self, key: K, value: V) -> bool
+    where
+        V: PartialEq,
+    {
+        self.inner.try_update(|m| {
+            if m.get(&key) == Some(&value) {
+                return None;
+            }
+            Some(m.update(key.clone(), value.clone()))
+        })
+    }
+
+    /// Removes the entry of `key` from the map.
+    ///
+    /// Returns `false` if the map has no such entry.
+    pub fn remove<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.try_update(|m| {
+            if !m.contains_key(key) {
+                return None;
+            }
+            Some(m.without(key))
+        })
+    }
+
+    /// Modifies a clone of the map by `f` and stores it (retrying on conflicts like `AtomicImmut::update`).
+    ///
+    /// `f` may be called more than once.
+    pub fn update<F>(&self, mut f: F)
+    where
+        F: FnMut(&mut im::HashMap<K, V>),
+    {
+        self.inner.update(|m| {
+            let mut m = m.clone();
+            f(&mut m);
+            m
+        });
+    }
+
+    /// Stores a map into this pointer.
+    pub fn store(&self, map: im::HashMap<K, V>) {
+        self.inner.store(map);
+    }
+
+    /// Returns the underlying `AtomicImmut` (e.g., to subscribe to stores).
+    pub fn as_immut(&self) -> &AtomicImmut<im::HashMap<K, V>> {
+        &self.inner
+    }
+}
+impl<K, V> Default for AtomicImmutHashMap<K, V>
+where
+    K: Clone + Hash + Eq,
+    V: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<K, V> From<im::HashMap<K, V>> for AtomicImmutHashMap<K, V> {
+    fn from(map: im::HashMap<K, V>) -> Self {
+        AtomicImmutHashMap {
+            inner: AtomicImmut::new(map),
+        }
+    }
+}
+impl<K, V> fmt::Debug for AtomicImmutHashMap<K, V>
+where
+    K: fmt::Debug + Hash + Eq,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicImmutHashMap")
+            .field("value", &*self.inner.peek())
+            .finish()
+    }
+}
+
+/// A thread-safe pointer for `im::Vector` (available when the `im` feature is enabled).
+///
+/// Like `AtomicImmutHashMap`, cloning the vector is O(1),
+/// so the update methods of this type take O(log n) time instead of copying the whole vector.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutVector;
+///
+/// let log = AtomicImmutVector::new();
+/// log.push_back("started");
+/// let snapshot = log.load();
+///
+/// log.push_back("ready");
+/// assert!(log.set(0, "restarted"));
+/// assert!(!log.set(2, "stopped"));
+/// assert_eq!(log.get(0), Some("restarted"));
+/// assert_eq!(log.len(), 2);
+/// assert_eq!(snapshot.len(), 1);
+/// assert_eq!(snapshot[0], "started");
+/// ```
+pub struct AtomicImmutVector<T> {
+    inner: AtomicImmut<im::Vector<T>>,
+}
+impl<T: Clone> AtomicImmutVector<T> {
+    /// Makes a new `AtomicImmutVector` instance which has an empty vector.
+    pub fn new() -> Self {
+        Self::from(im::Vector::new())
+    }
+
+    /// Loads the vector from this pointer.
+    ///
+    /// This is O(1) since the returned vector shares the nodes with the stored one.
+    pub fn load(&self) -> im::Vector<T> {
+        im::Vector::clone(&self.inner.peek())
+    }
+
+    /// Returns a clone of the element at `index`.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.inner.peek().get(index).cloned()
+    }
+
+    /// Returns the number of the elements in the vector.
+    pub fn len(&self) -> usize {
+        self.inner.peek().len()
+    }
+
+    /// Returns `true` if the vector is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `value` to the back of the vector.
+    pub fn push_back(&self, value: T) {
+        self.update(|v| v.push_back(value.clone()));
+    }
+
+    /// Prepends `value` to the front of the vector.
+    pub fn push_front(&self, value: T) {
+        self.update(|v| v.push_front(value.clone()));
+    }
+
+    /// Replaces the element at `index` with `value`.
+    ///
+    /// Returns `false` if `index` is out of bounds.
+    pub fn set(&self, index: usize, value: T) -> bool {
+        self.inner.try_update(|v| {
+            if index >= v.len() {
+                return None;
+            }
+            Some(v.update(index, value.clone()))
+        })
+    }
+
+    /// Modifies a clone of the vector by `f` and stores it (retrying on conflicts like `AtomicImmut::update`).
+    ///
+    /// `f` may be called more than once.
+    pub fn update<F>(&self, mut f: F)
+    where
+        F: FnMut(&mut im::Vector<T>),
+    {
+        self.inner.update(|v| {
+            let mut v = v.clone();
+            f(&mut v);
+            v
+        });
+    }
+
+    /// Stores a vector into this pointer.
+    pub fn store(&self, vector: im::Vector<T>) {
+        self.inner.store(vector);
+    }
+
+    /// Returns the underlying `AtomicImmut` (e.g., to subscribe to stores).
+    pub fn as_immut(&self) -> &AtomicImmut<im::Vector<T>> {
+        &self.inner
+    }
+}
+impl<T: Clone> Default for AtomicImmutVector<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T> From<im::Vector<T>> for AtomicImmutVector<T> {
+    fn from(vector: im::Vector<T>) -> Self {
+        AtomicImmutVector {
+            inner: AtomicImmut::new(vector),
+        }
+    }
+}
+impl<T: Clone + fmt::Debug> fmt::Debug for AtomicImmutVector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicImmutVector")
+            .field("value", &*self.inner.peek())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn hash_map_works() {
+        let m = Arc::new(AtomicImmutHashMap::new());
+        let handles = (0..4)
+            .map(|i| {
+                let m = Arc::clone(&m);
+                thread::spawn(move || {
+                    for j in 0..50 {
+                        assert!(m.insert(i * 50 + j, j));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(m.len(), 200);
+        assert!(!m.insert(0, 0));
+        assert!(m.insert(0, 1));
+        assert!(m.remove(&0));
+        assert!(!m.remove(&0));
+        assert!(!m.contains_key(&0));
+
+        m.update(|m| m.retain(|&k, _| k < 100));
+        assert_eq!(m.len(), 99);
+        assert_eq!(m.get(&99), Some(49));
+    }
+
+    #[test]
+    fn hash_map_updates_share_nodes() {
+        let m =
+            AtomicImmutHashMap::from((0..10_000).map(|i| (i, i)).collect::<im::HashMap<_, _>>());
+        let old = m.load();
+        assert!(m.insert(0, 1));
+        assert!(!old.ptr_eq(&m.load()));
+        assert_eq!(old.get(&0), Some(&0));
+        assert_eq!(m.get(&0), Some(1));
+    }
+
+    #[test]
+    fn vector_works() {
+        let v = Arc::new(AtomicImmutVector::new());
+        let handles = (0..4)
+            .map(|i| {
+                let v = Arc::clone(&v);
+                thread::spawn(move || {
+                    for j in 0..50 {
+                        v.push_back(i * 50 + j);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(v.len(), 200);
+
+        let old = v.load();
+        v.push_front(-1);
+        assert!(v.set(1, -2));
+        assert!(!v.set(201, 0));
+        assert_eq!(v.get(0), Some(-1));
+        assert_eq!(v.get(1), Some(-2));
+        assert_eq!(old.len(), 200);
+
+        let mut values = v.load().into_iter().skip(2).collect::<Vec<_>>();
+        values.push(old[0]);
+        values.sort();
+        assert_eq!(values, (0..200).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,66 @@
+//! Synchronization primitives used by the pointer protocol.
+//!
+//! When the crate is built with `--cfg loom`, these are replaced by the models of `loom`
+//! so that the protocol can be checked exhaustively (see `tests/loom.rs`).
+//!
+//! When the `portable-atomic` feature is enabled, atomics and `Arc` are taken from
+//! `portable-atomic` and `portable-atomic-util`, which work on targets without compare-and-swap.
+#[cfg(all(not(loom), not(feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize};
+#[cfg(all(not(loom), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize};
+
+#[cfg(not(feature = "portable-atomic"))]
+pub(crate) use alloc::sync::{Arc, Weak};
+#[cfg(feature = "portable-atomic")]
+pub(crate) use portable_atomic_util::{Arc, Weak};
+
+/// Atomics which are not modeled by `loom` (e.g., those of the other primitives of this crate).
+pub(crate) mod plain {
+    #[cfg(not(feature = "portable-atomic"))]
+    pub(crate) use core::sync::atomic::*;
+    #[cfg(feature = "portable-atomic")]
+    pub(crate) use portable_atomic::*;
+}
+
+/// Executes `f` with a mutable reference to the raw pointer held by `ptr`.
+///
+/// `loom::sync::atomic::AtomicPtr` has no `get_mut`.
+#[cfg(loom)]
+pub(crate) fn with_mut<T, F, R>(ptr: &mut AtomicPtr<T>, f: F) -> R
+where
+    F: FnOnce(&mut *mut T) -> R,
+{
+    ptr.with_mut(f)
+}
+#[cfg(not(loom))]
+pub(crate) fn with_mut<T, F, R>(ptr: &mut AtomicPtr<T>, f: F) -> R
+where
+    F: FnOnce(&mut *mut T) -> R,
+{
+    f(ptr.get_mut())
+}
+
+/// Signals that the current thread is busy-waiting.
+///
+/// `loom` requires spin loops to yield so that the other threads can make progress in the model.
+#[cfg(loom)]
+pub(crate) fn spin_loop() {
+    loom::thread::yield_now();
+}
+#[cfg(not(loom))]
+pub(crate) fn spin_loop() {
+    core::hint::spin_loop();
+}
+
+/// Yields the current thread.
+#[cfg(loom)]
+pub(crate) fn yield_now() {
+    loom::thread::yield_now();
+}
+#[cfg(all(not(loom), feature = "std"))]
+pub(crate) fn yield_now() {
+    std::thread::yield_now();
+}
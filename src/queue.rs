@@ -0,0 +1,193 @@
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use backend::{Backend, Backoff, DefaultBackend};
+use AtomicImmut;
+
+type UpdateFn<T> = Box<dyn FnOnce(&T) -> T + Send>;
+
+/// An `AtomicImmut` whose updates are queued and applied by a single thread at a time.
+///
+/// `AtomicImmut::update` retries the update function on conflicts,
+/// which wastes work when many threads update the same pointer.
+/// Instead, `UpdateQueue::update` enqueues the function,
+/// and one of the updating threads (the first one which finds no other applier)
+/// folds all the queued functions into the value in order and stores the result once.
+/// So every function is called exactly once, and it can be `FnOnce`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use atomic_immut::UpdateQueue;
+///
+/// let log = Arc::new(UpdateQueue::new(Vec::new()));
+/// let handles = (0..4)
+///     .map(|i| {
+///         let log = Arc::clone(&log);
+///         thread::spawn(move || {
+///             let entry = format!("thread-{}", i);
+///             log.update(move |v| {
+///                 let mut v = v.clone();
+///                 v.push(entry);
+///                 v
+///             });
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// for h in handles {
+///     h.join().unwrap();
+/// }
+/// assert_eq!(log.load().len(), 4);
+/// ```
+pub struct UpdateQueue<T, B = DefaultBackend> {
+    cell: AtomicImmut<T, B>,
+    queue: Mutex<Pending<T>>,
+    applying: AtomicBool,
+    applied: AtomicU64,
+}
+impl<T> UpdateQueue<T> {
+    /// Makes a new `UpdateQueue` instance.
+    pub fn new(value: T) -> Self {
+        Self::from_immut(AtomicImmut::new(value))
+    }
+}
+impl<T, B: Backend> UpdateQueue<T, B> {
+    /// Makes a new `UpdateQueue` instance which updates `cell`.
+    pub fn from_immut(cell: AtomicImmut<T, B>) -> Self {
+        UpdateQueue {
+            cell,
+            queue: Mutex::new(Pending {
+                updates: Vec::new(),
+                enqueued: 0,
+            }),
+            applying: AtomicBool::new(false),
+            applied: AtomicU64::new(0),
+        }
+    }
+
+    /// Loads the value from this pointer.
+    pub fn load(&self) -> Arc<T> {
+        self.cell.load()
+    }
+
+    /// Updates the value of this pointer by calling `f` on the value to get a new value.
+    ///
+    /// `f` is called exactly once, possibly by another thread updating this pointer,
+    /// and its result is visible when this method returns.
+    /// The functions enqueued by the concurrent updates are applied in the enqueued order
+    /// and stored at once, so intermediate values may not be observed by readers.
+    ///
+    /// If `f` panics, the panic propagates to the thread which has called it,
+    /// and the rest of the functions applied with it are discarded.
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&T) -> T + Send + 'static,
+    {
+        let ticket = {
+            let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+            queue.updates.push(Box::new(f));
+            queue.enqueued += 1;
+            queue.enqueued
+        };
+
+        let mut snooze = Backoff::new().start();
+        while self.applied.load(Ordering::Acquire) < ticket {
+            if self.applying.swap(true, Ordering::Acquire) {
+                snooze.snooze();
+            } else {
+                self.apply_pending();
+            }
+        }
+    }
+
+    /// Returns the underlying `AtomicImmut`.
+    ///
+    /// Values stored directly into the returned `AtomicImmut` are never lost:
+    /// queued functions are applied to whatever value is current at that time.
+    pub fn as_immut(&self) -> &AtomicImmut<T, B> {
+        &self.cell
+    }
+
+    fn apply_pending(&self) {
+        let (updates, last) = {
+            let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+            (mem::take(&mut queue.updates), queue.enqueued)
+        };
+
+        // Releases the waiters even if an update function panics.
+        let _guard = ApplyGuard { queue: self, last };
+        if updates.is_empty() {
+            return;
+        }
+        self.cell.update_exclusive(move |current| {
+            let mut updates = updates.into_iter();
+            let first = updates.next().expect("Never fails");
+            updates.fold(first(current), |v, f| f(&v))
+        });
+    }
+}
+impl<T: fmt::Debug, B: Backend> fmt::Debug for UpdateQueue<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpdateQueue")
+            .field("value", &&*self.cell.load())
+            .field("applied", &self.applied.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+struct Pending<T> {
+    updates: Vec<UpdateFn<T>>,
+    enqueued: u64,
+}
+
+struct ApplyGuard<'a, T: 'a, B: 'a> {
+    queue: &'a UpdateQueue<T, B>,
+    last: u64,
+}
+impl<'a, T, B> Drop for ApplyGuard<'a, T, B> {
+    fn drop(&mut self) {
+        self.queue.applied.store(self.last, Ordering::Release);
+        self.queue.applying.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn every_update_is_applied_once() {
+        let counter = Arc::new(UpdateQueue::new(0));
+        let handles = (0..8)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        counter.update(|v| v + 1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*counter.load(), 800);
+    }
+
+    #[test]
+    fn panics_do_not_block_waiters() {
+        let v = UpdateQueue::new(0);
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            v.update(|_| panic!());
+        }));
+        assert!(result.is_err());
+        v.update(|v| v + 1);
+        assert_eq!(*v.load(), 1);
+    }
+}
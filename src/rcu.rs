@@ -0,0 +1,61 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
+
+pub(crate) type Callback = Box<dyn FnOnce() + Send>;
+
+/// The callbacks deferred by `AtomicImmut::call_rcu` until the next grace period.
+pub(crate) struct Deferred {
+    #[cfg(feature = "std")]
+    count: AtomicUsize,
+    #[cfg(feature = "std")]
+    callbacks: Mutex<Vec<Callback>>,
+}
+impl Deferred {
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Deferred {
+            count: AtomicUsize::new(0),
+            callbacks: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn new() -> Self {
+        Deferred {}
+    }
+
+    #[cfg(feature = "std")]
+    pub fn push(&self, f: Callback) {
+        let mut callbacks = self
+            .callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        callbacks.push(f);
+        self.count.store(callbacks.len(), Ordering::Relaxed);
+    }
+
+    /// Takes the callbacks deferred so far.
+    #[cfg(feature = "std")]
+    pub fn take(&self) -> Vec<Callback> {
+        // Just a hint for the fast path: the callbacks pushed concurrently
+        // are left for the next grace period.
+        if self.count.load(Ordering::Relaxed) == 0 {
+            return Vec::new();
+        }
+        let mut callbacks = self
+            .callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.count.store(0, Ordering::Relaxed);
+        ::core::mem::take(&mut *callbacks)
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn take(&self) -> Vec<Callback> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,86 @@
+//! Deferred destruction of values on a background thread.
+//!
+//! Dropping a large value (e.g., a multi-megabyte map) may take a long time.
+//! The functions of this module move such destruction out of the current thread,
+//! so that writers of `AtomicImmut` are not blocked by destructors.
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::reclaim;
+//!
+//! reclaim::defer_drop(vec![0; 1024]);
+//! reclaim::flush();
+//! ```
+use std::any::Any;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread;
+
+enum Job {
+    Drop(Box<dyn Any + Send>),
+    Flush(Sender<()>),
+}
+
+fn reclaimer() -> &'static Mutex<Sender<Job>> {
+    static RECLAIMER: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+    RECLAIMER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("atomic_immut-reclaimer".to_owned())
+            .spawn(move || {
+                for job in rx {
+                    match job {
+                        Job::Drop(value) => drop(value),
+                        Job::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("Cannot spawn the reclaimer thread");
+        Mutex::new(tx)
+    })
+}
+
+fn send(job: Job) {
+    let tx = reclaimer().lock().unwrap_or_else(PoisonError::into_inner);
+    tx.send(job).expect("The reclaimer thread has terminated");
+}
+
+/// Drops `value` on the background reclaimer thread.
+///
+/// The thread is spawned on the first call.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use atomic_immut::reclaim;
+///
+/// let value = Arc::new(vec![0; 1024]);
+/// let weak = Arc::downgrade(&value);
+///
+/// reclaim::defer_drop(value);
+/// reclaim::flush();
+/// assert!(weak.upgrade().is_none());
+/// ```
+pub fn defer_drop<T: Send + 'static>(value: T) {
+    send(Job::Drop(Box::new(value)));
+}
+
+/// Waits until all the values passed to `defer_drop` before this call are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::reclaim;
+///
+/// reclaim::defer_drop(vec![0; 1024]);
+/// reclaim::flush();
+/// ```
+pub fn flush() {
+    let (tx, rx) = mpsc::channel();
+    send(Job::Flush(tx));
+    let _ = rx.recv();
+}
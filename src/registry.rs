@@ -0,0 +1,178 @@
+//! A process-wide registry of named `AtomicImmutAny` cells.
+//!
+//! This is handy for application-wide settings which would otherwise be plumbed
+//! through every constructor.
+//! The registry itself is an `AtomicImmut`, so lookups never block.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use atomic_immut::{registry, AtomicImmutAny};
+//!
+//! registry::insert("example.timeout", Arc::new(AtomicImmutAny::new(30u32)));
+//! assert_eq!(registry::get::<u32>("example.timeout").map(|v| *v), Some(30));
+//!
+//! registry::cell("example.timeout").unwrap().store(60u32);
+//! assert_eq!(registry::get::<u32>("example.timeout").map(|v| *v), Some(60));
+//! assert!(registry::get::<String>("example.timeout").is_none());
+//! ```
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::{Arc, OnceLock};
+
+use {AnyValue, AtomicImmut, AtomicImmutAny};
+
+type Entries = BTreeMap<String, Arc<AtomicImmutAny>>;
+
+fn registry() -> &'static AtomicImmut<Entries> {
+    static REGISTRY: OnceLock<AtomicImmut<Entries>> = OnceLock::new();
+    REGISTRY.get_or_init(AtomicImmut::default)
+}
+
+/// Registers `cell` under `name`, returning the cell previously registered under the name (if any).
+pub fn insert<N: Into<String>>(name: N, cell: Arc<AtomicImmutAny>) -> Option<Arc<AtomicImmutAny>> {
+    let name = name.into();
+    let old = registry().fetch_update(|entries| {
+        let mut entries = entries.clone();
+        entries.insert(name.clone(), Arc::clone(&cell));
+        entries
+    });
+    old.get(&name).cloned()
+}
+
+/// Unregisters the cell registered under `name`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use atomic_immut::{registry, AtomicImmutAny};
+///
+/// registry::insert("example.removed", Arc::new(AtomicImmutAny::new(1)));
+/// assert!(registry::remove("example.removed").is_some());
+/// assert!(registry::cell("example.removed").is_none());
+/// ```
+pub fn remove(name: &str) -> Option<Arc<AtomicImmutAny>> {
+    let mut removed = None;
+    registry().try_update(|entries| {
+        let mut entries = entries.clone();
+        removed = entries.remove(name);
+        removed.as_ref().map(|_| entries)
+    });
+    removed
+}
+
+/// Returns the cell registered under `name`.
+pub fn cell(name: &str) -> Option<Arc<AtomicImmutAny>> {
+    registry().peek().get(name).cloned()
+}
+
+/// Loads the value of the cell registered under `name` as `T`.
+///
+/// Returns `None` if no cell is registered under the name or the value is not of the type `T`.
+pub fn get<T: Any + Send + Sync>(name: &str) -> Option<Arc<T>> {
+    registry().peek().get(name).and_then(|cell| cell.load_as())
+}
+
+/// Returns the names of the registered cells in ascending order.
+pub fn names() -> Vec<String> {
+    registry().peek().keys().cloned().collect()
+}
+
+/// Calls `f` with the name and the current value of each registered cell in ascending order of the names.
+///
+/// The set of the cells is a consistent snapshot taken before the first call.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use atomic_immut::{registry, AtomicImmutAny};
+///
+/// registry::insert("example.each", Arc::new(AtomicImmutAny::new(1)));
+///
+/// let mut found = false;
+/// registry::for_each(|name, value| {
+///     if name == "example.each" {
+///         found = value.is::<i32>();
+///     }
+/// });
+/// assert!(found);
+/// ```
+pub fn for_each<F>(mut f: F)
+where
+    F: FnMut(&str, &AnyValue),
+{
+    for (name, cell) in registry().load().iter() {
+        f(name, &*cell.load());
+    }
+}
+
+/// Formats the registered values of the type `T`, one `name = value` per line.
+///
+/// The values of other types are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use atomic_immut::{registry, AtomicImmutAny};
+///
+/// registry::insert("example.dump.a", Arc::new(AtomicImmutAny::new(1u8)));
+/// registry::insert("example.dump.b", Arc::new(AtomicImmutAny::new("b")));
+///
+/// let dump = registry::dump::<u8>();
+/// assert!(dump.contains("example.dump.a = 1\n"));
+/// assert!(!dump.contains("example.dump.b"));
+/// ```
+pub fn dump<T: Any + fmt::Debug>() -> String {
+    let mut s = String::new();
+    for_each(|name, value| {
+        if let Some(value) = value.downcast_ref::<T>() {
+            let _ = writeln!(s, "{} = {:?}", name, value);
+        }
+    });
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn registry_works() {
+        let a = Arc::new(AtomicImmutAny::new(1usize));
+        assert!(insert("test.registry.a", Arc::clone(&a)).is_none());
+
+        let handles = (0..4)
+            .map(|i| {
+                thread::spawn(move || {
+                    let name = format!("test.registry.{}", i);
+                    insert(name.as_str(), Arc::new(AtomicImmutAny::new(i)));
+                    cell("test.registry.a")
+                        .unwrap()
+                        .update_as(|v: &usize| v + 1)
+                        .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(get::<usize>("test.registry.a").map(|v| *v), Some(5));
+        for i in 0..4 {
+            let name = format!("test.registry.{}", i);
+            assert_eq!(get::<i32>(&name).map(|v| *v), Some(i));
+        }
+
+        let old = insert("test.registry.a", Arc::new(AtomicImmutAny::new(0usize))).unwrap();
+        assert!(Arc::ptr_eq(&old, &a));
+        assert!(remove("test.registry.a").is_some());
+        assert!(remove("test.registry.a").is_none());
+        assert!(!names().contains(&"test.registry.a".to_owned()));
+    }
+}
@@ -0,0 +1,401 @@
+//! Hot reloading of values from files and other sources.
+//!
+//! This module is available when the `reload` feature is enabled.
+//! On Unix, the `signal` feature additionally enables `on_sighup`.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::fs;
+//! use std::time::Duration;
+//! use atomic_immut::reload::{FileReloader, ParseError};
+//!
+//! let path = std::env::temp_dir()
+//!     .join(format!("atomic_immut_reload_doc_{}.txt", std::process::id()));
+//! fs::write(&path, "10").unwrap();
+//!
+//! let parse = |bytes: &[u8]| -> Result<u32, ParseError> {
+//!     Ok(std::str::from_utf8(bytes)?.trim().parse()?)
+//! };
+//! let config = FileReloader::spawn(&path, parse).unwrap();
+//! assert_eq!(*config.load(), 10);
+//!
+//! fs::write(&path, "20").unwrap();
+//! let value = config.wait_until_timeout(|v| *v == 20, Duration::from_secs(10));
+//! assert_eq!(value.map(|v| *v), Some(20));
+//! # fs::remove_file(&path).unwrap();
+//! ```
+use notify::{self, RecommendedWatcher, RecursiveMode, Watcher};
+#[cfg(all(unix, feature = "signal"))]
+use signal_hook::consts::SIGHUP;
+#[cfg(all(unix, feature = "signal"))]
+use signal_hook::iterator::{Handle, Signals};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use AtomicImmut;
+
+pub use self::env::{EnvSource, EnvVars};
+pub use self::http::{HttpConditions, HttpFetch, HttpResponse, HttpSource};
+
+mod env;
+mod http;
+
+/// How often the watcher thread checks whether the `AtomicImmut` is still alive.
+const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Boxed error returned by parse functions.
+pub type ParseError = Box<dyn error::Error + Send + Sync>;
+
+/// Possible errors of reloading.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read the file.
+    Io(io::Error),
+
+    /// Failed to watch the file.
+    Watch(notify::Error),
+
+    /// Failed to parse the contents of the file.
+    Parse(ParseError),
+
+    /// Failed to fetch the payload from a remote source.
+    Fetch(ParseError),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Watch(ref e) => write!(f, "Watch error: {}", e),
+            Error::Parse(ref e) => write!(f, "Parse error: {}", e),
+            Error::Fetch(ref e) => write!(f, "Fetch error: {}", e),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Watch(ref e) => Some(e),
+            Error::Parse(ref e) => Some(&**e),
+            Error::Fetch(ref e) => Some(&**e),
+        }
+    }
+}
+impl From<io::Error> for Error {
+    fn from(f: io::Error) -> Self {
+        Error::Io(f)
+    }
+}
+impl From<notify::Error> for Error {
+    fn from(f: notify::Error) -> Self {
+        Error::Watch(f)
+    }
+}
+
+/// A builder of a background thread which watches a file
+/// and stores freshly parsed values into an `AtomicImmut`.
+///
+/// The thread terminates after all the `Arc`s of the `AtomicImmut` are dropped.
+pub struct FileReloader<C, P> {
+    path: PathBuf,
+    parse: P,
+    on_error: Box<dyn Fn(&Error) + Send>,
+    _value: ::std::marker::PhantomData<fn() -> C>,
+}
+impl<C, P> FileReloader<C, P>
+where
+    C: Send + Sync + 'static,
+    P: Fn(&[u8]) -> Result<C, ParseError> + Send + 'static,
+{
+    /// Makes a new `FileReloader` instance which watches `path` and parses it by `parse`.
+    pub fn new<T: AsRef<Path>>(path: T, parse: P) -> Self {
+        FileReloader {
+            path: path.as_ref().to_path_buf(),
+            parse,
+            on_error: Box::new(|_| {}),
+            _value: ::std::marker::PhantomData,
+        }
+    }
+
+    /// Shorthand of `FileReloader::new(path, parse).spawn()`.
+    pub fn spawn<T: AsRef<Path>>(path: T, parse: P) -> Result<Arc<AtomicImmut<C>>, Error> {
+        Self::new(path, parse).spawn_watcher()
+    }
+
+    /// Sets the hook which is called when reloading fails.
+    ///
+    /// On failure, the `AtomicImmut` keeps the last successfully parsed value.
+    /// The default hook does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::sync::mpsc;
+    /// use std::time::Duration;
+    /// use atomic_immut::reload::{Error, FileReloader, ParseError};
+    ///
+    /// let path = std::env::temp_dir()
+    ///     .join(format!("atomic_immut_reload_error_doc_{}.txt", std::process::id()));
+    /// fs::write(&path, "10").unwrap();
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let parse = |bytes: &[u8]| -> Result<u32, ParseError> {
+    ///     Ok(std::str::from_utf8(bytes)?.trim().parse()?)
+    /// };
+    /// let config = FileReloader::new(&path, parse)
+    ///     .on_error(move |e| {
+    ///         let _ = tx.send(matches!(e, Error::Parse(_)));
+    ///     })
+    ///     .spawn_watcher()
+    ///     .unwrap();
+    ///
+    /// fs::write(&path, "foo").unwrap();
+    /// assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(true));
+    /// assert_eq!(*config.load(), 10);
+    /// # fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn on_error<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Error) + Send + 'static,
+    {
+        self.on_error = Box::new(f);
+        self
+    }
+
+    /// Loads the initial value and spawns the watcher thread.
+    ///
+    /// If the initial value cannot be loaded, an error is returned
+    /// (the error hook is not called in that case).
+    pub fn spawn_watcher(self) -> Result<Arc<AtomicImmut<C>>, Error> {
+        let initial = load(&self.path, &self.parse)?;
+        let cell = Arc::new(AtomicImmut::new(initial));
+
+        // The parent directory is watched rather than the file itself,
+        // because many editors replace files by renaming.
+        let file_name = self.path.file_name().map(|n| n.to_owned()).ok_or_else(|| {
+            Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Not a file path",
+            ))
+        })?;
+        let dir = match self.path.parent() {
+            Some(dir) if dir != Path::new("") => dir.canonicalize()?,
+            _ => PathBuf::from(".").canonicalize()?,
+        };
+        let target = dir.join(file_name);
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = RecommendedWatcher::new(tx, notify::Config::default())?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+        let weak = Arc::downgrade(&cell);
+        thread::Builder::new()
+            .name("atomic_immut-reload".to_owned())
+            .spawn(move || {
+                let _watcher = watcher;
+                let FileReloader {
+                    parse, on_error, ..
+                } = self;
+                loop {
+                    match rx.recv_timeout(LIVENESS_CHECK_INTERVAL) {
+                        Ok(Ok(event)) => {
+                            if !is_relevant(&event, &target) {
+                                continue;
+                            }
+                            let result = load(&target, &parse);
+                            let cell = match weak.upgrade() {
+                                Some(cell) => cell,
+                                None => break,
+                            };
+                            match result {
+                                Ok(value) => cell.store(value),
+                                Err(e) => on_error(&e),
+                            }
+                        }
+                        Ok(Err(e)) => on_error(&Error::Watch(e)),
+                        Err(RecvTimeoutError::Timeout) => {
+                            if Weak::strong_count(&weak) == 0 {
+                                break;
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })?;
+        Ok(cell)
+    }
+}
+impl<C, P> fmt::Debug for FileReloader<C, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileReloader")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Spawns a thread which reloads the value of `cell` by calling `loader` every time SIGHUP arrives.
+///
+/// If `loader` returns `None`, `cell` is left untouched
+/// (reporting the failure is the responsibility of `loader`).
+/// The thread terminates when the returned handle is closed
+/// or after all the `Arc`s of `cell` are dropped (and the next signal arrives).
+///
+/// This function is available on Unix when the `signal` feature is enabled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::reload;
+///
+/// let config = Arc::new(AtomicImmut::new(String::new()));
+/// let handle = reload::on_sighup(&config, || std::fs::read_to_string("app.conf").ok()).unwrap();
+///
+/// // `kill -HUP <pid>` reloads `app.conf`
+///
+/// handle.close();
+/// ```
+#[cfg(all(unix, feature = "signal"))]
+pub fn on_sighup<C, F>(cell: &Arc<AtomicImmut<C>>, mut loader: F) -> io::Result<SighupHandle>
+where
+    C: Send + Sync + 'static,
+    F: FnMut() -> Option<C> + Send + 'static,
+{
+    let mut signals = Signals::new([SIGHUP])?;
+    let handle = signals.handle();
+    let weak = Arc::downgrade(cell);
+    thread::Builder::new()
+        .name("atomic_immut-sighup".to_owned())
+        .spawn(move || {
+            for _ in signals.forever() {
+                let cell = match weak.upgrade() {
+                    Some(cell) => cell,
+                    None => break,
+                };
+                if let Some(value) = loader() {
+                    cell.store(value);
+                }
+            }
+        })?;
+    Ok(SighupHandle(handle))
+}
+
+/// A handle of the thread spawned by `on_sighup`.
+///
+/// Note that dropping this handle does not terminate the thread.
+#[cfg(all(unix, feature = "signal"))]
+#[derive(Debug, Clone)]
+pub struct SighupHandle(Handle);
+#[cfg(all(unix, feature = "signal"))]
+impl SighupHandle {
+    /// Terminates the reloading thread and unregisters the signal handler.
+    pub fn close(&self) {
+        self.0.close();
+    }
+
+    /// Returns `true` if this handle has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+fn load<C, P>(path: &Path, parse: &P) -> Result<C, Error>
+where
+    P: Fn(&[u8]) -> Result<C, ParseError>,
+{
+    let bytes = fs::read(path)?;
+    parse(&bytes).map_err(Error::Parse)
+}
+
+fn is_relevant(event: &notify::Event, target: &Path) -> bool {
+    (event.kind.is_create() || event.kind.is_modify()) && event.paths.iter().any(|p| p == target)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, Ordering::SeqCst);
+        ::std::env::temp_dir().join(format!(
+            "atomic_immut_reload_{}_{}_{}",
+            name,
+            ::std::process::id(),
+            n
+        ))
+    }
+
+    fn parse(bytes: &[u8]) -> Result<u32, ParseError> {
+        Ok(::std::str::from_utf8(bytes)?.trim().parse()?)
+    }
+
+    #[test]
+    fn reload_works() {
+        let path = temp_path("works");
+        fs::write(&path, "1").unwrap();
+        let cell = FileReloader::spawn(&path, parse).unwrap();
+        assert_eq!(*cell.load(), 1);
+
+        // Replaces by renaming
+        let tmp = temp_path("works_tmp");
+        fs::write(&tmp, "2").unwrap();
+        fs::rename(&tmp, &path).unwrap();
+        let value = cell.wait_until_timeout(|v| *v == 2, Duration::from_secs(10));
+        assert_eq!(value.map(|v| *v), Some(2));
+
+        fs::write(&path, "3").unwrap();
+        let value = cell.wait_until_timeout(|v| *v == 3, Duration::from_secs(10));
+        assert_eq!(value.map(|v| *v), Some(3));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "signal"))]
+    #[test]
+    fn on_sighup_works() {
+        let cell = Arc::new(AtomicImmut::new(0));
+        let count = Arc::new(AtomicUsize::new(0));
+        let handle = {
+            let count = Arc::clone(&count);
+            on_sighup(&cell, move || {
+                Some(count.fetch_add(1, Ordering::SeqCst) + 1)
+            })
+            .unwrap()
+        };
+
+        ::signal_hook::low_level::raise(SIGHUP).unwrap();
+        let value = cell.wait_until_timeout(|v| *v == 1, Duration::from_secs(10));
+        assert_eq!(value.map(|v| *v), Some(1));
+
+        handle.close();
+        assert!(handle.is_closed());
+    }
+
+    #[test]
+    fn initial_error_works() {
+        let path = temp_path("initial_error");
+        assert!(matches!(
+            FileReloader::spawn(&path, parse),
+            Err(Error::Io(_))
+        ));
+
+        fs::write(&path, "foo").unwrap();
+        assert!(matches!(
+            FileReloader::spawn(&path, parse),
+            Err(Error::Parse(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+#[cfg(all(unix, feature = "signal"))]
+use std::io;
+#[cfg(all(unix, feature = "signal"))]
+use std::sync::Arc;
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(all(unix, feature = "signal"))]
+use super::SighupHandle;
+use super::{Error, ParseError};
+use AtomicImmut;
+
+/// The environment variables with a prefix, keyed by their names without the prefix.
+pub type EnvVars = BTreeMap<String, String>;
+
+/// A source which makes values from the environment variables with a prefix.
+///
+/// Variables whose names or values are not valid Unicode are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::reload::{EnvSource, EnvVars, ParseError};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Settings {
+///     workers: usize,
+/// }
+///
+/// let parse = |vars: &EnvVars| -> Result<Settings, ParseError> {
+///     let workers = vars.get("WORKERS").map_or(Ok(1), |v| v.parse())?;
+///     Ok(Settings { workers })
+/// };
+/// let source = EnvSource::new("ENV_SOURCE_DOC_", parse);
+///
+/// std::env::set_var("ENV_SOURCE_DOC_WORKERS", "4");
+/// let settings = AtomicImmut::new(source.load().unwrap());
+/// assert_eq!(settings.load().workers, 4);
+///
+/// std::env::set_var("ENV_SOURCE_DOC_WORKERS", "8");
+/// assert!(source.refresh(&settings).unwrap());
+/// assert!(!source.refresh(&settings).unwrap());
+/// assert_eq!(settings.load().workers, 8);
+/// ```
+pub struct EnvSource<P> {
+    prefix: String,
+    parse: P,
+    last_vars: Mutex<Option<EnvVars>>,
+    on_error: Box<dyn Fn(&Error) + Send + Sync>,
+}
+impl<P> EnvSource<P> {
+    /// Makes a new `EnvSource` instance which parses the variables prefixed by `prefix` by `parse`.
+    pub fn new<S: Into<String>>(prefix: S, parse: P) -> Self {
+        EnvSource {
+            prefix: prefix.into(),
+            parse,
+            last_vars: Mutex::new(None),
+            on_error: Box::new(|_| {}),
+        }
+    }
+
+    /// Sets the hook which is called when reloading fails in the thread spawned by `on_sighup`.
+    ///
+    /// The default hook does nothing.
+    pub fn on_error<H>(mut self, f: H) -> Self
+    where
+        H: Fn(&Error) + Send + Sync + 'static,
+    {
+        self.on_error = Box::new(f);
+        self
+    }
+
+    /// Returns the current variables with the prefix.
+    pub fn vars(&self) -> EnvVars {
+        env::vars_os()
+            .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+            .filter_map(|(k, v)| Some((k.strip_prefix(&self.prefix[..])?.to_owned(), v)))
+            .collect()
+    }
+
+    /// Makes a value from the current variables.
+    pub fn load<C>(&self) -> Result<C, Error>
+    where
+        P: Fn(&EnvVars) -> Result<C, ParseError>,
+    {
+        (self.parse)(&self.vars()).map_err(Error::Parse)
+    }
+
+    /// Stores a value made from the current variables into `cell` if the variables have changed
+    /// since the last refresh.
+    ///
+    /// Returns `true` if a value has been stored.
+    pub fn refresh<C>(&self, cell: &AtomicImmut<C>) -> Result<bool, Error>
+    where
+        P: Fn(&EnvVars) -> Result<C, ParseError>,
+    {
+        let vars = self.vars();
+        let mut last_vars = self
+            .last_vars
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if last_vars.as_ref() == Some(&vars) {
+            return Ok(false);
+        }
+        let value = (self.parse)(&vars).map_err(Error::Parse)?;
+        cell.store(value);
+        *last_vars = Some(vars);
+        Ok(true)
+    }
+
+    /// Refreshes `cell` every time SIGHUP arrives (see `reload::on_sighup`).
+    ///
+    /// This method is available on Unix when the `signal` feature is enabled.
+    #[cfg(all(unix, feature = "signal"))]
+    pub fn on_sighup<C>(self, cell: &Arc<AtomicImmut<C>>) -> io::Result<SighupHandle>
+    where
+        C: Send + Sync + 'static,
+        P: Fn(&EnvVars) -> Result<C, ParseError> + Send + 'static,
+    {
+        super::on_sighup(cell, move || match self.load() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                (self.on_error)(&e);
+                None
+            }
+        })
+    }
+}
+impl<P> fmt::Debug for EnvSource<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EnvSource")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vars_are_filtered_by_prefix() {
+        env::set_var("ATOMIC_IMMUT_ENV_TEST_A", "1");
+        env::set_var("ATOMIC_IMMUT_ENV_TEST_B", "2");
+        env::set_var("ATOMIC_IMMUT_ENV_TESTB", "3");
+        let source = EnvSource::new("ATOMIC_IMMUT_ENV_TEST_", |vars: &EnvVars| {
+            Ok(vars
+                .values()
+                .map(|v| v.parse::<u32>())
+                .sum::<Result<u32, _>>()?)
+        });
+        assert_eq!(source.vars().keys().collect::<Vec<_>>(), ["A", "B"]);
+        assert_eq!(source.load().ok(), Some(3));
+
+        env::set_var("ATOMIC_IMMUT_ENV_TEST_B", "foo");
+        let cell = AtomicImmut::new(0);
+        assert!(matches!(source.refresh(&cell), Err(Error::Parse(_))));
+        assert_eq!(*cell.load(), 0);
+    }
+}
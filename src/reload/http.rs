@@ -0,0 +1,239 @@
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::{Error, ParseError};
+use AtomicImmut;
+
+/// The conditional request headers sent by `HttpSource`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct HttpConditions {
+    /// The value of the `If-None-Match` header (the `ETag` of the last response).
+    pub if_none_match: Option<String>,
+
+    /// The value of the `If-Modified-Since` header (the `Last-Modified` of the last response).
+    pub if_modified_since: Option<String>,
+}
+
+/// A response to a request of `HttpSource`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HttpResponse {
+    /// `304 Not Modified`.
+    NotModified,
+
+    /// A successful response with the payload and its `ETag` and `Last-Modified` headers.
+    Ok {
+        /// The payload.
+        body: Vec<u8>,
+
+        /// The value of the `ETag` header.
+        etag: Option<String>,
+
+        /// The value of the `Last-Modified` header.
+        last_modified: Option<String>,
+    },
+}
+
+/// An HTTP client used by `HttpSource`.
+///
+/// This is implemented for closures, so any HTTP client can be plugged in.
+pub trait HttpFetch {
+    /// Sends a GET request with the given conditional headers.
+    fn fetch(&mut self, conditions: &HttpConditions) -> Result<HttpResponse, ParseError>;
+}
+impl<F> HttpFetch for F
+where
+    F: FnMut(&HttpConditions) -> Result<HttpResponse, ParseError>,
+{
+    fn fetch(&mut self, conditions: &HttpConditions) -> Result<HttpResponse, ParseError> {
+        self(conditions)
+    }
+}
+
+/// A source which polls an HTTP endpoint and stores freshly parsed values into an `AtomicImmut`.
+///
+/// Requests are conditional (`If-None-Match` and `If-Modified-Since`),
+/// and the value is stored only if the payload has actually changed.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::reload::{HttpConditions, HttpResponse, HttpSource, ParseError};
+///
+/// // A fake client; use a real HTTP client to send the conditional headers.
+/// let fetch = |c: &HttpConditions| -> Result<HttpResponse, ParseError> {
+///     if c.if_none_match.as_ref().map(|s| s.as_str()) == Some("v1") {
+///         Ok(HttpResponse::NotModified)
+///     } else {
+///         Ok(HttpResponse::Ok {
+///             body: b"10".to_vec(),
+///             etag: Some("v1".to_owned()),
+///             last_modified: None,
+///         })
+///     }
+/// };
+/// let parse = |bytes: &[u8]| -> Result<u32, ParseError> {
+///     Ok(std::str::from_utf8(bytes)?.trim().parse()?)
+/// };
+///
+/// let config = AtomicImmut::new(0);
+/// let mut source = HttpSource::new(fetch, parse);
+/// assert_eq!(source.poll(&config).unwrap(), true);
+/// assert_eq!(source.poll(&config).unwrap(), false);
+/// assert_eq!(*config.load(), 10);
+/// ```
+pub struct HttpSource<F, P> {
+    fetch: F,
+    parse: P,
+    conditions: HttpConditions,
+    last_body: Option<Vec<u8>>,
+    on_error: Box<dyn Fn(&Error) + Send>,
+}
+impl<F, P> HttpSource<F, P>
+where
+    F: HttpFetch,
+{
+    /// Makes a new `HttpSource` instance which fetches payloads by `fetch` and parses them by `parse`.
+    pub fn new(fetch: F, parse: P) -> Self {
+        HttpSource {
+            fetch,
+            parse,
+            conditions: HttpConditions::default(),
+            last_body: None,
+            on_error: Box::new(|_| {}),
+        }
+    }
+
+    /// Sets the hook which is called when polling fails in the thread spawned by `spawn`.
+    ///
+    /// On failure, the `AtomicImmut` keeps the last successfully parsed value.
+    /// The default hook does nothing.
+    pub fn on_error<H>(mut self, f: H) -> Self
+    where
+        H: Fn(&Error) + Send + 'static,
+    {
+        self.on_error = Box::new(f);
+        self
+    }
+
+    /// Fetches the payload and stores the parsed value into `cell` if the payload has changed.
+    ///
+    /// Returns `true` if a value has been stored.
+    pub fn poll<C>(&mut self, cell: &AtomicImmut<C>) -> Result<bool, Error>
+    where
+        P: Fn(&[u8]) -> Result<C, ParseError>,
+    {
+        let (body, etag, last_modified) = match self.fetch.fetch(&self.conditions) {
+            Ok(HttpResponse::NotModified) => return Ok(false),
+            Ok(HttpResponse::Ok {
+                body,
+                etag,
+                last_modified,
+            }) => (body, etag, last_modified),
+            Err(e) => return Err(Error::Fetch(e)),
+        };
+        if self.last_body.as_ref() == Some(&body) {
+            // Some servers do not support conditional requests.
+            return Ok(false);
+        }
+        let value = (self.parse)(&body).map_err(Error::Parse)?;
+        cell.store(value);
+        self.conditions = HttpConditions {
+            if_none_match: etag,
+            if_modified_since: last_modified,
+        };
+        self.last_body = Some(body);
+        Ok(true)
+    }
+
+    /// Spawns a thread which polls every `period`.
+    ///
+    /// The thread terminates after all the `Arc`s of `cell` are dropped.
+    pub fn spawn<C>(mut self, cell: &Arc<AtomicImmut<C>>, period: Duration) -> io::Result<()>
+    where
+        C: Send + Sync + 'static,
+        F: Send + 'static,
+        P: Fn(&[u8]) -> Result<C, ParseError> + Send + 'static,
+    {
+        let weak = Arc::downgrade(cell);
+        thread::Builder::new()
+            .name("atomic_immut-http".to_owned())
+            .spawn(move || {
+                while let Some(cell) = weak.upgrade() {
+                    if let Err(e) = self.poll(&cell) {
+                        (self.on_error)(&e);
+                    }
+                    drop(cell);
+                    thread::sleep(period);
+                }
+            })?;
+        Ok(())
+    }
+}
+impl<F, P> fmt::Debug for HttpSource<F, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpSource")
+            .field("conditions", &self.conditions)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Result<u32, ParseError> {
+        Ok(::std::str::from_utf8(bytes)?.trim().parse()?)
+    }
+
+    #[test]
+    fn unchanged_payload_is_not_stored() {
+        let bodies = ["1", "1", "2", "foo"];
+        let mut i = 0;
+        let fetch = move |c: &HttpConditions| -> Result<HttpResponse, ParseError> {
+            assert_eq!(c.if_modified_since.is_some(), i > 0);
+            let body = bodies[i].as_bytes().to_vec();
+            i += 1;
+            Ok(HttpResponse::Ok {
+                body,
+                etag: None,
+                last_modified: Some("Thu, 01 Jan 1970 00:00:00 GMT".to_owned()),
+            })
+        };
+        let cell = AtomicImmut::new(0);
+        let mut source = HttpSource::new(fetch, parse);
+        assert!(source.poll(&cell).unwrap());
+        assert!(!source.poll(&cell).unwrap());
+        assert!(source.poll(&cell).unwrap());
+        assert!(matches!(source.poll(&cell), Err(Error::Parse(_))));
+        assert_eq!(*cell.load(), 2);
+        assert_eq!(cell.version(), 2);
+    }
+
+    #[test]
+    fn spawn_works() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let fetch = {
+            let count = Arc::clone(&count);
+            move |_: &HttpConditions| -> Result<HttpResponse, ParseError> {
+                let n = count.fetch_add(1, Ordering::SeqCst);
+                Ok(HttpResponse::Ok {
+                    body: n.to_string().into_bytes(),
+                    etag: None,
+                    last_modified: None,
+                })
+            }
+        };
+        let cell = Arc::new(AtomicImmut::new(0));
+        HttpSource::new(fetch, parse)
+            .spawn(&cell, Duration::from_millis(1))
+            .unwrap();
+        let value = cell.wait_until_timeout(|v| *v >= 3, Duration::from_secs(10));
+        assert!(value.is_some());
+    }
+}
@@ -0,0 +1,43 @@
+use core::fmt;
+use primitive::Arc;
+
+use backend::{Backend, DefaultBackend};
+use AtomicImmut;
+
+/// A guard which restores the overridden value of an `AtomicImmut` when dropped.
+///
+/// This is created by the `AtomicImmut::override_scoped` method.
+#[must_use = "the value is restored as soon as the guard is dropped"]
+pub struct OverrideGuard<'a, T: ?Sized + 'a, B: 'a + Backend = DefaultBackend> {
+    cell: &'a AtomicImmut<T, B>,
+    previous: Option<Arc<T>>,
+}
+impl<'a, T: ?Sized, B: Backend> OverrideGuard<'a, T, B> {
+    pub(crate) fn new(cell: &'a AtomicImmut<T, B>, previous: Arc<T>) -> Self {
+        OverrideGuard {
+            cell,
+            previous: Some(previous),
+        }
+    }
+
+    /// Returns the value which will be restored.
+    pub fn previous(&self) -> &Arc<T> {
+        self.previous.as_ref().expect("Never fails")
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Drop for OverrideGuard<'a, T, B> {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            // The previous value has been stored once, so it needs no validation
+            // (and a panic here would abort an unwinding thread).
+            self.cell.swap_arc_unchecked(previous);
+        }
+    }
+}
+impl<'a, T: ?Sized + fmt::Debug, B: Backend> fmt::Debug for OverrideGuard<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverrideGuard")
+            .field("previous", self.previous())
+            .finish()
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use backend::Backend;
+use AtomicImmut;
+
+/// Serializes the current value.
+impl<T, B> Serialize for AtomicImmut<T, B>
+where
+    T: ?Sized + Serialize,
+    B: Backend,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (*self.load()).serialize(serializer)
+    }
+}
+
+/// Deserializes a value and makes a new `AtomicImmut` instance which has it.
+impl<'de, T, B> Deserialize<'de> for AtomicImmut<T, B>
+where
+    T: Deserialize<'de>,
+    B: Backend + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(|value| AtomicImmut::with_backend(value, B::default()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json;
+    use std::collections::BTreeMap;
+
+    use AtomicImmut;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct State {
+        name: String,
+        config: AtomicImmut<BTreeMap<String, u32>>,
+    }
+
+    #[test]
+    fn serde_works() {
+        let state: State = serde_json::from_str(r#"{"name":"foo","config":{"a":1}}"#).unwrap();
+        assert_eq!(state.config.load().get("a"), Some(&1));
+
+        state.config.update(|c| {
+            let mut c = c.clone();
+            c.insert("b".to_owned(), 2);
+            c
+        });
+        assert_eq!(
+            serde_json::to_string(&state).unwrap(),
+            r#"{"name":"foo","config":{"a":1,"b":2}}"#
+        );
+    }
+}
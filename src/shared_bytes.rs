@@ -0,0 +1,180 @@
+use bytes::{Bytes, BytesMut};
+use core::fmt;
+
+use AtomicImmut;
+
+/// A thread-safe pointer for `Bytes` (available when the `bytes` feature is enabled).
+///
+/// `AtomicImmut<Bytes>::load` would return an `Arc<Bytes>`,
+/// whose reference count is maintained in addition to the one of the `Bytes` itself.
+/// Instead, `load` of this type returns a clone of the `Bytes`,
+/// which shares the underlying buffer without copying it.
+/// So readers only touch the reference count of the buffer.
+///
+/// Each stored `Bytes` is still wrapped in an `Arc` internally:
+/// `Bytes` is four words, while the pointer swapped by `AtomicImmut` must be a single word.
+/// The `Arc` is allocated once per store and is never cloned by `load`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate atomic_immut;
+/// # extern crate bytes;
+/// use atomic_immut::AtomicImmutBytes;
+/// use bytes::Bytes;
+///
+/// # fn main() {
+/// let routes = AtomicImmutBytes::new(Bytes::from_static(b"v1"));
+/// let snapshot = routes.load();
+///
+/// let diff = routes.store_vec(b"v2:extended".to_vec());
+/// assert_eq!(diff, 9);
+/// assert_eq!(routes.load(), "v2:extended");
+/// assert_eq!(snapshot, "v1");
+/// # }
+/// ```
+pub struct AtomicImmutBytes {
+    inner: AtomicImmut<Bytes>,
+}
+impl AtomicImmutBytes {
+    /// Makes a new `AtomicImmutBytes` instance.
+    pub fn new(value: Bytes) -> Self {
+        AtomicImmutBytes {
+            inner: AtomicImmut::new(value),
+        }
+    }
+
+    /// Loads the bytes from this pointer.
+    ///
+    /// Like `AtomicImmut::peek`, writers of this pointer wait until the clone is finished,
+    /// which only increments the reference count of the buffer.
+    pub fn load(&self) -> Bytes {
+        Bytes::clone(&self.inner.peek())
+    }
+
+    /// Returns the length of the current bytes.
+    pub fn len(&self) -> usize {
+        self.inner.peek().len()
+    }
+
+    /// Returns `true` if the current bytes are empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stores bytes into this pointer.
+    ///
+    /// Returns the length of the new bytes minus the length of the old ones,
+    /// which is convenient for maintaining metrics such as the total size of published payloads.
+    pub fn store(&self, value: Bytes) -> isize {
+        let new_len = value.len();
+        let old = self.inner.swap(value);
+        // Neither length exceeds `isize::MAX`, so the difference never overflows.
+        new_len as isize - old.len() as isize
+    }
+
+    /// Stores the bytes of `value` into this pointer without copying them.
+    ///
+    /// Returns the difference of the lengths like `store`.
+    pub fn store_vec(&self, value: Vec<u8>) -> isize {
+        self.store(Bytes::from(value))
+    }
+
+    /// Freezes `value` and stores it into this pointer without copying the bytes.
+    ///
+    /// Returns the difference of the lengths like `store`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate atomic_immut;
+    /// # extern crate bytes;
+    /// use atomic_immut::AtomicImmutBytes;
+    /// use bytes::{BufMut, BytesMut};
+    ///
+    /// # fn main() {
+    /// let payload = AtomicImmutBytes::default();
+    /// let mut buf = BytesMut::with_capacity(16);
+    /// buf.put_slice(b"hello");
+    /// assert_eq!(payload.store_bytes_mut(buf), 5);
+    /// assert_eq!(payload.store_vec(Vec::new()), -5);
+    /// assert!(payload.is_empty());
+    /// # }
+    /// ```
+    pub fn store_bytes_mut(&self, value: BytesMut) -> isize {
+        self.store(value.freeze())
+    }
+
+    /// Stores bytes into this pointer, returning the old bytes.
+    pub fn swap(&self, value: Bytes) -> Bytes {
+        Bytes::clone(&self.inner.swap(value))
+    }
+
+    /// Returns the underlying `AtomicImmut` (e.g., to subscribe to stores).
+    pub fn as_immut(&self) -> &AtomicImmut<Bytes> {
+        &self.inner
+    }
+}
+impl Default for AtomicImmutBytes {
+    fn default() -> Self {
+        Self::new(Bytes::new())
+    }
+}
+impl From<Bytes> for AtomicImmutBytes {
+    fn from(value: Bytes) -> Self {
+        Self::new(value)
+    }
+}
+impl fmt::Debug for AtomicImmutBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicImmutBytes")
+            .field("value", &*self.inner.peek())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn it_works() {
+        let v = AtomicImmutBytes::new(Bytes::from_static(b"foo"));
+        let snapshot = v.load();
+        assert_eq!(v.len(), 3);
+
+        let buf = b"barbaz".to_vec();
+        let ptr = buf.as_ptr();
+        assert_eq!(v.store_vec(buf), 3);
+        assert_eq!(v.load().as_ptr(), ptr); // Not copied
+        assert_eq!(v.swap(Bytes::new()), "barbaz");
+        assert!(v.is_empty());
+        assert_eq!(snapshot, "foo");
+    }
+
+    #[test]
+    fn concurrent_loads_work() {
+        let v = Arc::new(AtomicImmutBytes::default());
+        let readers = (0..4)
+            .map(|_| {
+                let v = Arc::clone(&v);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let bytes = v.load();
+                        assert!(bytes.iter().all(|&b| b == bytes.len() as u8));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut total = 0;
+        for i in 0..100 {
+            total += v.store_vec(vec![i % 10; (i % 10) as usize]);
+        }
+        for r in readers {
+            r.join().unwrap();
+        }
+        assert_eq!(total, v.len() as isize);
+    }
+}
@@ -0,0 +1,347 @@
+//! Cells shared between processes over memory-mapped files.
+//!
+//! This module is available when the `shm` feature is enabled.
+//!
+//! A `SharedImmut` maps a file (e.g., under `/dev/shm`) laid out as a small header
+//! followed by a fixed-capacity payload area holding the encoded value.
+//! Writers (in any process) are serialized by a spin lock in the header,
+//! and readers validate their copies of the payload by a sequence number like a seqlock,
+//! so they never block writers.
+//! Each `SharedImmut` caches the decoded value until the sequence number changes,
+//! so loading an unchanged value costs only an atomic load.
+//!
+//! # Examples
+//!
+//! ```
+//! use atomic_immut::codec::CodecError;
+//! use atomic_immut::shm::SharedImmut;
+//!
+//! let path = std::env::temp_dir()
+//!     .join(format!("atomic_immut_shm_doc_{}", std::process::id()));
+//! let codec = (
+//!     |v: &String| -> Result<Vec<u8>, CodecError> { Ok(v.clone().into_bytes()) },
+//!     |b: &[u8]| -> Result<String, CodecError> { Ok(String::from_utf8(b.to_vec())?) },
+//! );
+//!
+//! let table = SharedImmut::create(&path, 4096, codec, "v1".to_owned()).unwrap();
+//!
+//! // In another process
+//! let reader = SharedImmut::open(&path, codec).unwrap();
+//! assert_eq!(*reader.load().unwrap(), "v1");
+//!
+//! table.store("v2".to_owned()).unwrap();
+//! assert_eq!(*reader.load().unwrap(), "v2");
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+use memmap2::MmapRaw;
+use std::error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{self, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use backend::Backoff;
+use codec::{Codec, CodecError};
+use AtomicImmut;
+
+/// The magic number at the head of shared files.
+const MAGIC: &[u8; 8] = b"AIMSHM01";
+
+/// The size of the header (the magic number, the sequence number, the writer lock and the length).
+const HEADER_LEN: usize = 32;
+
+/// Possible errors of shared cells.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to create, open or map the shared file.
+    Io(io::Error),
+
+    /// Failed to encode or decode the value.
+    Codec(CodecError),
+
+    /// The encoded value does not fit in the payload area.
+    TooLarge {
+        /// The length of the encoded value.
+        len: usize,
+
+        /// The capacity of the payload area.
+        capacity: usize,
+    },
+
+    /// The shared file is not a valid one.
+    Corrupted(&'static str),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Codec(ref e) => write!(f, "Codec error: {}", e),
+            Error::TooLarge { len, capacity } => write!(
+                f,
+                "Too large value: {} bytes (capacity: {} bytes)",
+                len, capacity
+            ),
+            Error::Corrupted(reason) => write!(f, "Corrupted shared file: {}", reason),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Codec(ref e) => Some(&**e),
+            Error::TooLarge { .. } | Error::Corrupted(_) => None,
+        }
+    }
+}
+impl From<io::Error> for Error {
+    fn from(f: io::Error) -> Self {
+        Error::Io(f)
+    }
+}
+
+/// A cell whose value is shared between processes over a memory-mapped file.
+///
+/// Values are exchanged in the encoded form, so `T` needs a `Codec`
+/// (e.g., `JsonCodec`, `BincodeCodec`, or a plain byte copy for POD types).
+///
+/// Note that a process which crashes while storing a value leaves the writer lock held,
+/// and the other writers wait forever (readers keep reading the last complete value).
+pub struct SharedImmut<T, C> {
+    map: MmapRaw,
+    codec: C,
+    cache: AtomicImmut<Option<(u64, Arc<T>)>>,
+}
+impl<T, C: Codec<T>> SharedImmut<T, C> {
+    /// Creates the shared file at `path` with the payload area of `capacity` bytes,
+    /// and stores `initial` into it.
+    ///
+    /// An existing file is overwritten,
+    /// so this must be called before the other processes open the file.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        capacity: usize,
+        codec: C,
+        initial: T,
+    ) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((HEADER_LEN + capacity) as u64)?;
+        let map = MmapRaw::map_raw(&file)?;
+        unsafe {
+            ptr::copy_nonoverlapping(MAGIC.as_ptr(), map.as_mut_ptr(), MAGIC.len());
+        }
+
+        let this = SharedImmut {
+            map,
+            codec,
+            cache: AtomicImmut::new(None),
+        };
+        this.store(initial)?;
+        Ok(this)
+    }
+
+    /// Opens the shared file at `path` created by `create`.
+    pub fn open<P: AsRef<Path>>(path: P, codec: C) -> Result<Self, Error> {
+        let file = File::options().read(true).write(true).open(path)?;
+        let map = MmapRaw::map_raw(&file)?;
+        if map.len() < HEADER_LEN {
+            return Err(Error::Corrupted("too short"));
+        }
+        let magic = unsafe { ptr::read_volatile(map.as_ptr().cast::<[u8; 8]>()) };
+        if &magic != MAGIC {
+            return Err(Error::Corrupted("unknown magic number"));
+        }
+        Ok(SharedImmut {
+            map,
+            codec,
+            cache: AtomicImmut::new(None),
+        })
+    }
+
+    /// Loads the value from the shared file.
+    ///
+    /// The decoded value is cached until another value is stored.
+    pub fn load(&self) -> Result<Arc<T>, Error> {
+        if let Some((seq, ref value)) = *self.cache.peek() {
+            if self.seq().load(Ordering::Acquire) == seq {
+                return Ok(Arc::clone(value));
+            }
+        }
+
+        let (seq, bytes) = self.read_payload()?;
+        let value = Arc::new(self.codec.decode(&bytes).map_err(Error::Codec)?);
+        self.cache.store(Some((seq, Arc::clone(&value))));
+        Ok(value)
+    }
+
+    /// Stores `value` into the shared file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` cannot be encoded or does not fit in the payload area.
+    /// In that case, the shared value is not changed.
+    pub fn store(&self, value: T) -> Result<(), Error> {
+        let bytes = self.codec.encode(&value).map_err(Error::Codec)?;
+        if bytes.len() > self.capacity() {
+            return Err(Error::TooLarge {
+                len: bytes.len(),
+                capacity: self.capacity(),
+            });
+        }
+
+        let lock = self.header(16);
+        Backoff::new().wait_until(|| {
+            lock.compare_exchange_weak(0, 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        });
+
+        // Makes the sequence number odd while the payload is being written.
+        let seq = self.seq().fetch_add(1, Ordering::Relaxed) + 2;
+        atomic::fence(Ordering::Release);
+        self.header(24).store(bytes.len() as u64, Ordering::Relaxed);
+        unsafe {
+            copy_volatile(bytes.as_ptr(), self.payload(), bytes.len());
+        }
+        self.seq().store(seq, Ordering::Release);
+        lock.store(0, Ordering::Release);
+
+        self.cache.store(Some((seq, Arc::new(value))));
+        Ok(())
+    }
+
+    /// Returns the capacity of the payload area in bytes.
+    pub fn capacity(&self) -> usize {
+        self.map.len() - HEADER_LEN
+    }
+
+    fn read_payload(&self) -> Result<(u64, Vec<u8>), Error> {
+        let mut snooze = Backoff::new().start();
+        loop {
+            let seq = self.seq().load(Ordering::Acquire);
+            if seq & 1 == 0 {
+                let len = self.header(24).load(Ordering::Relaxed) as usize;
+                if len > self.capacity() {
+                    return Err(Error::Corrupted("too large length"));
+                }
+                let mut bytes = vec![0; len];
+                unsafe {
+                    copy_volatile(self.payload(), bytes.as_mut_ptr(), len);
+                }
+                atomic::fence(Ordering::Acquire);
+                if self.seq().load(Ordering::Relaxed) == seq {
+                    return Ok((seq, bytes));
+                }
+            }
+            snooze.snooze();
+        }
+    }
+
+    fn seq(&self) -> &AtomicU64 {
+        self.header(8)
+    }
+
+    fn header(&self, offset: usize) -> &AtomicU64 {
+        // The mapping is page-aligned, and the offset is a multiple of 8.
+        unsafe { &*self.map.as_mut_ptr().add(offset).cast::<AtomicU64>() }
+    }
+
+    fn payload(&self) -> *mut u8 {
+        unsafe { self.map.as_mut_ptr().add(HEADER_LEN) }
+    }
+}
+impl<T: fmt::Debug, C> fmt::Debug for SharedImmut<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedImmut")
+            .field("len", &self.map.len())
+            .field("cache", &*self.cache.load())
+            .finish()
+    }
+}
+
+/// Copies bytes which may be concurrently written by other processes (see `copy::AtomicImmutCopy`).
+unsafe fn copy_volatile(src: *const u8, dst: *mut u8, len: usize) {
+    for i in 0..len {
+        ptr::write_volatile(dst.add(i), ptr::read_volatile(src.add(i)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::thread;
+
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "atomic_immut_shm_{}_{}",
+            name,
+            ::std::process::id()
+        ))
+    }
+
+    fn codec() -> impl Codec<u64> + Copy {
+        (
+            |v: &u64| -> Result<Vec<u8>, CodecError> { Ok(v.to_string().into_bytes()) },
+            |b: &[u8]| -> Result<u64, CodecError> { Ok(::std::str::from_utf8(b)?.parse()?) },
+        )
+    }
+
+    #[test]
+    fn concurrent_stores_and_loads_work() {
+        let path = path("concurrent");
+        let writer = Arc::new(SharedImmut::create(&path, 64, codec(), 0).unwrap());
+        let handles = (0..2)
+            .map(|_| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    let reader = SharedImmut::open(&path, codec()).unwrap();
+                    let mut last = 0;
+                    while last < 1000 {
+                        let v = *reader.load().unwrap();
+                        assert!(v >= last);
+                        last = v;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 1..=1000 {
+            writer.store(i).unwrap();
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn errors_work() {
+        let path = path("errors");
+        let cell = SharedImmut::create(&path, 2, codec(), 10).unwrap();
+        assert!(matches!(
+            cell.store(100),
+            Err(Error::TooLarge {
+                len: 3,
+                capacity: 2
+            })
+        ));
+        assert_eq!(*cell.load().unwrap(), 10);
+
+        fs::write(&path, b"not a shared file").unwrap();
+        assert!(matches!(
+            SharedImmut::open(&path, codec()),
+            Err(Error::Corrupted(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
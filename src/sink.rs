@@ -0,0 +1,112 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_sink::Sink;
+
+use backend::{Backend, DefaultBackend};
+use AtomicImmut;
+
+/// A sink which stores every received value into an `AtomicImmut`.
+///
+/// This is created by the `AtomicImmut::sink` method (available when the `async` feature is enabled).
+/// It accepts both `T` and `Arc<T>`, and is always ready (storing never blocks for long).
+///
+/// # Examples
+///
+/// ```
+/// # extern crate atomic_immut;
+/// # extern crate futures;
+/// use atomic_immut::AtomicImmut;
+/// use futures::executor::block_on;
+/// use futures::stream::{self, StreamExt};
+///
+/// # fn main() {
+/// let config = AtomicImmut::new(0);
+/// let updates = stream::iter(vec![1, 2, 3]).map(Ok);
+/// block_on(updates.forward(config.sink())).unwrap();
+/// assert_eq!(*config.load(), 3);
+/// # }
+/// ```
+pub struct StoreSink<'a, T: ?Sized + 'a, B: 'a = DefaultBackend> {
+    cell: &'a AtomicImmut<T, B>,
+}
+impl<'a, T: ?Sized, B: Backend> StoreSink<'a, T, B> {
+    pub(crate) fn new(cell: &'a AtomicImmut<T, B>) -> Self {
+        StoreSink { cell }
+    }
+}
+impl<'a, T, B: Backend> Sink<T> for StoreSink<'a, T, B> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.cell.store(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Sink<Arc<T>> for StoreSink<'a, T, B> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Arc<T>) -> Result<(), Self::Error> {
+        self.cell.store_arc(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+impl<'a, T: ?Sized, B> Clone for StoreSink<'a, T, B> {
+    fn clone(&self) -> Self {
+        StoreSink { cell: self.cell }
+    }
+}
+impl<'a, T: ?Sized, B> fmt::Debug for StoreSink<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreSink").finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::SinkExt;
+
+    use super::*;
+
+    #[test]
+    fn sink_accepts_values_and_arcs() {
+        let v: AtomicImmut<str> = AtomicImmut::from_arc(Arc::from("foo"));
+        let mut sink = v.sink();
+        block_on(sink.send(Arc::from("bar"))).unwrap();
+        assert_eq!(&*v.load(), "bar");
+
+        let v = AtomicImmut::new(0);
+        let mut sink = v.sink();
+        block_on(sink.send(1)).unwrap();
+        block_on(sink.send(Arc::new(2))).unwrap();
+        assert_eq!(*v.load(), 2);
+    }
+}
@@ -0,0 +1,229 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::Ordering;
+
+use primitive::AtomicUsize;
+
+/// Values which `AtomicImmutSmall` can pack into a machine word.
+///
+/// # Safety
+///
+/// The size of the type must not exceed that of `usize`,
+/// and every byte of its values must be initialized (i.e., the type has no padding bytes).
+/// The type must not contain pointers either,
+/// since their provenance is lost when they are packed into a `usize`.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::{AtomicImmutSmall, SmallValue};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// #[repr(u8)]
+/// enum State {
+///     Idle,
+///     Busy,
+/// }
+/// unsafe impl SmallValue for State {}
+///
+/// let state = AtomicImmutSmall::new(State::Idle);
+/// state.store(State::Busy);
+/// assert_eq!(state.load(), State::Busy);
+/// ```
+pub unsafe trait SmallValue: Copy {}
+
+macro_rules! impl_small_value {
+    ($($t:ty),*) => {
+        $(unsafe impl SmallValue for $t {})*
+    };
+}
+impl_small_value!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    usize,
+    i8,
+    i16,
+    i32,
+    isize,
+    f32
+);
+#[cfg(target_pointer_width = "64")]
+impl_small_value!(u64, i64, f64);
+
+/// A thread-safe cell for word-sized immutable values.
+///
+/// This is the counterpart of `AtomicImmut` for small `Copy` values:
+/// a value is packed directly into an atomic word,
+/// so neither allocation nor reference counting is involved,
+/// and readers get the value itself instead of an `Arc`.
+///
+/// Values are compared by their bit patterns (e.g., in `compare_and_swap`),
+/// so `0.0` and `-0.0` are different values while `NaN` may equal itself.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmutSmall;
+///
+/// let value = AtomicImmutSmall::new(5u32);
+/// value.update(|x| x + 1);
+/// assert_eq!(value.load(), 6);
+///
+/// assert_eq!(value.swap(10), 6);
+/// assert_eq!(value.load(), 10);
+/// ```
+pub struct AtomicImmutSmall<T> {
+    word: AtomicUsize,
+    _value: PhantomData<T>,
+}
+impl<T: SmallValue> AtomicImmutSmall<T> {
+    /// Makes a new `AtomicImmutSmall` instance.
+    pub fn new(value: T) -> Self {
+        AtomicImmutSmall {
+            word: AtomicUsize::new(pack(value)),
+            _value: PhantomData,
+        }
+    }
+
+    /// Loads the value from this cell.
+    pub fn load(&self) -> T {
+        unsafe { unpack(self.word.load(Ordering::Acquire)) }
+    }
+
+    /// Stores a value into this cell.
+    pub fn store(&self, value: T) {
+        self.word.store(pack(value), Ordering::Release);
+    }
+
+    /// Stores a value into this cell, returning the old value.
+    pub fn swap(&self, value: T) -> T {
+        unsafe { unpack(self.word.swap(pack(value), Ordering::AcqRel)) }
+    }
+
+    /// Updates the value of this cell by applying `f` to the current one.
+    ///
+    /// As with `AtomicImmut::update`, `f` may be called more than once if other threads
+    /// update this cell concurrently.
+    pub fn update<F>(&self, mut f: F)
+    where
+        F: FnMut(T) -> T,
+    {
+        let mut current = self.word.load(Ordering::Acquire);
+        loop {
+            let new = pack(f(unsafe { unpack(current) }));
+            match self
+                .word
+                .compare_exchange_weak(current, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Stores `new` into this cell if the current value is the same as `current`.
+    ///
+    /// Returns the old value as `Ok` if the replacement succeeds,
+    /// otherwise the actual current value as `Err`.
+    /// This never fails spuriously.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::AtomicImmutSmall;
+    ///
+    /// let value = AtomicImmutSmall::new('a');
+    /// assert_eq!(value.compare_and_swap('a', 'b'), Ok('a'));
+    /// assert_eq!(value.compare_and_swap('a', 'c'), Err('b'));
+    /// assert_eq!(value.load(), 'b');
+    /// ```
+    pub fn compare_and_swap(&self, current: T, new: T) -> Result<T, T> {
+        self.word
+            .compare_exchange(
+                pack(current),
+                pack(new),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .map(|old| unsafe { unpack(old) })
+            .map_err(|actual| unsafe { unpack(actual) })
+    }
+}
+impl<T: SmallValue + fmt::Debug> fmt::Debug for AtomicImmutSmall<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicImmutSmall")
+            .field(&self.load())
+            .finish()
+    }
+}
+impl<T: SmallValue + Default> Default for AtomicImmutSmall<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<T: SmallValue> From<T> for AtomicImmutSmall<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+fn pack<T: SmallValue>(value: T) -> usize {
+    assert!(mem::size_of::<T>() <= mem::size_of::<usize>());
+    let mut word = 0;
+    // The unused bytes stay zero, so equal bit patterns of `T` always make equal words.
+    unsafe { ptr::write_unaligned((&mut word as *mut usize).cast::<T>(), value) };
+    word
+}
+
+/// # Safety
+///
+/// `word` must have been made by `pack::<T>`.
+unsafe fn unpack<T: SmallValue>(word: usize) -> T {
+    ptr::read_unaligned((&word as *const usize).cast::<T>())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let v = AtomicImmutSmall::new(-3i16);
+        assert_eq!(v.load(), -3);
+        assert_eq!(v.swap(7), -3);
+        assert_eq!(v.compare_and_swap(7, i16::MIN), Ok(7));
+        assert_eq!(v.load(), i16::MIN);
+
+        let v = AtomicImmutSmall::new(0.0f32);
+        assert_eq!(v.compare_and_swap(-0.0, 1.0), Err(0.0));
+        assert_eq!(format!("{:?}", v), "AtomicImmutSmall(0.0)");
+    }
+
+    #[test]
+    fn concurrent_update_works() {
+        let v = Arc::new(AtomicImmutSmall::new(0u32));
+        let handles = (0..4)
+            .map(|_| {
+                let v = Arc::clone(&v);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        v.update(|x| x + 1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(v.load(), 4000);
+    }
+}
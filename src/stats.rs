@@ -0,0 +1,48 @@
+#[cfg(feature = "stats")]
+use primitive::plain::{AtomicUsize, Ordering};
+
+/// Contention statistics of the updates of an `AtomicImmut`.
+///
+/// This is returned by `AtomicImmut::stats` (available when the `stats` feature is enabled).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Stats {
+    /// The number of the successful updates (e.g., `update`, `try_update` and `fetch_update`).
+    pub updates: usize,
+
+    /// The number of the retries caused by conflicts with other writers.
+    pub retries: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StatsCounter {
+    #[cfg(feature = "stats")]
+    updates: AtomicUsize,
+    #[cfg(feature = "stats")]
+    retries: AtomicUsize,
+}
+impl StatsCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn record(&self, updated: bool, retries: usize) {
+        if updated {
+            self.updates.fetch_add(1, Ordering::Relaxed);
+        }
+        if retries != 0 {
+            self.retries.fetch_add(retries, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(not(feature = "stats"))]
+    pub fn record(&self, _updated: bool, _retries: usize) {}
+
+    #[cfg(feature = "stats")]
+    pub fn get(&self) -> Stats {
+        Stats {
+            updates: self.updates.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+
+use backend::{Backend, DefaultBackend};
+use {AtomicImmut, HookId, Lagged, Subscriber};
+
+/// The policy of a `Snapshots` stream for the values stored faster than they are consumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamPolicy {
+    /// Yields only the latest value, skipping the intermediate ones (i.e., conflation).
+    Latest,
+
+    /// Buffers up to the given number of values.
+    ///
+    /// If the buffer is full, newly stored values are dropped
+    /// and the stream yields a `Lagged` error in their place.
+    Buffer(usize),
+}
+
+/// A stream which yields the values stored into an `AtomicImmut`.
+///
+/// This is created by the `AtomicImmut::stream` method (available when the `async` feature is enabled).
+///
+/// # Examples
+///
+/// ```
+/// # extern crate atomic_immut;
+/// # extern crate futures;
+/// use atomic_immut::{AtomicImmut, StreamPolicy};
+/// use futures::executor::block_on;
+/// use futures::StreamExt;
+///
+/// # fn main() {
+/// let value = AtomicImmut::new(0);
+/// let mut stream = value.stream(StreamPolicy::Buffer(2));
+/// for i in 1..4 {
+///     value.store(i);
+/// }
+///
+/// assert_eq!(block_on(stream.next()).map(|v| *v.unwrap()), Some(1));
+/// assert_eq!(block_on(stream.next()).map(|v| *v.unwrap()), Some(2));
+/// assert_eq!(block_on(stream.next()).map(|v| v.unwrap_err().missed()), Some(1));
+/// # }
+/// ```
+pub struct Snapshots<'a, T: ?Sized + 'a, B: 'a + Backend = DefaultBackend> {
+    inner: Inner<'a, T, B>,
+}
+impl<'a, T, B> Snapshots<'a, T, B>
+where
+    T: ?Sized + Send + Sync + 'static,
+    B: Backend,
+{
+    pub(crate) fn new(cell: &'a AtomicImmut<T, B>, policy: StreamPolicy) -> Self {
+        let inner = match policy {
+            StreamPolicy::Latest => Inner::Latest(cell.subscribe()),
+            StreamPolicy::Buffer(capacity) => {
+                let buffer = Arc::new(Mutex::new(Buffer {
+                    entries: VecDeque::new(),
+                    values: 0,
+                    capacity,
+                    waker: None,
+                }));
+                let id = {
+                    let buffer = Arc::clone(&buffer);
+                    cell.register_on_store(move |_, new| lock(&buffer).push(new))
+                };
+                Inner::Buffer { cell, id, buffer }
+            }
+        };
+        Snapshots { inner }
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Stream for Snapshots<'a, T, B> {
+    type Item = Result<Arc<T>, Lagged>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut().inner {
+            Inner::Latest(ref mut subscriber) => Pin::new(subscriber)
+                .poll_next(cx)
+                .map(|value| value.map(Ok)),
+            Inner::Buffer { ref buffer, .. } => {
+                let mut buffer = lock(buffer);
+                match buffer.entries.pop_front() {
+                    Some(Entry::Value(value)) => {
+                        buffer.values -= 1;
+                        Poll::Ready(Some(Ok(value)))
+                    }
+                    Some(Entry::Lagged(missed)) => Poll::Ready(Some(Err(Lagged::new(missed)))),
+                    None => {
+                        buffer.waker = Some(cx.waker().clone());
+                        Poll::Pending
+                    }
+                }
+            }
+        }
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Drop for Snapshots<'a, T, B> {
+    fn drop(&mut self) {
+        if let Inner::Buffer { cell, id, .. } = self.inner {
+            cell.unregister(id);
+        }
+    }
+}
+impl<'a, T: ?Sized, B: Backend> fmt::Debug for Snapshots<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.inner {
+            Inner::Latest(_) => f
+                .debug_struct("Snapshots")
+                .field("policy", &"Latest")
+                .finish(),
+            Inner::Buffer { ref buffer, .. } => {
+                let buffer = lock(buffer);
+                f.debug_struct("Snapshots")
+                    .field("capacity", &buffer.capacity)
+                    .field("buffered", &buffer.entries.len())
+                    .finish()
+            }
+        }
+    }
+}
+
+enum Inner<'a, T: ?Sized + 'a, B: 'a + Backend> {
+    Latest(Subscriber<'a, T, B>),
+    Buffer {
+        cell: &'a AtomicImmut<T, B>,
+        id: HookId,
+        buffer: Arc<Mutex<Buffer<T>>>,
+    },
+}
+
+struct Buffer<T: ?Sized> {
+    entries: VecDeque<Entry<T>>,
+    // The number of `Entry::Value`s in `entries`.
+    values: usize,
+    capacity: usize,
+    waker: Option<Waker>,
+}
+impl<T: ?Sized> Buffer<T> {
+    fn push(&mut self, value: &Arc<T>) {
+        if self.values < self.capacity {
+            self.entries.push_back(Entry::Value(Arc::clone(value)));
+            self.values += 1;
+        } else if let Some(Entry::Lagged(missed)) = self.entries.back_mut() {
+            *missed += 1;
+        } else {
+            // Markers are not counted so that the consumer knows where the values are missed.
+            self.entries.push_back(Entry::Lagged(1));
+        }
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+enum Entry<T: ?Sized> {
+    Value(Arc<T>),
+    Lagged(usize),
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn latest_policy_conflates_values() {
+        let v = AtomicImmut::new(0);
+        let mut stream = v.stream(StreamPolicy::Latest);
+        v.store(1);
+        v.store(2);
+        assert_eq!(block_on(stream.next()).map(|v| *v.unwrap()), Some(2));
+    }
+
+    #[test]
+    fn buffer_policy_yields_every_value() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let mut stream = v.stream(StreamPolicy::Buffer(1000));
+        let handle = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || (1..=100).for_each(|i| v.store(i)))
+        };
+        for i in 1..=100 {
+            assert_eq!(block_on(stream.next()).map(|v| *v.unwrap()), Some(i));
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn buffer_policy_reports_overflow() {
+        let v = AtomicImmut::new(0);
+        let mut stream = v.stream(StreamPolicy::Buffer(1));
+        v.store(1);
+        v.store(2);
+        v.store(3);
+        assert_eq!(block_on(stream.next()).map(|v| *v.unwrap()), Some(1));
+        v.store(4);
+        assert_eq!(
+            block_on(stream.next()).map(|v| v.unwrap_err().missed()),
+            Some(2)
+        );
+        assert_eq!(block_on(stream.next()).map(|v| *v.unwrap()), Some(4));
+
+        drop(stream);
+        v.store(5);
+    }
+}
@@ -0,0 +1,472 @@
+#[cfg(feature = "async")]
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "async")]
+use core::future::Future;
+#[cfg(feature = "async")]
+use core::pin::Pin;
+#[cfg(feature = "async")]
+use core::task::{Context, Poll, Waker};
+#[cfg(feature = "async")]
+use futures_core::Stream;
+#[cfg(target_has_atomic = "64")]
+use primitive::plain::AtomicU64;
+#[cfg(any(feature = "std", not(target_has_atomic = "64")))]
+use primitive::plain::AtomicUsize;
+use primitive::plain::Ordering;
+#[cfg(feature = "std")]
+use primitive::Arc;
+#[cfg(feature = "std")]
+use std::sync::{Condvar, Mutex, PoisonError};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use backend::{Backend, DefaultBackend};
+use AtomicImmut;
+
+/// A subscriber of the changes of the value of an `AtomicImmut`.
+///
+/// This is created by the `AtomicImmut::subscribe` method.
+///
+/// A subscriber remembers the last value it has seen,
+/// and each call of `wait` blocks until a newer value is stored into the `AtomicImmut`.
+///
+/// Note that `wait` and `wait_timeout` are only available when the `std` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::AtomicImmut;
+///
+/// let value = AtomicImmut::new(5);
+/// let mut subscriber = value.subscribe();
+/// assert!(!subscriber.has_changed());
+///
+/// value.store(10);
+/// assert!(subscriber.has_changed());
+/// assert_eq!(*subscriber.wait(), 10);
+/// assert!(!subscriber.has_changed());
+/// ```
+pub struct Subscriber<'a, T: ?Sized + 'a, B: 'a = DefaultBackend> {
+    cell: &'a AtomicImmut<T, B>,
+    seen: u64,
+}
+impl<'a, T: ?Sized, B> fmt::Debug for Subscriber<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscriber")
+            .field("seen", &self.seen)
+            .finish()
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Subscriber<'a, T, B> {
+    pub(crate) fn new(cell: &'a AtomicImmut<T, B>) -> Self {
+        let seen = cell.notifier.version();
+        Subscriber { cell, seen }
+    }
+
+    /// Returns `true` if a new value has been stored since the last time this subscriber saw the value.
+    pub fn has_changed(&self) -> bool {
+        self.cell.notifier.version() != self.seen
+    }
+
+    /// Blocks the current thread until a new value is stored, and returns the latest value.
+    ///
+    /// If a new value has already been stored since the last time this subscriber saw the value,
+    /// this method returns immediately.
+    #[cfg(feature = "std")]
+    pub fn wait(&mut self) -> Arc<T> {
+        let waiters = &self.cell.extras.get_or_init().waiters;
+        self.cell.notifier.wait(waiters, self.seen, None);
+        self.mark_seen()
+    }
+
+    /// Same as `wait` except that this method gives up waiting after `timeout` has elapsed.
+    ///
+    /// If the timeout expires, `None` will be returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use atomic_immut::AtomicImmut;
+    ///
+    /// let value = AtomicImmut::new(5);
+    /// let mut subscriber = value.subscribe();
+    /// assert_eq!(subscriber.wait_timeout(Duration::from_millis(1)), None);
+    ///
+    /// value.store(10);
+    /// assert_eq!(subscriber.wait_timeout(Duration::from_millis(1)).map(|v| *v), Some(10));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Option<Arc<T>> {
+        let waiters = &self.cell.extras.get_or_init().waiters;
+        if self.cell.notifier.wait(waiters, self.seen, Some(timeout)) {
+            Some(self.mark_seen())
+        } else {
+            None
+        }
+    }
+
+    /// Returns a future which completes with the latest value when a new value is stored.
+    ///
+    /// This is the asynchronous version of `wait`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate atomic_immut;
+    /// # extern crate futures;
+    /// use atomic_immut::AtomicImmut;
+    /// use futures::executor::block_on;
+    ///
+    /// # fn main() {
+    /// let value = AtomicImmut::new(5);
+    /// let mut subscriber = value.subscribe();
+    ///
+    /// value.store(10);
+    /// assert_eq!(*block_on(subscriber.changed()), 10);
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn changed(&mut self) -> Changed<'_, 'a, T, B> {
+        Changed(self)
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<Arc<T>> {
+        let waiters = &self.cell.extras.get_or_init().waiters;
+        match self.cell.notifier.poll_wait(waiters, self.seen, cx) {
+            Poll::Ready(()) => Poll::Ready(self.mark_seen()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn mark_seen(&mut self) -> Arc<T> {
+        let (value, version) = self.cell.load_versioned();
+        self.seen = version;
+        value
+    }
+}
+
+/// A `Subscriber` is a never ending stream which yields each newly stored value.
+///
+/// Note that if several values are stored in quick succession,
+/// only the latest one may be yielded.
+#[cfg(feature = "async")]
+impl<'a, T: ?Sized, B: Backend> Stream for Subscriber<'a, T, B> {
+    type Item = Arc<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut().poll_changed(cx) {
+            Poll::Ready(value) => Poll::Ready(Some(value)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A future which completes when a new value is stored into an `AtomicImmut`.
+///
+/// This is created by the `Subscriber::changed` method.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct Changed<'b, 'a: 'b, T: ?Sized + 'a, B: 'a = DefaultBackend>(
+    &'b mut Subscriber<'a, T, B>,
+);
+#[cfg(feature = "async")]
+impl<'b, 'a, T: ?Sized, B: Backend> Future for Changed<'b, 'a, T, B> {
+    type Output = Arc<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().0.poll_changed(cx)
+    }
+}
+
+/// The version of the value of an `AtomicImmut`, which subscribers wait to change.
+///
+/// The waiters are kept in the extras of the `AtomicImmut`, which are allocated by the first waiter.
+#[derive(Debug)]
+pub(crate) struct Notifier {
+    version: Version,
+}
+impl Notifier {
+    pub(crate) fn new() -> Self {
+        Notifier {
+            version: Version::new(),
+        }
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.version.get()
+    }
+
+    /// Increments the version, returning the new one.
+    ///
+    /// The waiters must be notified after the new value becomes visible.
+    pub(crate) fn advance(&self) -> u64 {
+        self.version.increment()
+    }
+
+    /// Registers the waker of `cx` if the version has not been changed from `seen`.
+    #[cfg(feature = "async")]
+    fn poll_wait(&self, waiters: &Waiters, seen: u64, cx: &mut Context<'_>) -> Poll<()> {
+        if self.version() != seen {
+            return Poll::Ready(());
+        }
+
+        waiters.register(cx.waker());
+        if self.version() != seen {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Waits until the version is changed from `seen`.
+    ///
+    /// Returns `false` if the timeout expired.
+    #[cfg(feature = "std")]
+    fn wait(&self, waiters: &Waiters, seen: u64, timeout: Option<Duration>) -> bool {
+        if self.version() != seen {
+            return true;
+        }
+        waiters.wait(|| self.version() != seen, timeout)
+    }
+}
+
+// `Version` and `Waiters::count` stay `SeqCst`: a waiter increments the count and then checks the version,
+// while a notifier increments the version and then checks the count.
+// This store-load pattern needs a total order for the waiter not to miss the notification.
+// The same goes for the pointer to the extras holding `Waiters` (see `LazyExtras::get`).
+#[cfg(target_has_atomic = "64")]
+#[derive(Debug)]
+struct Version(AtomicU64);
+#[cfg(target_has_atomic = "64")]
+impl Version {
+    fn new() -> Self {
+        Version(AtomicU64::new(0))
+    }
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+    fn increment(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+// Falls back to a pointer sized counter on targets without 64-bit atomics.
+#[cfg(not(target_has_atomic = "64"))]
+#[derive(Debug)]
+struct Version(AtomicUsize);
+#[cfg(not(target_has_atomic = "64"))]
+impl Version {
+    fn new() -> Self {
+        Version(AtomicUsize::new(0))
+    }
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst) as u64
+    }
+    fn increment(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst).wrapping_add(1) as u64
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct Waiters {
+    count: AtomicUsize,
+    mutex: Mutex<Wakers>,
+    condvar: Condvar,
+}
+#[cfg(feature = "std")]
+impl Waiters {
+    pub(crate) fn new() -> Self {
+        Waiters {
+            count: AtomicUsize::new(0),
+            mutex: Mutex::new(Wakers::default()),
+            condvar: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn notify_all(&self) {
+        if self.count.load(Ordering::SeqCst) != 0 {
+            let mut wakers = self.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+            self.condvar.notify_all();
+            self.count.fetch_sub(wakers.len(), Ordering::SeqCst);
+            wakers.wake_all();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+        if wakers.register(waker) {
+            self.count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn wait<F>(&self, is_changed: F, timeout: Option<Duration>) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        self.count.fetch_add(1, Ordering::SeqCst);
+        let mut guard = self.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+        let changed = loop {
+            if is_changed() {
+                break true;
+            }
+            if let Some(deadline) = deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    break false;
+                }
+                guard = self
+                    .condvar
+                    .wait_timeout(guard, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            } else {
+                guard = self
+                    .condvar
+                    .wait(guard)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        };
+        self.count.fetch_sub(1, Ordering::SeqCst);
+        changed
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+struct Wakers {
+    #[cfg(feature = "async")]
+    wakers: Vec<Waker>,
+}
+#[cfg(feature = "std")]
+impl Wakers {
+    #[cfg(feature = "async")]
+    fn register(&mut self, waker: &Waker) -> bool {
+        if self.wakers.iter().any(|w| w.will_wake(waker)) {
+            false
+        } else {
+            self.wakers.push(waker.clone());
+            true
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn len(&self) -> usize {
+        self.wakers.len()
+    }
+    #[cfg(not(feature = "async"))]
+    fn len(&self) -> usize {
+        0
+    }
+
+    #[cfg(feature = "async")]
+    fn wake_all(&mut self) {
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+    #[cfg(not(feature = "async"))]
+    fn wake_all(&mut self) {}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use AtomicImmut;
+
+    #[test]
+    fn wait_works() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let mut subscriber = v.subscribe();
+
+        let handle = {
+            let v = v.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                v.store(1);
+                v.update(|x| x + 1);
+            })
+        };
+        let mut last = 0;
+        while last != 2 {
+            last = *subscriber.wait();
+        }
+        handle.join().unwrap();
+        assert!(!subscriber.has_changed());
+    }
+
+    #[test]
+    fn wait_timeout_works() {
+        let v = AtomicImmut::new(0);
+        let mut subscriber = v.subscribe();
+        assert_eq!(subscriber.wait_timeout(Duration::from_millis(5)), None);
+
+        let current = v.load();
+        assert!(v.compare_and_swap(&current, 1).is_ok());
+        assert!(v.compare_and_swap(&current, 2).is_err());
+        assert_eq!(
+            subscriber
+                .wait_timeout(Duration::from_millis(5))
+                .map(|v| *v),
+            Some(1)
+        );
+        assert_eq!(subscriber.wait_timeout(Duration::from_millis(5)), None);
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_test {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use AtomicImmut;
+
+    #[test]
+    fn changed_works() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let mut subscriber = v.subscribe();
+
+        let handle = {
+            let v = v.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                v.store(1);
+            })
+        };
+        assert_eq!(*block_on(subscriber.changed()), 1);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn stream_works() {
+        let v = Arc::new(AtomicImmut::new(0));
+        let mut subscriber = v.subscribe();
+
+        let handle = {
+            let v = v.clone();
+            thread::spawn(move || {
+                for i in 1..4 {
+                    thread::sleep(Duration::from_millis(5));
+                    v.store(i);
+                }
+            })
+        };
+        let mut last = 0;
+        while last != 3 {
+            last = *block_on(subscriber.next()).unwrap();
+        }
+        handle.join().unwrap();
+    }
+}
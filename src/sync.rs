@@ -0,0 +1,330 @@
+//! General-purpose synchronization primitives.
+//!
+//! These are handy for small critical sections without depending on other crates.
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use primitive::plain::{AtomicUsize, Ordering};
+
+use backend::Backoff;
+
+const WRITER: usize = 0b01;
+const WRITER_WAITING: usize = 0b10;
+const ONE_READER: usize = 0b100;
+
+/// The maximum number of readers (like the reference count of `Arc`, this is never reached in practice).
+const MAX_READERS: usize = (isize::MAX as usize) / ONE_READER;
+
+/// A reader-writer spin lock packed into a single word.
+///
+/// Readers and writers wait following `Backoff` instead of being blocked by the OS,
+/// so this is suitable only for short critical sections.
+///
+/// Writers are preferred: once a writer is waiting, new readers wait until it finishes,
+/// so writers are never starved by a constant stream of readers.
+///
+/// # Examples
+///
+/// ```
+/// use atomic_immut::sync::SpinRwLock;
+///
+/// let lock = SpinRwLock::new(5);
+/// {
+///     let r0 = lock.read();
+///     let r1 = lock.read();
+///     assert_eq!(*r0 + *r1, 10);
+/// }
+/// *lock.write() += 1;
+/// assert_eq!(*lock.read(), 6);
+/// ```
+pub struct SpinRwLock<T: ?Sized> {
+    state: AtomicUsize,
+    backoff: Backoff,
+    value: UnsafeCell<T>,
+}
+impl<T> SpinRwLock<T> {
+    /// Makes a new `SpinRwLock` instance.
+    pub const fn new(value: T) -> Self {
+        Self::with_backoff(value, Backoff::new())
+    }
+
+    /// Makes a new `SpinRwLock` instance which waits following `backoff`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::backend::Backoff;
+    /// use atomic_immut::sync::SpinRwLock;
+    ///
+    /// let lock = SpinRwLock::with_backoff(5, Backoff::new().yielding(false));
+    /// assert_eq!(*lock.read(), 5);
+    /// ```
+    pub const fn with_backoff(value: T, backoff: Backoff) -> Self {
+        SpinRwLock {
+            state: AtomicUsize::new(0),
+            backoff,
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes this lock, returning the contained value.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+impl<T: ?Sized> SpinRwLock<T> {
+    /// Acquires a shared read access, waiting until no writer holds or waits for the lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of the readers overflows.
+    pub fn read(&self) -> SpinRwLockReadGuard<'_, T> {
+        let mut snooze = self.backoff.start();
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            snooze.snooze();
+        }
+    }
+
+    /// Tries to acquire a shared read access without waiting.
+    ///
+    /// Returns `None` if a writer holds or waits for the lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of the readers overflows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::sync::SpinRwLock;
+    ///
+    /// let lock = SpinRwLock::new(5);
+    /// let writer = lock.write();
+    /// assert!(lock.try_read().is_none());
+    ///
+    /// drop(writer);
+    /// assert_eq!(lock.try_read().map(|v| *v), Some(5));
+    /// ```
+    pub fn try_read(&self) -> Option<SpinRwLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & (WRITER | WRITER_WAITING) != 0 {
+                return None;
+            }
+            if state / ONE_READER >= MAX_READERS {
+                panic!("Too many readers of a `SpinRwLock`");
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + ONE_READER,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(SpinRwLockReadGuard(self)),
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Acquires an exclusive write access, waiting until all the other guards are released.
+    pub fn write(&self) -> SpinRwLockWriteGuard<'_, T> {
+        let mut snooze = self.backoff.start();
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+
+            // Stops new readers from coming in.
+            let state = self.state.load(Ordering::Relaxed);
+            if state & WRITER_WAITING == 0 {
+                self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            }
+            snooze.snooze();
+        }
+    }
+
+    /// Tries to acquire an exclusive write access without waiting.
+    ///
+    /// Returns `None` if another guard is alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::sync::SpinRwLock;
+    ///
+    /// let lock = SpinRwLock::new(5);
+    /// let reader = lock.read();
+    /// assert!(lock.try_write().is_none());
+    ///
+    /// drop(reader);
+    /// *lock.try_write().unwrap() = 10;
+    /// assert_eq!(*lock.read(), 10);
+    /// ```
+    pub fn try_write(&self) -> Option<SpinRwLockWriteGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & !WRITER_WAITING != 0 {
+                return None;
+            }
+            // Clears `WRITER_WAITING`: other waiting writers set it again.
+            match self.state.compare_exchange_weak(
+                state,
+                WRITER,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(SpinRwLockWriteGuard(self)),
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the contained value.
+    ///
+    /// No locking is needed since this lock is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+unsafe impl<T: ?Sized + Send> Send for SpinRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for SpinRwLock<T> {}
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SpinRwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = f.debug_struct("SpinRwLock");
+        match self.try_read() {
+            Some(guard) => s.field("value", &&*guard),
+            None => s.field("value", &format_args!("<locked>")),
+        };
+        s.finish()
+    }
+}
+impl<T: Default> Default for SpinRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<T> From<T> for SpinRwLock<T> {
+    fn from(f: T) -> Self {
+        Self::new(f)
+    }
+}
+
+/// A guard of the shared read access to a `SpinRwLock`.
+pub struct SpinRwLockReadGuard<'a, T: ?Sized + 'a>(&'a SpinRwLock<T>);
+impl<'a, T: ?Sized> Deref for SpinRwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.0.value.get() }
+    }
+}
+impl<'a, T: ?Sized> Drop for SpinRwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.0.state.fetch_sub(ONE_READER, Ordering::Release);
+    }
+}
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for SpinRwLockReadGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// A guard of the exclusive write access to a `SpinRwLock`.
+pub struct SpinRwLockWriteGuard<'a, T: ?Sized + 'a>(&'a SpinRwLock<T>);
+impl<'a, T: ?Sized> Deref for SpinRwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.0.value.get() }
+    }
+}
+impl<'a, T: ?Sized> DerefMut for SpinRwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.0.value.get() }
+    }
+}
+impl<'a, T: ?Sized> Drop for SpinRwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // Keeps `WRITER_WAITING` set by other writers.
+        self.0.state.fetch_and(!WRITER, Ordering::Release);
+    }
+}
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for SpinRwLockWriteGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn spin_rw_lock_works() {
+        let lock = Arc::new(SpinRwLock::new(0));
+        let handles = (0..4)
+            .map(|_| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        *lock.write() += 1;
+                        let v = lock.read();
+                        assert!(*v > 0);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*lock.read(), 400);
+        assert_eq!(lock.state.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn waiting_writer_blocks_new_readers() {
+        let lock = Arc::new(SpinRwLock::new(0));
+        let reader = lock.read();
+
+        let writer = {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || *lock.write() = 1)
+        };
+        while lock.state.load(Ordering::SeqCst) & WRITER_WAITING == 0 {
+            thread::yield_now();
+        }
+        assert!(lock.try_read().is_none());
+
+        drop(reader);
+        writer.join().unwrap();
+        assert_eq!(*lock.read(), 1);
+        assert_eq!(lock.state.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn try_lock_works() {
+        let mut lock = SpinRwLock::new(vec![0]);
+        {
+            let r = lock.try_read().unwrap();
+            assert!(lock.try_write().is_none());
+            assert!(lock.try_read().is_some());
+            assert_eq!(format!("{:?}", r), "[0]");
+        }
+        {
+            let mut w = lock.try_write().unwrap();
+            w.push(1);
+            assert!(lock.try_read().is_none());
+            assert!(lock.try_write().is_none());
+            assert_eq!(format!("{:?}", lock), "SpinRwLock { value: <locked> }");
+        }
+        lock.get_mut().push(2);
+        assert_eq!(format!("{:?}", lock), "SpinRwLock { value: [0, 1, 2] }");
+        assert_eq!(lock.into_inner(), [0, 1, 2]);
+    }
+}
@@ -0,0 +1,306 @@
+//! Helpers for testing code which uses `AtomicImmut`.
+//!
+//! These replace sleeping and polling in tests
+//! with waits for (and deterministic control over) the stores into cells.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use std::thread;
+//! use std::time::Duration;
+//! use atomic_immut::AtomicImmut;
+//! use atomic_immut::test_util::{assert_eventually, Recorder};
+//!
+//! let value = Arc::new(AtomicImmut::new(0));
+//! let recorder = Recorder::attach(&value);
+//! {
+//!     let value = Arc::clone(&value);
+//!     thread::spawn(move || (1..=3).for_each(|i| value.store(i)));
+//! }
+//! assert_eventually(&value, |v| *v == 3, Duration::from_secs(10));
+//! # while recorder.values().len() < 3 { thread::yield_now(); }
+//! assert_eq!(recorder.values().iter().map(|v| **v).collect::<Vec<_>>(), [1, 2, 3]);
+//! ```
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use backend::{Backend, DefaultBackend};
+use {AtomicImmut, HookId};
+
+/// Waits until the value of `cell` satisfies `pred`, and returns the value.
+///
+/// # Panics
+///
+/// Panics if `timeout` elapses before that.
+pub fn assert_eventually<T, B, F>(cell: &AtomicImmut<T, B>, pred: F, timeout: Duration) -> Arc<T>
+where
+    T: ?Sized + fmt::Debug,
+    B: Backend,
+    F: FnMut(&T) -> bool,
+{
+    match cell.wait_until_timeout(pred, timeout) {
+        Some(value) => value,
+        None => panic!(
+            "The value did not satisfy the predicate within {:?} (last value: {:?})",
+            timeout,
+            cell.load()
+        ),
+    }
+}
+
+/// A recorder of the values stored into an `AtomicImmut`.
+///
+/// The values are recorded (in the order of the stores) until this recorder is dropped.
+pub struct Recorder<'a, T: ?Sized + 'a, B: 'a + Backend = DefaultBackend> {
+    cell: &'a AtomicImmut<T, B>,
+    id: HookId,
+    values: Arc<Mutex<Vec<Arc<T>>>>,
+}
+impl<'a, T, B> Recorder<'a, T, B>
+where
+    T: ?Sized + Send + Sync + 'static,
+    B: Backend,
+{
+    /// Starts recording the values stored into `cell`.
+    pub fn attach(cell: &'a AtomicImmut<T, B>) -> Self {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let id = {
+            let values = Arc::clone(&values);
+            cell.register_on_store(move |_, new| lock(&values).push(Arc::clone(new)))
+        };
+        Recorder { cell, id, values }
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Recorder<'a, T, B> {
+    /// Returns the values recorded so far.
+    pub fn values(&self) -> Vec<Arc<T>> {
+        lock(&self.values).clone()
+    }
+
+    /// Returns the values recorded so far, clearing the record.
+    pub fn take(&self) -> Vec<Arc<T>> {
+        ::std::mem::take(&mut *lock(&self.values))
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Drop for Recorder<'a, T, B> {
+    fn drop(&mut self) {
+        self.cell.unregister(self.id);
+    }
+}
+impl<'a, T: ?Sized + fmt::Debug, B: Backend> fmt::Debug for Recorder<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("values", &*lock(&self.values))
+            .finish()
+    }
+}
+
+/// A controller which pauses writers of an `AtomicImmut` and lets them proceed one by one.
+///
+/// While paused, each store blocks the storing thread right after the value is stored
+/// (and subscribers are notified), until the test thread calls `step`.
+/// So the test thread can observe the state after every store deterministically.
+///
+/// Dropping this resumes all the writers.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use std::time::Duration;
+/// use atomic_immut::AtomicImmut;
+/// use atomic_immut::test_util::Stepper;
+///
+/// let value = Arc::new(AtomicImmut::new(0));
+/// let stepper = Stepper::attach(&value);
+/// stepper.pause();
+///
+/// let writer = {
+///     let value = Arc::clone(&value);
+///     thread::spawn(move || (1..=3).for_each(|i| value.store(i)))
+/// };
+/// for i in 1..=3 {
+///     let stored = stepper.step(Duration::from_secs(10)).unwrap();
+///     assert_eq!(*stored, i);
+/// }
+/// writer.join().unwrap();
+/// ```
+pub struct Stepper<'a, T: ?Sized + 'a, B: 'a + Backend = DefaultBackend> {
+    cell: &'a AtomicImmut<T, B>,
+    id: HookId,
+    state: Arc<StepState<T>>,
+}
+impl<'a, T, B> Stepper<'a, T, B>
+where
+    T: ?Sized + Send + Sync + 'static,
+    B: Backend,
+{
+    /// Starts controlling the writers of `cell`.
+    ///
+    /// The writers are not paused until `pause` is called.
+    pub fn attach(cell: &'a AtomicImmut<T, B>) -> Self {
+        let state = Arc::new(StepState {
+            inner: Mutex::new(StepInner {
+                paused: false,
+                pending: VecDeque::new(),
+                next_ticket: 0,
+                released: 0,
+            }),
+            cond: Condvar::new(),
+        });
+        let id = {
+            let state = Arc::clone(&state);
+            cell.register_on_store(move |_, new| state.hold(new))
+        };
+        Stepper { cell, id, state }
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Stepper<'a, T, B> {
+    /// Makes the subsequent stores wait for `step`.
+    pub fn pause(&self) {
+        lock(&self.state.inner).paused = true;
+    }
+
+    /// Lets all the waiting and subsequent stores proceed.
+    pub fn resume(&self) {
+        let mut inner = lock(&self.state.inner);
+        inner.paused = false;
+        inner.pending.clear();
+        inner.released = inner.next_ticket;
+        self.state.cond.notify_all();
+    }
+
+    /// Returns the number of the stores waiting for `step`.
+    pub fn pending(&self) -> usize {
+        lock(&self.state.inner).pending.len()
+    }
+
+    /// Waits for a paused store, lets it proceed, and returns the stored value.
+    ///
+    /// Returns `None` if no store is paused within `timeout`.
+    pub fn step(&self, timeout: Duration) -> Option<Arc<T>> {
+        let deadline = Instant::now() + timeout;
+        let mut inner = lock(&self.state.inner);
+        loop {
+            if let Some(value) = inner.pending.pop_front() {
+                inner.released += 1;
+                self.state.cond.notify_all();
+                return Some(value);
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            inner = self
+                .state
+                .cond
+                .wait_timeout(inner, remaining)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+impl<'a, T: ?Sized, B: Backend> Drop for Stepper<'a, T, B> {
+    fn drop(&mut self) {
+        self.cell.unregister(self.id);
+        self.resume();
+    }
+}
+impl<'a, T: ?Sized, B: Backend> fmt::Debug for Stepper<'a, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = lock(&self.state.inner);
+        f.debug_struct("Stepper")
+            .field("paused", &inner.paused)
+            .field("pending", &inner.pending.len())
+            .finish()
+    }
+}
+
+struct StepState<T: ?Sized> {
+    inner: Mutex<StepInner<T>>,
+    cond: Condvar,
+}
+impl<T: ?Sized> StepState<T> {
+    fn hold(&self, value: &Arc<T>) {
+        let mut inner = lock(&self.inner);
+        if !inner.paused {
+            return;
+        }
+        let ticket = inner.next_ticket;
+        inner.next_ticket += 1;
+        inner.pending.push_back(Arc::clone(value));
+        self.cond.notify_all();
+        while inner.released <= ticket {
+            inner = self
+                .cond
+                .wait(inner)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+struct StepInner<T: ?Sized> {
+    paused: bool,
+    pending: VecDeque<Arc<T>>,
+    next_ticket: u64,
+    released: u64,
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "last value: 1")]
+    fn assert_eventually_panics_on_timeout() {
+        let value = AtomicImmut::new(1);
+        assert_eventually(&value, |v| *v == 2, Duration::from_millis(1));
+    }
+
+    #[test]
+    fn recorder_works() {
+        let value = AtomicImmut::new(0);
+        let recorder = Recorder::attach(&value);
+        value.store(1);
+        value.update(|v| v + 1);
+        assert_eq!(
+            recorder.take().iter().map(|v| **v).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert!(recorder.values().is_empty());
+
+        let id = recorder.id;
+        drop(recorder);
+        assert!(!value.unregister(id));
+    }
+
+    #[test]
+    fn stepper_works() {
+        let value = Arc::new(AtomicImmut::new(0));
+        let stepper = Stepper::attach(&value);
+        stepper.pause();
+        let writer = {
+            let value = Arc::clone(&value);
+            thread::spawn(move || {
+                value.store(1);
+                value.store(2);
+            })
+        };
+
+        assert_eq!(stepper.step(Duration::from_secs(10)).map(|v| *v), Some(1));
+        assert_eventually(&value, |v| *v == 2, Duration::from_secs(10));
+        while stepper.pending() == 0 {
+            thread::yield_now();
+        }
+        drop(stepper);
+        writer.join().unwrap();
+        assert_eq!(*value.load(), 2);
+    }
+}
@@ -0,0 +1,235 @@
+//! Rate-limited stores.
+//!
+//! A misbehaving producer storing values at a high rate makes readers reload
+//! (and subscribers wake up) for values which are immediately replaced.
+//! `ThrottledWriter` limits the rate of the stores into an `AtomicImmut`,
+//! either dropping the excess stores or coalescing them into the latest value.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use std::thread;
+//! use std::time::Duration;
+//! use atomic_immut::AtomicImmut;
+//! use atomic_immut::throttle::ThrottledWriter;
+//!
+//! let cell = Arc::new(AtomicImmut::new(0));
+//! let writer = ThrottledWriter::new(Arc::clone(&cell))
+//!     .min_store_interval(Duration::from_millis(20))
+//!     .coalesce();
+//!
+//! for i in 1..=100 {
+//!     writer.store(i);
+//! }
+//! // Only the first store has been applied so far.
+//! assert_eq!(*cell.load(), 1);
+//!
+//! // The latest value is applied after the interval.
+//! thread::sleep(Duration::from_millis(100));
+//! assert_eq!(*cell.load(), 100);
+//! ```
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use backend::{Backend, DefaultBackend};
+use AtomicImmut;
+
+/// A writer which limits the rate of the stores into an `AtomicImmut`.
+///
+/// Only the stores through this writer are limited:
+/// the methods of the underlying `AtomicImmut` still store values immediately.
+pub struct ThrottledWriter<T, B = DefaultBackend> {
+    shared: Arc<Shared<T, B>>,
+}
+impl<T, B> ThrottledWriter<T, B>
+where
+    T: Send + Sync + 'static,
+    B: Backend + Send + Sync + 'static,
+{
+    /// Makes a new `ThrottledWriter` instance which stores values into `cell`.
+    ///
+    /// No limit is set by default.
+    pub fn new(cell: Arc<AtomicImmut<T, B>>) -> Self {
+        ThrottledWriter {
+            shared: Arc::new(Shared {
+                cell,
+                min_interval: Duration::from_secs(0),
+                coalesce: false,
+                state: Mutex::new(State {
+                    last_store: None,
+                    pending: None,
+                }),
+            }),
+        }
+    }
+
+    /// Sets the minimum interval between the stores.
+    ///
+    /// The stores within the interval after the previous one are dropped,
+    /// unless `coalesce` is set.
+    pub fn min_store_interval(mut self, interval: Duration) -> Self {
+        self.shared_mut().min_interval = interval;
+        self
+    }
+
+    /// Makes the stores within the interval coalesce instead of being dropped.
+    ///
+    /// The latest of them is stored when the interval elapses
+    /// (by a background thread spawned for each burst of stores).
+    pub fn coalesce(mut self) -> Self {
+        self.shared_mut().coalesce = true;
+        self
+    }
+
+    /// Stores `value` into the underlying `AtomicImmut` if the interval has elapsed.
+    ///
+    /// Returns `true` if the value has been stored immediately.
+    /// Otherwise, the value is dropped or left pending (in the `coalesce` mode).
+    pub fn store(&self, value: T) -> bool {
+        self.store_arc(Arc::new(value))
+    }
+
+    /// Stores the shared `value` like `store`.
+    pub fn store_arc(&self, value: Arc<T>) -> bool {
+        let shared = &self.shared;
+        let mut state = shared.lock();
+        let now = Instant::now();
+        let next = state.next_store(shared.min_interval);
+        if next.is_none_or(|next| next <= now) && state.pending.is_none() {
+            state.last_store = Some(now);
+            shared.cell.store_arc(value);
+            return true;
+        }
+        if !shared.coalesce {
+            return false;
+        }
+
+        let waiting = state.pending.replace(value).is_some();
+        if !waiting {
+            let shared = Arc::clone(shared);
+            thread::spawn(move || shared.store_pending());
+        }
+        false
+    }
+
+    /// Stores the pending value (if any) immediately.
+    ///
+    /// Returns `true` if a value has been stored.
+    pub fn flush(&self) -> bool {
+        let mut state = self.shared.lock();
+        match state.pending.take() {
+            Some(value) => {
+                state.last_store = Some(Instant::now());
+                self.shared.cell.store_arc(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` if there is a pending value.
+    pub fn has_pending(&self) -> bool {
+        self.shared.lock().pending.is_some()
+    }
+
+    /// Returns the underlying `AtomicImmut`.
+    pub fn cell(&self) -> &Arc<AtomicImmut<T, B>> {
+        &self.shared.cell
+    }
+
+    fn shared_mut(&mut self) -> &mut Shared<T, B> {
+        Arc::get_mut(&mut self.shared).expect("Never fails")
+    }
+}
+impl<T, B> fmt::Debug for ThrottledWriter<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThrottledWriter")
+            .field("min_interval", &self.shared.min_interval)
+            .field("coalesce", &self.shared.coalesce)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Shared<T, B> {
+    cell: Arc<AtomicImmut<T, B>>,
+    min_interval: Duration,
+    coalesce: bool,
+    state: Mutex<State<T>>,
+}
+impl<T, B: Backend> Shared<T, B> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn store_pending(&self) {
+        loop {
+            let mut state = self.lock();
+            let now = Instant::now();
+            match state.next_store(self.min_interval) {
+                Some(next) if now < next => {
+                    drop(state);
+                    thread::sleep(next - now);
+                }
+                _ => {
+                    // The pending value may have been flushed meanwhile.
+                    if let Some(value) = state.pending.take() {
+                        state.last_store = Some(now);
+                        self.cell.store_arc(value);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+struct State<T> {
+    last_store: Option<Instant>,
+    pending: Option<Arc<T>>,
+}
+impl<T> State<T> {
+    fn next_store(&self, min_interval: Duration) -> Option<Instant> {
+        self.last_store.and_then(|t| t.checked_add(min_interval))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn excess_stores_are_dropped() {
+        let cell = Arc::new(AtomicImmut::new(0));
+        let writer =
+            ThrottledWriter::new(Arc::clone(&cell)).min_store_interval(Duration::from_secs(60));
+        assert!(writer.store(1));
+        assert!(!writer.store(2));
+        assert!(!writer.has_pending());
+        assert!(!writer.flush());
+        assert_eq!(*cell.load(), 1);
+
+        let unlimited = ThrottledWriter::new(Arc::clone(&cell));
+        assert!(unlimited.store(3));
+        assert!(unlimited.store(4));
+        assert_eq!(*cell.load(), 4);
+    }
+
+    #[test]
+    fn flush_works() {
+        let cell = Arc::new(AtomicImmut::new(0));
+        let writer = ThrottledWriter::new(Arc::clone(&cell))
+            .min_store_interval(Duration::from_secs(60))
+            .coalesce();
+        assert!(writer.store(1));
+        assert!(!writer.store(2));
+        assert!(!writer.store(3));
+        assert!(writer.has_pending());
+
+        assert!(writer.flush());
+        assert_eq!(*cell.load(), 3);
+        assert!(!writer.flush());
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The time of the last store into an `AtomicImmut` (tracked only if enabled).
+#[derive(Debug, Default)]
+pub(crate) struct Timestamp {
+    #[cfg(feature = "std")]
+    enabled: AtomicBool,
+    // Nanoseconds since `UNIX_EPOCH`.
+    #[cfg(feature = "std")]
+    nanos: AtomicU64,
+}
+impl Timestamp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking, regarding the current time as the time of the last store.
+    #[cfg(feature = "std")]
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+        self.touch();
+    }
+
+    #[cfg(feature = "std")]
+    pub fn touch(&self) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        // Concurrent stores may finish in any order, but the timestamp never goes back.
+        self.nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn touch(&self) {}
+
+    #[cfg(feature = "std")]
+    pub fn get(&self) -> Option<SystemTime> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let nanos = self.nanos.load(Ordering::Relaxed);
+        Some(UNIX_EPOCH + Duration::from_nanos(nanos))
+    }
+}
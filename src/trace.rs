@@ -0,0 +1,102 @@
+#[cfg(feature = "tracing")]
+use alloc::string::String;
+use core::fmt;
+
+/// The maximum length (in bytes) of the rendered values in the events.
+#[cfg(feature = "tracing")]
+const MAX_RENDERED_LEN: usize = 256;
+
+#[cfg(feature = "tracing")]
+type RenderFn<T> = fn(&T) -> String;
+
+/// Emits `tracing` events for the stores into an `AtomicImmut`.
+pub(crate) struct Tracer<T: ?Sized> {
+    #[cfg(feature = "tracing")]
+    render: Option<RenderFn<T>>,
+    #[cfg(not(feature = "tracing"))]
+    _value: core::marker::PhantomData<fn(&T)>,
+}
+impl<T: ?Sized> Tracer<T> {
+    #[cfg(feature = "tracing")]
+    pub fn new() -> Self {
+        Tracer { render: None }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub fn new() -> Self {
+        Tracer {
+            _value: core::marker::PhantomData,
+        }
+    }
+
+    /// Makes the events include the (truncated) `Debug` rendering of the new values.
+    #[cfg(feature = "tracing")]
+    pub fn render_values(&mut self)
+    where
+        T: fmt::Debug,
+    {
+        self.render = Some(render::<T>);
+    }
+
+    /// Returns `true` if the events need the new values.
+    #[cfg(feature = "tracing")]
+    pub fn needs_value(&self) -> bool {
+        self.render.is_some()
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub fn needs_value(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "tracing")]
+    pub fn stored(&self, name: Option<&str>, version: u64, new: Option<&T>) {
+        let name = name.unwrap_or("");
+        match (self.render, new) {
+            (Some(render), Some(new)) => {
+                ::tracing::debug!(name, version, value = %render(new), "AtomicImmut stored");
+            }
+            _ => {
+                ::tracing::debug!(name, version, "AtomicImmut stored");
+            }
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub fn stored(&self, _name: Option<&str>, _version: u64, _new: Option<&T>) {}
+}
+impl<T: ?Sized> fmt::Debug for Tracer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("render_values", &self.needs_value())
+            .finish()
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn render<T: ?Sized + fmt::Debug>(value: &T) -> String {
+    let mut s = ::alloc::format!("{:?}", value);
+    if s.len() > MAX_RENDERED_LEN {
+        let mut end = MAX_RENDERED_LEN;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+        s.push_str("...");
+    }
+    s
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_truncates_long_values() {
+        assert_eq!(render(&"foo"), "\"foo\"");
+
+        let s = render(&"ä".repeat(200));
+        assert!(s.ends_with("..."));
+        assert!(s.len() <= MAX_RENDERED_LEN + 3);
+    }
+}
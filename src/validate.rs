@@ -0,0 +1,105 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use primitive::Arc;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+/// The error returned when a value is rejected by the validator of an `AtomicImmut`.
+///
+/// See `AtomicImmut::with_validator`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ValidationError {
+    cause: Box<dyn Error + Send + Sync>,
+}
+#[cfg(feature = "std")]
+impl ValidationError {
+    /// Makes a new `ValidationError` instance.
+    pub fn new<E>(cause: E) -> Self
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        ValidationError {
+            cause: cause.into(),
+        }
+    }
+
+    /// Returns the reason why the value has been rejected.
+    pub fn cause(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.cause
+    }
+}
+#[cfg(feature = "std")]
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid value: {}", self.cause)
+    }
+}
+#[cfg(feature = "std")]
+impl Error for ValidationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.cause)
+    }
+}
+
+#[cfg(feature = "std")]
+type ValidatorFn<T> = Arc<dyn Fn(&T) -> Result<(), ValidationError> + Send + Sync>;
+
+/// The validator which every value stored into an `AtomicImmut` has to pass.
+pub(crate) struct Validator<T: ?Sized> {
+    #[cfg(feature = "std")]
+    f: Option<ValidatorFn<T>>,
+    #[cfg(not(feature = "std"))]
+    _value: core::marker::PhantomData<fn(&T)>,
+}
+impl<T: ?Sized> Validator<T> {
+    #[cfg(feature = "std")]
+    pub fn none() -> Self {
+        Validator { f: None }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn none() -> Self {
+        Validator {
+            _value: core::marker::PhantomData,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn new(f: ValidatorFn<T>) -> Self {
+        Validator { f: Some(f) }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn is_enabled(&self) -> bool {
+        self.f.is_some()
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn is_enabled(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "std")]
+    pub fn validate(&self, value: &T) -> Result<(), ValidationError> {
+        self.f.as_ref().map_or(Ok(()), |f| f(value))
+    }
+
+    /// Validates `value` for the stores which cannot report errors.
+    #[cfg(feature = "std")]
+    pub fn assert_valid(&self, value: &T) {
+        if let Err(e) = self.validate(value) {
+            panic!("{}", e);
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn assert_valid(&self, _value: &T) {}
+}
+impl<T: ?Sized> fmt::Debug for Validator<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Validator")
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
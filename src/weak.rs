@@ -0,0 +1,201 @@
+use core::mem::ManuallyDrop;
+use core::sync::atomic::Ordering;
+use primitive::{Arc, Weak};
+
+use backend::{Backend, SpinBackend};
+use lock::WriterLock;
+use primitive::{self, AtomicPtr};
+use AtomicImmut;
+
+/// A thread-safe pointer for weak references to immutable values.
+///
+/// This is the same as `AtomicImmut` except that it holds a `Weak` reference,
+/// so it does not keep the referred value alive.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use atomic_immut::AtomicImmutWeak;
+///
+/// let value = Arc::new(5);
+/// let weak = AtomicImmutWeak::new(Arc::downgrade(&value));
+/// assert_eq!(weak.upgrade(), Some(Arc::new(5)));
+///
+/// drop(value);
+/// assert_eq!(weak.upgrade(), None);
+/// ```
+#[derive(Debug)]
+pub struct AtomicImmutWeak<T> {
+    ptr: AtomicPtr<T>,
+    backend: SpinBackend,
+    writer: WriterLock,
+}
+impl<T> AtomicImmutWeak<T> {
+    /// Makes a new `AtomicImmutWeak` instance.
+    pub fn new(value: Weak<T>) -> Self {
+        let ptr = AtomicPtr::new(Weak::into_raw(value).cast_mut());
+        let backend = SpinBackend::new();
+        AtomicImmutWeak {
+            ptr,
+            backend,
+            writer: WriterLock::new(),
+        }
+    }
+
+    /// Makes a new `AtomicImmutWeak` instance which refers to the current value of `cell`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atomic_immut::{AtomicImmut, AtomicImmutWeak};
+    ///
+    /// let cell = AtomicImmut::new(5);
+    /// let weak = AtomicImmutWeak::downgrade_from(&cell);
+    /// assert_eq!(weak.upgrade().map(|v| *v), Some(5));
+    ///
+    /// cell.store(10);
+    /// assert_eq!(weak.upgrade(), None);
+    /// ```
+    pub fn downgrade_from<B: Backend>(cell: &AtomicImmut<T, B>) -> Self {
+        Self::new(Arc::downgrade(&cell.load()))
+    }
+
+    /// Loads the weak reference from this pointer.
+    pub fn load(&self) -> Weak<T> {
+        let (_guard, ptr) = self.backend.protect(&self.ptr);
+        let weak = ManuallyDrop::new(unsafe { Weak::from_raw(ptr) });
+        Weak::clone(&weak)
+    }
+
+    /// Attempts to upgrade the weak reference in this pointer to an `Arc`.
+    ///
+    /// Returns `None` if the referred value has been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use atomic_immut::AtomicImmutWeak;
+    ///
+    /// let weak = AtomicImmutWeak::default();
+    /// assert_eq!(weak.upgrade(), None::<Arc<usize>>);
+    ///
+    /// let value = Arc::new(5);
+    /// weak.store(Arc::downgrade(&value));
+    /// assert_eq!(weak.upgrade(), Some(value));
+    /// ```
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        let (_guard, ptr) = self.backend.protect(&self.ptr);
+        let weak = ManuallyDrop::new(unsafe { Weak::from_raw(ptr) });
+        weak.upgrade()
+    }
+
+    /// Stores a weak reference into this pointer.
+    pub fn store(&self, value: Weak<T>) {
+        self.swap(value);
+    }
+
+    /// Stores a weak reference into this pointer, returning the old one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Weak};
+    /// use atomic_immut::AtomicImmutWeak;
+    ///
+    /// let value = Arc::new(5);
+    /// let weak = AtomicImmutWeak::new(Weak::new());
+    /// let old = weak.swap(Arc::downgrade(&value));
+    /// assert_eq!(old.upgrade(), None);
+    /// assert_eq!(weak.upgrade(), Some(value));
+    /// ```
+    pub fn swap(&self, value: Weak<T>) -> Weak<T> {
+        let new = Weak::into_raw(value).cast_mut();
+        // See `AtomicImmutOption::replace`.
+        let _lock = self.writer.lock();
+        // `Acquire` makes the old reference, stored by another thread, safe to release.
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        self.backend.synchronize(old);
+        unsafe { Weak::from_raw(old) }
+    }
+}
+unsafe impl<T: Send + Sync> Send for AtomicImmutWeak<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicImmutWeak<T> {}
+impl<T> Drop for AtomicImmutWeak<T> {
+    fn drop(&mut self) {
+        let ptr = primitive::with_mut(&mut self.ptr, |p| *p);
+        let _ = unsafe { Weak::from_raw(ptr) };
+    }
+}
+impl<T> Default for AtomicImmutWeak<T> {
+    fn default() -> Self {
+        Self::new(Weak::new())
+    }
+}
+impl<T> From<Weak<T>> for AtomicImmutWeak<T> {
+    fn from(value: Weak<T>) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let value = Arc::new(vec![0, 1, 2]);
+        let weak = AtomicImmutWeak::new(Arc::downgrade(&value));
+        assert_eq!(Arc::weak_count(&value), 1);
+
+        let loaded = weak.load();
+        assert_eq!(Arc::weak_count(&value), 2);
+        assert!(Weak::ptr_eq(&loaded, &Arc::downgrade(&value)));
+        drop(loaded);
+
+        let upgraded = weak.upgrade().unwrap();
+        assert_eq!(Arc::strong_count(&value), 2);
+        drop(upgraded);
+
+        weak.store(Weak::new());
+        assert_eq!(Arc::weak_count(&value), 0);
+        assert_eq!(weak.upgrade(), None);
+    }
+
+    #[test]
+    fn concurrent_store_and_upgrade() {
+        let values = (0..8).map(Arc::new).collect::<Vec<_>>();
+        let weak = Arc::new(AtomicImmutWeak::default());
+        let thread_count = 4;
+        let barrier = Arc::new(Barrier::new(thread_count + 1));
+        let handles = (0..thread_count)
+            .map(|_| {
+                let weak = Arc::clone(&weak);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..1000 {
+                        if let Some(v) = weak.upgrade() {
+                            assert!(*v < 8);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        barrier.wait();
+        for _ in 0..100 {
+            for v in &values {
+                weak.store(Arc::downgrade(v));
+            }
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        drop(weak);
+        assert!(values.iter().all(|v| Arc::weak_count(v) == 0));
+    }
+}
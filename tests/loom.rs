@@ -0,0 +1,268 @@
+//! Model checking of the pointer protocol with `loom`.
+//!
+//! ```console
+//! $ RUSTFLAGS="--cfg loom" cargo test --test loom --release
+//! ```
+#![cfg(loom)]
+extern crate atomic_immut;
+extern crate loom;
+
+use atomic_immut::backend::{Backend, Backoff, SpinBackend};
+use atomic_immut::{AtomicImmut, AtomicImmutOption};
+use loom::cell::UnsafeCell;
+use loom::model::Builder;
+use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+
+/// Explores the interleavings of `f` with a bounded number of preemptions,
+/// which keeps the spinning waits of writers tractable.
+fn model<F>(f: F)
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    let mut builder = Builder::new();
+    if builder.preemption_bound.is_none() {
+        builder.preemption_bound = Some(3);
+    }
+    builder.check(f);
+}
+
+/// A value whose accesses are tracked by `loom`.
+///
+/// Reading a value concurrently with its release is reported as a causality violation.
+///
+/// Note that `std::sync::Arc` is not modeled,
+/// so values must be read through guards (`peek`) rather than cloned `Arc`s (`load`).
+struct Tracked {
+    n: UnsafeCell<usize>,
+}
+impl Tracked {
+    fn new(n: usize) -> Self {
+        Tracked {
+            n: UnsafeCell::new(n),
+        }
+    }
+
+    fn get(&self) -> usize {
+        self.n.with(|n| unsafe { *n })
+    }
+}
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.n.with_mut(|n| unsafe { *n = usize::MAX });
+    }
+}
+unsafe impl Sync for Tracked {}
+
+/// A value which counts how many times it has been dropped.
+struct Counted {
+    n: usize,
+    drops: Arc<AtomicUsize>,
+}
+impl Counted {
+    fn new(n: usize, drops: &Arc<AtomicUsize>) -> Self {
+        Counted {
+            n,
+            drops: Arc::clone(drops),
+        }
+    }
+}
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn peek_and_store() {
+    model(|| {
+        let v = Arc::new(AtomicImmut::new(Tracked::new(0)));
+        let reader = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || {
+                let n = v.peek().get();
+                assert!(n == 0 || n == 1);
+            })
+        };
+        v.store(Tracked::new(1));
+        reader.join().unwrap();
+        assert_eq!(v.peek().get(), 1);
+    });
+}
+
+#[test]
+fn peek_and_update() {
+    model(|| {
+        let v = Arc::new(AtomicImmut::new(Tracked::new(0)));
+        let reader = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || {
+                let first = v.peek().get();
+                let second = v.peek().get();
+                assert!(first <= second && second <= 2);
+            })
+        };
+        v.update(|x| Tracked::new(x.get() + 1));
+        v.update(|x| Tracked::new(x.get() + 1));
+        reader.join().unwrap();
+        assert_eq!(v.peek().get(), 2);
+    });
+}
+
+#[test]
+fn peek_and_concurrent_stores() {
+    model_spinning(|| {
+        let backend = SpinBackend::with_backoff(Backoff::new().spin_limit(0));
+        let v = Arc::new(AtomicImmut::with_backend(Tracked::new(0), backend));
+        let reader = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || {
+                let n = v.peek().get();
+                assert!(n <= 2);
+            })
+        };
+        let writer = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || v.store(Tracked::new(1)))
+        };
+        v.store(Tracked::new(2));
+        reader.join().unwrap();
+        writer.join().unwrap();
+        assert_ne!(v.peek().get(), 0);
+    });
+}
+
+#[test]
+fn load_and_store() {
+    model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let v = Arc::new(AtomicImmut::new(Counted::new(0, &drops)));
+        let reader = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || v.load().n)
+        };
+        v.store(Counted::new(1, &drops));
+        let n = reader.join().unwrap();
+        assert!(n == 0 || n == 1);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    });
+}
+
+#[test]
+fn concurrent_updates() {
+    model(|| {
+        let v = Arc::new(AtomicImmut::new(0));
+        let writer = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || v.update(|n| n + 1))
+        };
+        v.update(|n| n + 1);
+        writer.join().unwrap();
+        assert_eq!(*v.load(), 2);
+    });
+}
+
+#[test]
+fn compare_and_swap_once() {
+    model(|| {
+        let v = Arc::new(AtomicImmut::new(0));
+        let current = v.load();
+        let writer = {
+            let v = Arc::clone(&v);
+            let current = current.clone();
+            thread::spawn(move || v.compare_and_swap(&current, 1).is_ok())
+        };
+        let ok = v.compare_and_swap(&current, 2).is_ok();
+        assert!(ok != writer.join().unwrap());
+        assert_ne!(*v.load(), 0);
+    });
+}
+
+#[test]
+fn drop_while_loaded() {
+    model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let v = Arc::new(AtomicImmut::new(Counted::new(0, &drops)));
+        let reader = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || {
+                let loaded = v.load();
+                drop(v);
+                loaded.n
+            })
+        };
+        drop(v);
+        assert_eq!(reader.join().unwrap(), 0);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    });
+}
+
+#[test]
+fn option_take_and_load() {
+    model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let v = Arc::new(AtomicImmutOption::new(Some(Counted::new(0, &drops))));
+        let reader = {
+            let v = Arc::clone(&v);
+            thread::spawn(move || v.load().map(|x| x.n))
+        };
+        drop(v.take());
+        assert!(matches!(reader.join().unwrap(), None | Some(0)));
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        assert!(v.is_none());
+    });
+}
+
+/// Replaces the value of `ptr` and releases the old one after a grace period of `backend`.
+fn retire_with<B: Backend>(backend: &B, ptr: &AtomicPtr<Tracked>, n: usize) {
+    let old = ptr.swap(Box::into_raw(Box::new(Tracked::new(n))), Ordering::AcqRel);
+    backend.synchronize(old);
+    drop(unsafe { Box::from_raw(old) });
+}
+
+/// One reader against two writers whose grace periods overlap.
+fn one_reader_two_writers<B>(backend: B)
+where
+    B: Backend + Send + Sync + 'static,
+{
+    let backend = Arc::new(backend);
+    let ptr = Arc::new(AtomicPtr::new(Box::into_raw(Box::new(Tracked::new(0)))));
+    let reader = {
+        let backend = Arc::clone(&backend);
+        let ptr = Arc::clone(&ptr);
+        thread::spawn(move || {
+            let (_guard, p) = backend.protect(&ptr);
+            assert!(unsafe { (*p).get() } <= 2);
+        })
+    };
+    let writer = {
+        let backend = Arc::clone(&backend);
+        let ptr = Arc::clone(&ptr);
+        thread::spawn(move || retire_with(&*backend, &ptr, 1))
+    };
+    retire_with(&*backend, &ptr, 2);
+    reader.join().unwrap();
+    writer.join().unwrap();
+    drop(unsafe { Box::from_raw(ptr.load(Ordering::Relaxed)) });
+}
+
+/// Like `model`, but explores fewer preemptions of longer executions (i.e., spinning writers).
+fn model_spinning<F>(f: F)
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    let mut builder = Builder::new();
+    if builder.preemption_bound.is_none() {
+        builder.preemption_bound = Some(2);
+    }
+    builder.max_branches = 100_000;
+    builder.check(f);
+}
+
+#[test]
+fn spin_backend_concurrent_grace_periods() {
+    model_spinning(|| {
+        one_reader_two_writers(SpinBackend::with_backoff(Backoff::new().spin_limit(0)))
+    });
+}
@@ -0,0 +1,100 @@
+//! Small tests of the raw pointer lifecycle, sized to run under Miri.
+//!
+//! ```console
+//! $ MIRIFLAGS="-Zmiri-strict-provenance" cargo +nightly miri test --test miri
+//! ```
+//!
+//! They also run as ordinary tests.
+#![cfg(not(loom))]
+extern crate atomic_immut;
+
+use atomic_immut::backend::{SingleThreadBackend, StdBackend};
+use atomic_immut::{AtomicImmut, AtomicImmutOption, AtomicImmutWeak};
+use std::sync::{Arc, Weak};
+use std::thread;
+
+#[test]
+fn store_swap_and_drop() {
+    let v = AtomicImmut::new(vec![1]);
+    let old = v.load();
+    v.store(vec![2]);
+    assert_eq!(*v.swap(vec![3]), [2]);
+    assert_eq!(*old, [1]);
+
+    let current = v.load();
+    assert!(v.compare_and_swap(&current, vec![4]).is_ok());
+    assert!(v.compare_and_swap(&current, vec![5]).is_err());
+    assert_eq!(*v.peek(), [4]);
+}
+
+#[test]
+fn raw_pointer_round_trip() {
+    let v = AtomicImmut::new(vec![1]);
+    let raw = v.load_raw();
+    assert_eq!(v.as_raw(), raw);
+    v.store(vec![2]);
+
+    let restored = unsafe { AtomicImmut::from_raw(raw) };
+    assert_eq!(*restored.load(), [1]);
+    assert_eq!(Arc::strong_count(&restored.load()), 2);
+}
+
+#[test]
+fn unsized_values() {
+    let v: AtomicImmut<str> = AtomicImmut::from_arc(Arc::from("foo"));
+    v.store_arc(Arc::from("bar"));
+    assert_eq!(&*v.load(), "bar");
+}
+
+#[test]
+fn concurrent_readers_and_writers() {
+    let v = Arc::new(AtomicImmut::new(0));
+    let handles = (0..2)
+        .map(|_| {
+            let v = Arc::clone(&v);
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    v.update(|x| x + 1);
+                    let _ = *v.peek();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(*v.load(), 20);
+}
+
+#[test]
+fn other_backends() {
+    let v = AtomicImmut::with_backend(String::from("a"), StdBackend::new());
+    v.store(String::from("b"));
+    assert_eq!(*v.load(), "b");
+
+    let v = AtomicImmut::with_backend(String::from("a"), SingleThreadBackend::new());
+    v.store(String::from("b"));
+    assert_eq!(*v.peek(), "b");
+}
+
+#[test]
+fn option_take_and_set() {
+    let v = AtomicImmutOption::none();
+    assert!(v.set_if_none(String::from("a")));
+    assert!(!v.set_if_none(String::from("b")));
+    assert_eq!(v.take().map(|s| (*s).clone()), Some(String::from("a")));
+    assert!(v.is_none());
+}
+
+#[test]
+fn weak_with_dangling_sentinel() {
+    // `Weak::new` makes a dangling pointer, which must survive the round trip as is.
+    let weak = AtomicImmutWeak::new(Weak::new());
+    assert!(weak.upgrade().is_none());
+
+    let value = Arc::new(5);
+    weak.store(Arc::downgrade(&value));
+    assert_eq!(weak.upgrade(), Some(Arc::clone(&value)));
+    drop(value);
+    assert!(weak.upgrade().is_none());
+}